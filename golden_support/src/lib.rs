@@ -0,0 +1,87 @@
+//! Helpers shared by the golden snapshot tests of pulstar and profile, used by both crates as a dev-dependency.
+//!
+//! The expected tables are comma separated, with a header line naming the columns and lines starting
+//! with `#` as comments. Every column of a table is compared with the output within its [Tolerance].
+use polars::prelude::*;
+use std::path::Path;
+
+/// Tolerance for a single column of the output. A value passes when
+/// `|actual - expected| <= absolute + relative * |expected|`.
+pub struct Tolerance {
+    pub column: &'static str,
+    pub absolute: f64,
+    pub relative: f64,
+}
+
+/// Reads a comma separated table with a header line. Lines starting with `#` are comments.
+pub fn read_expected_table(path: &Path) -> (Vec<String>, Vec<Vec<f64>>) {
+    let contents = std::fs::read_to_string(path)
+        .unwrap_or_else(|_| panic!("Could not read {}", path.display()));
+    let mut lines = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'));
+    let header: Vec<String> = lines
+        .next()
+        .expect("the expected table has no header")
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .collect();
+    let mut columns: Vec<Vec<f64>> = vec![Vec::new(); header.len()];
+    for line in lines {
+        for (n, value) in line.split(',').enumerate() {
+            columns[n].push(value.trim().parse().expect("not a number in the expected table"));
+        }
+    }
+    (header, columns)
+}
+
+/// Writes the columns of `df` listed in `tolerances` as a comma separated table, after the comment line `comment`.
+pub fn write_expected_table(path: &Path, df: &DataFrame, tolerances: &[Tolerance], comment: &str) {
+    let columns: Vec<Vec<f64>> = tolerances
+        .iter()
+        .map(|tolerance| column_as_vec(df, tolerance.column))
+        .collect();
+    let mut contents = format!("# {}\n", comment);
+    let header: Vec<&str> = tolerances.iter().map(|tolerance| tolerance.column).collect();
+    contents.push_str(&header.join(","));
+    contents.push('\n');
+    for row in 0..df.height() {
+        let values: Vec<String> = columns.iter().map(|column| format!("{:e}", column[row])).collect();
+        contents.push_str(&values.join(","));
+        contents.push('\n');
+    }
+    std::fs::write(path, contents).expect("unable to write the expected table");
+}
+
+/// Returns a column of `df` as a vector, it panics if the column is missing or isn't a float column.
+pub fn column_as_vec(df: &DataFrame, name: &str) -> Vec<f64> {
+    df.column(name)
+        .unwrap_or_else(|_| panic!("column {} is missing from the output", name))
+        .f64()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Compares the columns of `df` listed in `tolerances` with the expected table at `path`,
+/// and panics with every value out of tolerance.
+pub fn assert_matches_expected_table(df: &DataFrame, path: &Path, tolerances: &[Tolerance]) {
+    let (header, expected) = read_expected_table(path);
+    assert_eq!(df.height(), expected[0].len(), "number of rows differs from {}", path.display());
+
+    let mut failures: Vec<String> = Vec::new();
+    for tolerance in tolerances.iter() {
+        let index = header
+            .iter()
+            .position(|name| name == tolerance.column)
+            .unwrap_or_else(|| panic!("column {} is missing from the expected table", tolerance.column));
+        let actual = column_as_vec(df, tolerance.column);
+        for (row, (a, e)) in actual.iter().zip(expected[index].iter()).enumerate() {
+            if (a - e).abs() > tolerance.absolute + tolerance.relative * e.abs() {
+                failures.push(format!("{} row {}: expected {:e}, got {:e}", tolerance.column, row, e, a));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{} values out of tolerance:\n{}", failures.len(), failures.join("\n"));
+}
@@ -0,0 +1,83 @@
+//! Benchmarks of the interpolation of the intensity grids over the wavelengths of a surface cell.
+//!
+//! Run with `cargo bench -p profile`. The Joris-like grids are written into a temporary directory, sampled every 0.005 nm.
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use profile::provider::{IntensityProvider, LocalAtmosphere};
+use profile::{ProfileConfig, SpectralGrid};
+use std::hint::black_box;
+use std::path::PathBuf;
+use temp_name_lib::interpolation::BoundsHandling;
+
+const GRIDS: [(u32, u32); 4] = [(20000, 35), (20000, 45), (26000, 35), (26000, 45)];
+
+/// Writes the grids of a lattice with an absorption line and returns the directory.
+fn write_grids() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("profile_bench_intensities_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let lines: Vec<String> = (0..=400)
+        .map(|row| {
+            let wavelength = 413.0 + 0.005 * row as f64;
+            let line = 1.0 - 0.6 * (-0.5 * ((wavelength - 414.0) / 0.05).powi(2)).exp();
+            let law = [1.0, -0.45, 0.05, 0.0];
+            let specific = law.iter().map(|c| format!("{:.12e}", c * line));
+            let continuum = law.iter().map(|c| format!("{:.12e}", c));
+            std::iter::once(format!("{:.8e}", wavelength)).chain(specific).chain(continuum).collect::<Vec<String>>().join(" ")
+        })
+        .collect();
+    for (temperature, log_gravity) in GRIDS {
+        std::fs::write(dir.join(format!("t{}g{}.txt", temperature, log_gravity)), lines.join("\n") + "\n").unwrap();
+    }
+    dir
+}
+
+/// Loads the grids for the observed wavelengths `[413.5, 414.5]` sampled every `step`.
+fn spectral_grid(dir: &std::path::Path, interpolation: &str, step: f64) -> (ProfileConfig, SpectralGrid) {
+    let mut input = format!(
+        "path_to_grids = \"{}/\"\ninterpolation = {{wavelength = \"{}\"}}\n\n[wavelength_range]\nstart = 413.5\nend = 414.5\nstep = {}\n",
+        dir.display(),
+        interpolation,
+        step
+    );
+    for (temperature, log_gravity) in GRIDS {
+        input += &format!(
+            "[[intensity_grids]]\nJoris = {{temperature = {:.1}, log_gravity = {:.1}, filename = \"t{}g{}.txt\"}}\n",
+            temperature as f64,
+            log_gravity as f64 / 10.0,
+            temperature,
+            log_gravity
+        );
+    }
+    let config: ProfileConfig = toml::from_str(&input).unwrap();
+    let grid = config.init_spectral_grid_from_csv(1.001, 0.999).unwrap();
+    (config, grid)
+}
+
+fn intensities(c: &mut Criterion) {
+    let dir = write_grids();
+    let mut group = c.benchmark_group("intensities");
+    for interpolation in ["Linear", "CatmullRom"] {
+        // Observed wavelengths sparser and denser than the ones of the grids.
+        for step in [0.01, 0.001] {
+            let (config, grid) = spectral_grid(&dir, interpolation, step);
+            // The wavelengths of a cell moving away from the observer.
+            let wavelengths: Vec<f64> =
+                config.wavelength_range.get_wavelength_vector().unwrap().iter().map(|wavelength| wavelength * 1.0002).collect();
+            let atmosphere = LocalAtmosphere { temperature: 22000.0, log_gravity: 4.0, metallicity: None, mu: 0.55 };
+            let mut scratch = grid.new_scratch();
+            let (mut specific, mut continuum) = (vec![0.0; wavelengths.len()], vec![0.0; wavelengths.len()]);
+            let id = BenchmarkId::new(interpolation, format!("{} wavelengths", wavelengths.len()));
+            group.bench_function(id, |b| {
+                b.iter(|| {
+                    grid.intensities(black_box(&atmosphere), &wavelengths, BoundsHandling::Error, &mut scratch, &mut specific, &mut continuum)
+                        .unwrap();
+                    black_box(&specific);
+                })
+            });
+        }
+    }
+    group.finish();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+criterion_group!(benches, intensities);
+criterion_main!(benches);
@@ -0,0 +1,346 @@
+use crate::IntensityGrid;
+use crate::error::ProfileError;
+use crate::provider::{IntensityProvider,LocalAtmosphere};
+use serde::Deserialize;
+use temp_name_lib::interpolation::{BoundsHandling,RegularGridInterpolator};
+use ndarray::{ArrayD,ArrayView1,s};
+use temp_name_lib::type_def::{CLIGHT,PI};
+use temp_name_lib::utils::MathErrors;
+
+/// Planck constant in SI-units (J s).
+const PLANCK:f64 = 6.62607015e-34;
+/// Boltzmann constant in SI-units (J/K).
+const BOLTZMANN:f64 = 1.380649e-23;
+
+/// An atmosphere described by analytic laws instead of intensity grid files, see [IntensityGrid::Analytic].
+///
+/// The continuum intensity is the Planck function at the effective temperature of the cell times a limb-darkening law,
+/// `I_c(λ,µ) = B_λ(T_eff) L(µ)`, and the specific intensity has an intrinsic line profile, `I(λ,µ) = I_c(λ,µ) (1 - d φ(λ-λ_0)/φ(0))`,
+/// where `d` is the central depth of the line. Here µ is the cosine of the angle between the normal of the cell and the line of sight.
+///
+/// On the toml file it's given as e.g.
+/// ```toml
+/// [[intensity_grids]]
+/// Analytic = {law = "Quadratic", coefficients = {Fixed = [0.3, 0.2]}, line = {center = 413.95, profile = {Gaussian = {sigma = 0.005}}, strength = {Depth = 0.6}}}
+/// ```
+#[derive(Deserialize,Debug,PartialEq,Clone)]
+pub struct AnalyticAtmosphere{
+    /// The limb-darkening law of the continuum.
+    pub law: LimbDarkeningLaw,
+    /// The coefficients of the limb-darkening law.
+    pub coefficients: LimbDarkeningCoefficients,
+    /// The intrinsic profile of the spectral line.
+    pub line: SpectralLine,
+}
+
+/// The limb-darkening laws `L(µ) = I(µ)/I(1)`.
+#[derive(Deserialize,Debug,PartialEq,Clone,Copy)]
+pub enum LimbDarkeningLaw{
+    /// `1 - u (1-µ)`, one coefficient.
+    Linear,
+    /// `1 - a (1-µ) - b (1-µ)²`, two coefficients.
+    Quadratic,
+    /// `1 - c (1-µ) - d (1-√µ)`, two coefficients.
+    SquareRoot,
+    /// Claret's law `1 - Σ a_k (1-µ^(k/2))` for k = 1..4, four coefficients.
+    Claret,
+}
+
+/// Where the coefficients of the limb-darkening law come from.
+#[derive(Deserialize,Debug,PartialEq,Clone)]
+pub enum LimbDarkeningCoefficients{
+    /// The same coefficients for every cell of the star.
+    Fixed(Vec<f64>),
+    /// The name of a file in `path_to_grids` with a table of coefficients by T_eff and log g, see [CoefficientTable].
+    Table(String),
+}
+
+/// The intrinsic profile of the spectral line.
+#[derive(Deserialize,Debug,PartialEq,Clone)]
+pub struct SpectralLine{
+    /// Central wavelength of the line at rest in nm.
+    pub center: f64,
+    /// The shape of the line.
+    pub profile: LineProfile,
+    /// How strong the line is.
+    pub strength: LineStrength,
+}
+
+/// The shape of the line, the widths are given in nm.
+#[derive(Deserialize,Debug,PartialEq,Clone,Copy)]
+pub enum LineProfile{
+    /// A Gaussian profile with standard deviation `sigma`.
+    Gaussian{sigma:f64},
+    /// A Voigt profile with Gaussian standard deviation `sigma` and Lorentzian half width at half maximum `gamma`,
+    /// evaluated with the pseudo-Voigt approximation of Thompson, Cox & Hastings (1987).
+    Voigt{sigma:f64, gamma:f64},
+}
+
+/// The strength of the line.
+#[derive(Deserialize,Debug,PartialEq,Clone,Copy)]
+pub enum LineStrength{
+    /// Central depth of the line relative to the continuum, between 0 and 1.
+    Depth(f64),
+    /// Equivalent width of the line in nm, the central depth follows from the profile.
+    EquivalentWidth(f64),
+}
+
+impl LimbDarkeningLaw{
+    /// Returns the number of coefficients of the law.
+    pub fn ncoefficients(&self)->usize{
+        match self{
+            Self::Linear => {1}
+            Self::Quadratic | Self::SquareRoot => {2}
+            Self::Claret => {4}
+        }
+    }
+
+    /// This function evaluates the limb-darkening law.
+    /// ### Arguments:
+    /// * `coefficients` - the coefficients of the law, there must be [LimbDarkeningLaw::ncoefficients] of them.
+    /// * `mu` - the cosine of the angle between the normal of the surface and the line of sight.
+    /// ### Returns:
+    /// * `I(µ)/I(1)`.
+    pub fn evaluate(&self, coefficients:&[f64], mu:f64)->f64{
+        match self{
+            Self::Linear => {1.0 - coefficients[0]*(1.0-mu)}
+            Self::Quadratic => {1.0 - coefficients[0]*(1.0-mu) - coefficients[1]*(1.0-mu).powi(2)}
+            Self::SquareRoot => {1.0 - coefficients[0]*(1.0-mu) - coefficients[1]*(1.0-mu.sqrt())}
+            Self::Claret => {
+                1.0 - coefficients.iter().enumerate()
+                    .map(|(k,a)| a*(1.0 - mu.powf((k+1) as f64/2.0)))
+                    .sum::<f64>()
+            }
+        }
+    }
+}
+
+impl LineProfile{
+    /// Returns the profile normalized to unit area at a distance `offset` (in nm) from the centre of the line.
+    pub fn evaluate(&self, offset:f64)->f64{
+        match *self{
+            Self::Gaussian{sigma} => {gaussian(offset, sigma)}
+            Self::Voigt{sigma, gamma} => {
+                // Thompson, Cox & Hastings (1987): mixture of a Gaussian and a Lorentzian with the same full width at half maximum.
+                let fwhm_gauss = 2.0*sigma*(2.0*2f64.ln()).sqrt();
+                let fwhm_lorentz = 2.0*gamma;
+                let fwhm = (fwhm_gauss.powi(5) + 2.69269*fwhm_gauss.powi(4)*fwhm_lorentz + 2.42843*fwhm_gauss.powi(3)*fwhm_lorentz.powi(2)
+                    + 4.47163*fwhm_gauss.powi(2)*fwhm_lorentz.powi(3) + 0.07842*fwhm_gauss*fwhm_lorentz.powi(4) + fwhm_lorentz.powi(5)).powf(0.2);
+                let ratio = fwhm_lorentz/fwhm;
+                let eta = 1.36603*ratio - 0.47719*ratio.powi(2) + 0.11116*ratio.powi(3);
+                let half_width = fwhm/2.0;
+                let lorentzian = half_width/(PI*(offset.powi(2) + half_width.powi(2)));
+                eta*lorentzian + (1.0-eta)*gaussian(offset, fwhm/(2.0*(2.0*2f64.ln()).sqrt()))
+            }
+        }
+    }
+
+    /// Returns the widths of the profile, which must be positive.
+    fn widths(&self)->Vec<f64>{
+        match *self{
+            Self::Gaussian{sigma} => {vec![sigma]}
+            Self::Voigt{sigma, gamma} => {vec![sigma, gamma]}
+        }
+    }
+}
+
+/// Gaussian normalized to unit area.
+fn gaussian(offset:f64, sigma:f64)->f64{
+    (-0.5*(offset/sigma).powi(2)).exp()/(sigma*(2.0*PI).sqrt())
+}
+
+impl SpectralLine{
+    /// Returns the central depth of the line relative to the continuum.
+    /// For a given equivalent width `W`, the depth is `W φ(0)` where `φ` is the profile normalized to unit area.
+    pub fn depth(&self)->f64{
+        match self.strength{
+            LineStrength::Depth(depth) => {depth}
+            LineStrength::EquivalentWidth(width) => {width*self.profile.evaluate(0.0)}
+        }
+    }
+
+    /// Returns the fraction of the continuum absorbed at a wavelength in nm, `d φ(λ-λ_0)/φ(0)`.
+    pub fn absorption(&self, wavelength:f64)->f64{
+        self.depth()*self.profile.evaluate(wavelength-self.center)/self.profile.evaluate(0.0)
+    }
+}
+
+/// This function evaluates the Planck function.
+/// ### Arguments:
+/// * `wavelength` - the wavelength in nm.
+/// * `temperature` - the temperature in K.
+/// ### Returns:
+/// * `B_λ(T)` in SI-units (W m⁻³ sr⁻¹).
+pub fn planck(wavelength:f64, temperature:f64)->f64{
+    let wavelength = wavelength*1.0e-9;
+    2.0*PLANCK*CLIGHT.powi(2)/wavelength.powi(5)/((PLANCK*CLIGHT/(wavelength*BOLTZMANN*temperature)).exp() - 1.0)
+}
+
+/// A table of the coefficients of a limb-darkening law on a (T_eff, log g) lattice.
+///
+/// The file has a row per node with the effective temperature, log g and the coefficients, separated by spaces.
+/// Lines starting with `#` are comments. The nodes must form a complete lattice, and the coefficients are interpolated bilinearly
+/// with a [RegularGridInterpolator].
+#[derive(Debug,Clone,PartialEq)]
+pub struct CoefficientTable{
+    /// The coefficients at the nodes on the (T_eff, log g) plane.
+    interpolator: RegularGridInterpolator,
+}
+
+impl CoefficientTable{
+    /// This function reads a table of coefficients.
+    /// ### Arguments:
+    /// * `path` - the path to the file.
+    /// * `ncoefficients` - the number of coefficients of the limb-darkening law.
+    /// ### Returns:
+    /// * `Ok(CoefficientTable)` - the table.
+    /// * `Err(ProfileError::Io)` - if the file could not be read.
+    /// * `Err(ProfileError::GridFormat)` - if a row can't be parsed, has a wrong number of coefficients, or the nodes don't form a complete lattice
+    ///   with at least two temperatures and log g.
+    pub fn from_file(path:&str, ncoefficients:usize)->Result<Self,ProfileError>{
+        let contents = std::fs::read_to_string(path)?;
+        let invalid = |reason:String| ProfileError::GridFormat(format!("{}: {}", path, reason));
+
+        let mut rows:Vec<(f64,f64,Vec<f64>)> = Vec::new();
+        for (n,line) in contents.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#'){
+                continue
+            }
+            let values = line.split_whitespace().map(|value| value.parse::<f64>()).collect::<Result<Vec<f64>,_>>()
+                .map_err(|e| invalid(format!("line {}: {}", n+1, e)))?;
+            if values.len() != ncoefficients+2{
+                return Err(invalid(format!("line {} has {} values, expected T_eff, log g and {} coefficients", n+1, values.len(), ncoefficients)))
+            }
+            rows.push((values[0], values[1], values[2..].to_vec()));
+        }
+
+        let nodes = |values:Vec<f64>| {let mut values = values; values.sort_by(f64::total_cmp); values.dedup(); values};
+        let temperatures = nodes(rows.iter().map(|row| row.0).collect());
+        let log_gravities = nodes(rows.iter().map(|row| row.1).collect());
+        if temperatures.len() < 2 || log_gravities.len() < 2 || rows.len() != temperatures.len()*log_gravities.len(){
+            return Err(invalid(format!("the {} rows don't form a complete lattice of at least two temperatures and two log g", rows.len())))
+        }
+        let mut coefficients = ArrayD::from_elem(vec![temperatures.len(),log_gravities.len(),ncoefficients], f64::NAN);
+        for (temperature, log_gravity, row_coefficients) in rows{
+            let i = temperatures.partition_point(|node| *node < temperature);
+            let j = log_gravities.partition_point(|node| *node < log_gravity);
+            let mut node = coefficients.slice_mut(s![i,j,..]);
+            if !node[0].is_nan(){
+                return Err(invalid(format!("the node T_eff = {:.1} K, log g = {:.4} is repeated", temperature, log_gravity)))
+            }
+            node.assign(&ArrayView1::from(&row_coefficients));
+        }
+        let interpolator = RegularGridInterpolator::new(vec![temperatures, log_gravities], coefficients)
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(Self{interpolator})
+    }
+
+    /// Returns `true` if the point lies within the table.
+    pub fn contains(&self, temperature:f64, log_gravity:f64)->bool{
+        self.interpolator.contains(&[temperature, log_gravity])
+    }
+
+    /// This function interpolates the coefficients bilinearly.
+    /// ### Arguments:
+    /// * `temperature` - effective temperature in K.
+    /// * `log_gravity` - logarithm of the surface gravity.
+    /// * `bounds_handling` - what to do if the point lies outside of the table.
+    /// ### Returns:
+    /// * `Ok(Vec<f64>)` - the coefficients.
+    /// * `Err(MathErrors::OutOfBounds)` - if the point lies outside of the table and `bounds_handling` is [BoundsHandling::Error].
+    pub fn coefficients(&self, temperature:f64, log_gravity:f64, bounds_handling:BoundsHandling)->Result<Vec<f64>,MathErrors>{
+        self.interpolator.interpolate(&[temperature, log_gravity], bounds_handling)
+    }
+}
+
+impl AnalyticAtmosphere{
+    /// This function checks the parameters of the atmosphere and returns the description of every problem found.
+    pub fn check(&self)->Vec<String>{
+        let mut problems:Vec<String> = Vec::new();
+        if let LimbDarkeningCoefficients::Fixed(coefficients) = &self.coefficients
+            && coefficients.len() != self.law.ncoefficients(){
+            problems.push(format!("the {:?} limb-darkening law needs {} coefficients, {} given",
+                self.law, self.law.ncoefficients(), coefficients.len()));
+        }
+        if self.line.profile.widths().iter().any(|width| *width <= 0.0){
+            problems.push(format!("the widths of the line profile must be positive, {:?} given", self.line.profile));
+        }
+        else{
+            let depth = self.line.depth();
+            if !(0.0..=1.0).contains(&depth){
+                problems.push(format!("the central depth of the line must be between 0 and 1, it is {}", depth));
+            }
+        }
+        problems
+    }
+
+    /// This function prepares the atmosphere for the integration, reading the table of coefficients if there's one.
+    /// ### Arguments:
+    /// * `path_to_grids` - the directory of the table of coefficients.
+    /// ### Returns:
+    /// * `Ok(AnalyticModel)` - the atmosphere ready to be evaluated.
+    /// * `Err(ProfileError)` - if the parameters are not valid (see [AnalyticAtmosphere::check]) or the table could not be read.
+    pub fn load(&self, path_to_grids:&str)->Result<AnalyticModel,ProfileError>{
+        let problems = self.check();
+        if !problems.is_empty(){
+            return Err(ProfileError::InvalidConfig(problems.join(", ")))
+        }
+        let table = match &self.coefficients{
+            LimbDarkeningCoefficients::Fixed(_) => {None}
+            LimbDarkeningCoefficients::Table(filename) => {
+                Some(CoefficientTable::from_file(&format!("{}{}", path_to_grids, filename), self.law.ncoefficients())?)
+            }
+        };
+        Ok(AnalyticModel{depth: self.line.depth(), atmosphere: self.clone(), table})
+    }
+}
+
+/// An [AnalyticAtmosphere] ready to be evaluated on the surface cells.
+#[derive(Debug,Clone)]
+pub struct AnalyticModel{
+    atmosphere: AnalyticAtmosphere,
+    table: Option<CoefficientTable>,
+    depth: f64,
+}
+
+impl IntensityProvider for AnalyticModel{
+    type Scratch = ();
+
+    fn new_scratch(&self){}
+
+    /// Returns `true` if the coefficients of the atmosphere don't need to be clamped or extrapolated, i.e. they're fixed or the atmosphere lies within the table.
+    fn covers(&self, atmosphere:&LocalAtmosphere)->bool{
+        self.table.as_ref().is_none_or(|table| table.contains(atmosphere.temperature, atmosphere.log_gravity))
+    }
+
+    /// This function evaluates the specific and continuum intensities, see [AnalyticAtmosphere].
+    /// ### Returns:
+    /// * `Err(MathErrors::OutOfBounds)` - if the atmosphere lies outside of the table of coefficients and `bounds_handling` is [BoundsHandling::Error].
+    fn intensities(&self, atmosphere:&LocalAtmosphere, wavelengths:&[f64], bounds_handling:BoundsHandling,
+        _scratch:&mut (), specific:&mut [f64], continuum:&mut [f64])->Result<(),MathErrors>{
+        let (temperature, mu) = (atmosphere.temperature, atmosphere.mu);
+        let limb_darkening = match (&self.table, &self.atmosphere.coefficients){
+            (Some(table), _) => {self.atmosphere.law.evaluate(&table.coefficients(temperature, atmosphere.log_gravity, bounds_handling)?, mu)}
+            (None, LimbDarkeningCoefficients::Fixed(coefficients)) => {self.atmosphere.law.evaluate(coefficients, mu)}
+            (None, LimbDarkeningCoefficients::Table(_)) => {unreachable!("the table is read by AnalyticAtmosphere::load")}
+        };
+        let line = &self.atmosphere.line;
+        let central_profile = line.profile.evaluate(0.0);
+        for (n,wavelength) in wavelengths.iter().enumerate(){
+            continuum[n] = planck(*wavelength, temperature)*limb_darkening;
+            specific[n] = continuum[n]*(1.0 - self.depth*line.profile.evaluate(wavelength-line.center)/central_profile);
+        }
+        Ok(())
+    }
+}
+
+impl IntensityGrid{
+    /// Returns the analytic atmosphere if the grid is [IntensityGrid::Analytic].
+    pub fn analytic(&self)->Option<&AnalyticAtmosphere>{
+        match self{
+            Self::Analytic(atmosphere) => {Some(atmosphere)}
+            _ => {None}
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{IntensityGrid, ProfileConfig};
+use crate::error::ProfileError;
+use polars::prelude::*;
+use std::fs::File;
+use std::path::Path;
+
+/// Name of the index of the intensity grids that is written into the grid directory.
+pub const CATALOGUE_FILENAME: &str = "grid_catalogue.parquet";
+
+impl IntensityGrid{
+    /// This function recognizes an intensity grid out of its file name, the name encodes the parameters of the atmosphere.
+    ///
+    /// The recognized conventions are:
+    /// * Nadya's grids: `lp00000_21000_03500_0200..imu` - T_eff in K, log g in units of 10⁻³ and the metallicity in units of 10⁻⁴.
+    /// * Joris' grids: `t21000g35.txt` - T_eff in K and log g in units of 10⁻¹.
+    /// ### Arguments:
+    /// * `filename` - the name of the file, without the directory.
+    /// ### Returns:
+    /// * `Some(IntensityGrid)` - if the name follows one of the conventions.
+    /// * `None` - otherwise.
+    pub fn from_filename(filename:&str)->Option<Self>{
+        parse_nadya_filename(filename).or_else(|| parse_joris_filename(filename))
+    }
+}
+
+/// Parses the name of one of Nadya's grids, e.g. `lp00000_21000_03500_0200..imu`.
+fn parse_nadya_filename(filename:&str)->Option<IntensityGrid>{
+    let stem = filename.strip_suffix(".imu")?.trim_end_matches('.');
+    let fields:Vec<&str> = stem.split('_').collect();
+    if fields.len() != 4 || !fields[0].starts_with("lp"){
+        return None
+    }
+    let temperature = fields[1].parse::<u32>().ok()? as f64;
+    let log_gravity = fields[2].parse::<u32>().ok()? as f64 / 1000.0;
+    let metalicity = fields[3].parse::<u32>().ok()? as f64 / 10000.0;
+    Some(IntensityGrid::Nadya { temperature, log_gravity, metalicity, filename: filename.to_string() })
+}
+
+/// Parses the name of one of Joris' grids, e.g. `t21000g35.txt`.
+fn parse_joris_filename(filename:&str)->Option<IntensityGrid>{
+    let (temperature, log_gravity) = filename.strip_suffix(".txt")?.strip_prefix('t')?.split_once('g')?;
+    let temperature = temperature.parse::<u32>().ok()? as f64;
+    let log_gravity = log_gravity.parse::<u32>().ok()? as f64 / 10.0;
+    Some(IntensityGrid::Joris { temperature, log_gravity, filename: filename.to_string() })
+}
+
+/// This function scans a directory and returns all of the intensity grids whose file names follow one of the conventions of [IntensityGrid::from_filename].
+/// The other files are ignored. The grids are ordered by file name.
+/// ### Arguments:
+/// * `path_to_grids` - the directory of the intensity grids.
+/// ### Returns:
+/// * `Ok(Vec<IntensityGrid>)` - the intensity grids found.
+/// * `Err(ProfileError::Io)` - if the directory could not be read.
+pub fn scan_grid_directory(path_to_grids:&str)->Result<Vec<IntensityGrid>,ProfileError>{
+    let mut grids:Vec<IntensityGrid> = Vec::new();
+    for entry in std::fs::read_dir(path_to_grids)?{
+        let entry = entry?;
+        if !entry.file_type()?.is_file(){
+            continue
+        }
+        if let Some(grid) = entry.file_name().to_str().and_then(IntensityGrid::from_filename){
+            grids.push(grid);
+        }
+    }
+    grids.sort_by(|a,b| a.filename().cmp(b.filename()));
+    Ok(grids)
+}
+
+/// This function creates the [DataFrame] of the catalogue, with a row per intensity grid.
+/// ### Returns:
+/// * A [DataFrame] with the columns `|filename|grid type|temperature|log gravity|metallicity|`, the metallicity is null for Joris' grids.
+///   The [IntensityGrid::Analytic] atmospheres and the [IntensityGrid::Emulator]s are left out, they're not on the lattice of grid files.
+pub fn catalogue_to_dataframe(grids:&[IntensityGrid])->PolarsResult<DataFrame>{
+    let grids:Vec<&IntensityGrid> = grids.iter().filter(|grid| grid.is_grid()).collect();
+    df!(
+        "filename" => grids.iter().map(|grid| grid.filename().to_string()).collect::<Vec<String>>(),
+        "grid type" => grids.iter().map(|grid| match grid{
+            IntensityGrid::Joris{..} => "Joris",
+            IntensityGrid::Nadya{..} => "Nadya",
+            IntensityGrid::Analytic(_) | IntensityGrid::Emulator{..} => unreachable!("only the grid files are kept"),
+        }).collect::<Vec<&str>>(),
+        "temperature" => grids.iter().map(|grid| grid.temperature()).collect::<Vec<f64>>(),
+        "log gravity" => grids.iter().map(|grid| grid.log_gravity()).collect::<Vec<f64>>(),
+        "metallicity" => grids.iter().map(|grid| grid.metallicity()).collect::<Vec<Option<f64>>>(),
+    )
+}
+
+/// This function recovers the intensity grids out of the [DataFrame] of a catalogue, see [catalogue_to_dataframe].
+/// It returns a [ProfileError::GridFormat] if a row doesn't describe a valid intensity grid.
+pub fn catalogue_from_dataframe(df:&DataFrame)->Result<Vec<IntensityGrid>,ProfileError>{
+    let filenames = df.column("filename")?.str()?;
+    let grid_types = df.column("grid type")?.str()?;
+    let temperatures = df.column("temperature")?.f64()?;
+    let log_gravities = df.column("log gravity")?.f64()?;
+    let metallicities = df.column("metallicity")?.f64()?;
+
+    let mut grids:Vec<IntensityGrid> = Vec::with_capacity(df.height());
+    for row in 0..df.height(){
+        let invalid_row = || ProfileError::GridFormat(format!("row {} of the grid catalogue is incomplete", row));
+        let filename = filenames.get(row).ok_or_else(invalid_row)?.to_string();
+        let temperature = temperatures.get(row).ok_or_else(invalid_row)?;
+        let log_gravity = log_gravities.get(row).ok_or_else(invalid_row)?;
+        let grid = match (grid_types.get(row), metallicities.get(row)){
+            (Some("Joris"), _) => {IntensityGrid::Joris { temperature, log_gravity, filename }}
+            (Some("Nadya"), Some(metalicity)) => {IntensityGrid::Nadya { temperature, log_gravity, metalicity, filename }}
+            _ => {return Err(invalid_row())}
+        };
+        grids.push(grid);
+    }
+    Ok(grids)
+}
+
+/// This function scans the grid directory and writes the catalogue of the intensity grids found into it, see [CATALOGUE_FILENAME].
+/// ### Arguments:
+/// * `path_to_grids` - the directory of the intensity grids.
+/// ### Returns:
+/// * `Ok(Vec<IntensityGrid>)` - the intensity grids found.
+/// * `Err(ProfileError)` - if the directory could not be read or the catalogue could not be written.
+pub fn build_catalogue(path_to_grids:&str)->Result<Vec<IntensityGrid>,ProfileError>{
+    let grids = scan_grid_directory(path_to_grids)?;
+    let mut df = catalogue_to_dataframe(&grids)?;
+    let file = File::create(Path::new(path_to_grids).join(CATALOGUE_FILENAME))?;
+    ParquetWriter::new(file).finish(&mut df)?;
+    Ok(grids)
+}
+
+/// This function returns the intensity grids of a directory.
+///
+/// The catalogue written into the directory is used if it's newer than the contents of the directory,
+/// otherwise the directory is scanned and the catalogue is written again. If the catalogue can't be written
+/// (e.g. the directory is read only) the scanned grids are used anyway.
+/// ### Arguments:
+/// * `path_to_grids` - the directory of the intensity grids.
+/// ### Returns:
+/// * `Ok(Vec<IntensityGrid>)` - the intensity grids of the directory.
+/// * `Err(ProfileError)` - if neither the catalogue nor the directory could be read.
+pub fn load_catalogue(path_to_grids:&str)->Result<Vec<IntensityGrid>,ProfileError>{
+    let catalogue_path = Path::new(path_to_grids).join(CATALOGUE_FILENAME);
+    if catalogue_is_up_to_date(path_to_grids, &catalogue_path){
+        let df = ParquetReader::new(File::open(&catalogue_path)?).finish()?;
+        return catalogue_from_dataframe(&df)
+    }
+    match build_catalogue(path_to_grids){
+        Ok(grids) => {Ok(grids)}
+        Err(ProfileError::Io(_)) | Err(ProfileError::Polars(_)) => {
+            eprintln!("warning: unable to write the catalogue of the intensity grids into '{}'", path_to_grids);
+            scan_grid_directory(path_to_grids)
+        }
+        Err(e) => {Err(e)}
+    }
+}
+
+/// Returns `true` if the catalogue exists and it was modified after the directory, i.e. no grid was added or removed since it was written.
+fn catalogue_is_up_to_date(path_to_grids:&str, catalogue_path:&Path)->bool{
+    let modified = |path:&Path| std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(catalogue_path), modified(Path::new(path_to_grids))){
+        (Ok(catalogue), Ok(directory)) => {catalogue >= directory}
+        _ => {false}
+    }
+}
+
+impl ProfileConfig{
+    /// This function takes the intensity grids from the catalogue of `path_to_grids` if the configuration doesn't list them,
+    /// see [load_catalogue]. It does nothing if the configuration lists the intensity grids.
+    pub fn fill_intensity_grids_from_catalogue(&mut self)->Result<(),ProfileError>{
+        if self.intensity_grids.is_empty(){
+            self.intensity_grids = load_catalogue(&self.path_to_grids)?;
+        }
+        Ok(())
+    }
+}
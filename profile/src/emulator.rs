@@ -0,0 +1,320 @@
+use crate::{IntensityGrid,InterpolationScheme};
+use crate::error::ProfileError;
+use crate::provider::{IntensityProvider,LocalAtmosphere};
+use ndarray::{Array1,Array2};
+use serde::Deserialize;
+use temp_name_lib::interpolation::{BoundsHandling,bracket,linear_interpolation};
+use temp_name_lib::utils::MathErrors;
+
+/// A neural network that emulates the specific and continuum intensities of the model atmospheres, see [IntensityGrid::Emulator].
+///
+/// The network is a stack of dense layers evaluated on the CPU. It maps the parameters of the atmosphere of a surface cell
+/// to the coefficients of a principal component basis of the spectra (e.g. an incremental PCA, IPCA), and the intensities are reconstructed
+/// out of them, `I = mean + Σ_k z_k √σ²_k c_k`. Unlike the intensity grids it doesn't need a complete lattice of nodes,
+/// it covers the whole box of parameters it was trained on.
+///
+/// The weights are stored in a toml file in `path_to_grids`, see the [module documentation](crate::emulator) for its keys:
+/// ```toml
+/// inputs = ["Temperature", "LogGravity", "Mu"]
+/// offset = [22000.0, 4.0, 0.5]
+/// scale = [2000.0, 0.5, 0.5]
+/// domain = [[18000.0, 26000.0], [3.0, 5.0], [0.0, 1.0]]
+///
+/// [[layers]]
+/// weights = [[0.5, 0.0, 0.8], [0.0, 0.3, 0.0]]
+/// biases = [0.0, 0.0]
+/// activation = "Tanh"
+///
+/// [reconstruction]
+/// wavelengths = [413.9, 414.0]
+/// mean = [0.0, 0.0, 0.0, 0.0]
+/// components = [[1.0, 1.0, 1.0, 1.0], [0.0, -1.0, 0.0, 0.0]]
+/// explained_variance = [1.0, 0.25]
+/// ```
+#[derive(Debug,Clone)]
+pub struct Emulator{
+    /// The parameters of the atmosphere fed to the network.
+    inputs: Vec<EmulatorInput>,
+    /// Offset and scale of the standardization of every input.
+    offset: Vec<f64>,
+    scale: Vec<f64>,
+    /// The range of every input covered by the training set.
+    domain: Vec<[f64;2]>,
+    /// The dense layers of the network.
+    layers: Vec<DenseLayer>,
+    /// Wavelengths in nm of the reconstructed spectra, in increasing order.
+    wavelengths: Vec<f64>,
+    /// Mean of the spectra, the specific intensities followed by the continuum intensities.
+    mean: Array1<f64>,
+    /// The principal components of the spectra, one per row.
+    components: Array2<f64>,
+    /// Factor of the coefficient of every component, the square root of its variance if the coefficients are whitened.
+    component_scale: Vec<f64>,
+    /// If `true`, the spectra are log10 of the intensities.
+    log10: bool,
+}
+
+/// The parameters of the atmosphere of a surface cell that can be fed to an [Emulator].
+#[derive(Deserialize,Debug,PartialEq,Clone,Copy)]
+pub enum EmulatorInput{
+    /// Effective temperature in K.
+    Temperature,
+    /// Logarithm of the surface gravity.
+    LogGravity,
+    /// Metallicity [M/H].
+    Metallicity,
+    /// Cosine of the angle between the normal of the surface and the line of sight.
+    Mu,
+}
+
+/// The activation function of a [DenseLayer].
+#[derive(Deserialize,Debug,PartialEq,Clone,Copy)]
+pub enum Activation{
+    Linear,
+    Relu,
+    Tanh,
+    Sigmoid,
+}
+
+/// A dense layer of an [Emulator], `activation(weights · x + biases)`.
+#[derive(Debug,Clone)]
+struct DenseLayer{
+    /// The weights with a row per output and a column per input.
+    weights: Array2<f64>,
+    biases: Array1<f64>,
+    activation: Activation,
+}
+
+/// The contents of the weights file of an [Emulator].
+#[derive(Deserialize)]
+struct EmulatorFile{
+    inputs: Vec<EmulatorInput>,
+    offset: Vec<f64>,
+    scale: Vec<f64>,
+    domain: Vec<[f64;2]>,
+    layers: Vec<LayerFile>,
+    reconstruction: ReconstructionFile,
+}
+
+#[derive(Deserialize)]
+struct LayerFile{
+    weights: Vec<Vec<f64>>,
+    biases: Vec<f64>,
+    activation: Activation,
+}
+
+#[derive(Deserialize)]
+struct ReconstructionFile{
+    wavelengths: Vec<f64>,
+    mean: Vec<f64>,
+    components: Vec<Vec<f64>>,
+    explained_variance: Option<Vec<f64>>,
+    #[serde(default)]
+    log10: bool,
+}
+
+/// The working memory of an [Emulator], see [IntensityProvider::new_scratch].
+pub struct EmulatorScratch{
+    /// The inputs of the current layer, and at the end the coefficients of the components.
+    values: Vec<f64>,
+    /// The outputs of the current layer.
+    next_values: Vec<f64>,
+}
+
+impl Activation{
+    fn apply(&self, x:f64)->f64{
+        match self{
+            Self::Linear => {x}
+            Self::Relu => {x.max(0.0)}
+            Self::Tanh => {x.tanh()}
+            Self::Sigmoid => {1.0/(1.0 + (-x).exp())}
+        }
+    }
+}
+
+/// Converts the rows of a matrix of the weights file into an [Array2], they must have the same length.
+fn matrix(rows:&[Vec<f64>])->Option<Array2<f64>>{
+    let ncols = rows.first().map_or(0, |row| row.len());
+    if rows.iter().any(|row| row.len() != ncols){
+        return None
+    }
+    Array2::from_shape_vec((rows.len(),ncols), rows.concat()).ok()
+}
+
+impl Emulator{
+    /// This function reads the weights of an emulator.
+    /// ### Arguments:
+    /// * `path` - the path to the weights file, see [Emulator] for its format.
+    /// ### Returns:
+    /// * `Ok(Emulator)` - the emulator ready to be evaluated.
+    /// * `Err(ProfileError::Io)` - if the file could not be read.
+    /// * `Err(ProfileError::GridFormat)` - if the file can't be parsed or the sizes of its layers and components don't match.
+    pub fn from_file(path:&str)->Result<Self,ProfileError>{
+        let contents = std::fs::read_to_string(path)?;
+        let invalid = |reason:String| ProfileError::GridFormat(format!("{}: {}", path, reason));
+        let file:EmulatorFile = toml::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+
+        let ninputs = file.inputs.len();
+        if ninputs == 0 || file.offset.len() != ninputs || file.scale.len() != ninputs || file.domain.len() != ninputs{
+            return Err(invalid(format!("there must be an offset, a scale and a domain for each of the {} inputs", ninputs)))
+        }
+        if file.scale.contains(&0.0) || file.domain.iter().any(|range| range[0] > range[1]){
+            return Err(invalid(String::from("the scales of the inputs can't be zero and their domains must be increasing")))
+        }
+
+        let mut layers:Vec<DenseLayer> = Vec::with_capacity(file.layers.len());
+        let mut width = ninputs;
+        for (n,layer) in file.layers.into_iter().enumerate(){
+            let weights = matrix(&layer.weights)
+                .filter(|weights| weights.ncols() == width && weights.nrows() == layer.biases.len() && !layer.biases.is_empty())
+                .ok_or_else(|| invalid(format!("the weights of layer {} must have a row of {} values for each of its {} biases",
+                    n+1, width, layer.biases.len())))?;
+            width = weights.nrows();
+            layers.push(DenseLayer{weights, biases: Array1::from(layer.biases), activation: layer.activation});
+        }
+
+        let reconstruction = file.reconstruction;
+        let nvalues = 2*reconstruction.wavelengths.len();
+        if reconstruction.wavelengths.len() < 2 || reconstruction.wavelengths.windows(2).any(|pair| pair[0] >= pair[1]){
+            return Err(invalid(String::from("the wavelengths of the reconstruction must be at least two and increasing")))
+        }
+        let components = matrix(&reconstruction.components)
+            .filter(|components| components.nrows() == width && components.ncols() == nvalues && reconstruction.mean.len() == nvalues)
+            .ok_or_else(|| invalid(format!("the network gives {} coefficients, there must be as many components, and they and the mean must have {} values",
+                width, nvalues)))?;
+        let component_scale = match reconstruction.explained_variance{
+            None => {vec![1.0;width]}
+            Some(variance) if variance.len() == width && variance.iter().all(|variance| *variance >= 0.0) => {
+                variance.iter().map(|variance| variance.sqrt()).collect()
+            }
+            Some(_) => {return Err(invalid(format!("the explained variance must have {} non-negative values", width)))}
+        };
+
+        Ok(Self{
+            inputs: file.inputs,
+            offset: file.offset,
+            scale: file.scale,
+            domain: file.domain,
+            layers,
+            wavelengths: reconstruction.wavelengths,
+            mean: Array1::from(reconstruction.mean),
+            components,
+            component_scale,
+            log10: reconstruction.log10,
+        })
+    }
+
+    /// Returns the wavelengths in nm of the spectra of the emulator.
+    pub fn wavelengths(&self)->&[f64]{
+        &self.wavelengths
+    }
+
+    /// Returns the range of every input covered by the training set, in the order of [Emulator::inputs].
+    pub fn domain(&self)->&[[f64;2]]{
+        &self.domain
+    }
+
+    /// Returns the parameters of the atmosphere fed to the network.
+    pub fn inputs(&self)->&[EmulatorInput]{
+        &self.inputs
+    }
+
+    /// This function evaluates the network and leaves the coefficients of the components in `scratch.values`.
+    /// The inputs outside of the domain are treated according to `bounds_handling`.
+    fn coefficients(&self, atmosphere:&LocalAtmosphere, bounds_handling:BoundsHandling, scratch:&mut EmulatorScratch)->Result<(),MathErrors>{
+        scratch.values.clear();
+        for (n,input) in self.inputs.iter().enumerate(){
+            let value = input.value(atmosphere).ok_or(MathErrors::OutOfBounds)?;
+            let [lower,upper] = self.domain[n];
+            let value = match bounds_handling{
+                _ if (lower..=upper).contains(&value) => {value}
+                BoundsHandling::Error => {return Err(MathErrors::OutOfBounds)}
+                BoundsHandling::Clamp => {value.clamp(lower, upper)}
+                BoundsHandling::Extrapolate => {value}
+            };
+            scratch.values.push((value - self.offset[n])/self.scale[n]);
+        }
+
+        for layer in self.layers.iter(){
+            scratch.next_values.clear();
+            for (weights,bias) in layer.weights.rows().into_iter().zip(layer.biases.iter()){
+                let sum:f64 = weights.iter().zip(scratch.values.iter()).map(|(weight,value)| weight*value).sum();
+                scratch.next_values.push(layer.activation.apply(sum + bias));
+            }
+            std::mem::swap(&mut scratch.values, &mut scratch.next_values);
+        }
+        for (coefficient,scale) in scratch.values.iter_mut().zip(self.component_scale.iter()){
+            *coefficient *= scale;
+        }
+        Ok(())
+    }
+
+    /// Returns the intensity reconstructed at a value of the spectra out of the coefficients of the components.
+    fn reconstruct(&self, coefficients:&[f64], column:usize)->f64{
+        let value = self.mean[column] + self.components.column(column).iter().zip(coefficients.iter())
+            .map(|(component,coefficient)| component*coefficient)
+            .sum::<f64>();
+        if self.log10 {10f64.powf(value)} else {value}
+    }
+}
+
+impl EmulatorInput{
+    /// Returns the value of the input for an atmosphere, or [None] if it's the metallicity and the atmosphere doesn't have one.
+    fn value(&self, atmosphere:&LocalAtmosphere)->Option<f64>{
+        match self{
+            Self::Temperature => {Some(atmosphere.temperature)}
+            Self::LogGravity => {Some(atmosphere.log_gravity)}
+            Self::Metallicity => {atmosphere.metallicity}
+            Self::Mu => {Some(atmosphere.mu)}
+        }
+    }
+}
+
+impl IntensityProvider for Emulator{
+    type Scratch = EmulatorScratch;
+
+    fn new_scratch(&self)->EmulatorScratch{
+        let width = self.layers.iter().map(|layer| layer.biases.len()).max().unwrap_or(0).max(self.inputs.len());
+        EmulatorScratch{values: Vec::with_capacity(width), next_values: Vec::with_capacity(width)}
+    }
+
+    /// Returns `true` if every input of the atmosphere lies within the domain of the training set.
+    fn covers(&self, atmosphere:&LocalAtmosphere)->bool{
+        self.inputs.iter().zip(self.domain.iter())
+            .all(|(input,range)| input.value(atmosphere).is_some_and(|value| range[0] <= value && value <= range[1]))
+    }
+
+    /// This function evaluates the network for the atmosphere and reconstructs the specific and continuum intensities,
+    /// which are interpolated linearly between the wavelengths of the emulator.
+    /// ### Returns:
+    /// * `Err(MathErrors::OutOfBounds)` - if the atmosphere lies outside of the domain and `bounds_handling` is [BoundsHandling::Error],
+    ///   the network needs the metallicity and the atmosphere doesn't have one, or a wavelength is not covered by the emulator.
+    fn intensities(&self, atmosphere:&LocalAtmosphere, wavelengths:&[f64], bounds_handling:BoundsHandling,
+        scratch:&mut EmulatorScratch, specific:&mut [f64], continuum:&mut [f64])->Result<(),MathErrors>{
+        self.coefficients(atmosphere, bounds_handling, scratch)?;
+        let coefficients = &scratch.values;
+        let continuum_column = self.wavelengths.len();
+        for (n,wavelength) in wavelengths.iter().enumerate(){
+            let (index,fraction) = bracket(&self.wavelengths, *wavelength, BoundsHandling::Error)?;
+            specific[n] = linear_interpolation(&[self.reconstruct(coefficients, index), self.reconstruct(coefficients, index+1)], fraction);
+            continuum[n] = linear_interpolation(&[self.reconstruct(coefficients, continuum_column+index),
+                self.reconstruct(coefficients, continuum_column+index+1)], fraction);
+        }
+        Ok(())
+    }
+
+    /// Returns the wavelengths of the spectra of the emulator, which are interpolated linearly.
+    fn tabulated_wavelengths(&self)->Option<(&[f64],InterpolationScheme)>{
+        Some((&self.wavelengths, InterpolationScheme::Linear))
+    }
+}
+
+impl IntensityGrid{
+    /// Returns the name of the weights file if the grid is [IntensityGrid::Emulator].
+    pub fn emulator(&self)->Option<&str>{
+        match self{
+            Self::Emulator{model} => {Some(model)}
+            _ => {None}
+        }
+    }
+}
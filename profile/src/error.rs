@@ -0,0 +1,93 @@
+use polars::prelude::PolarsError;
+use temp_name_lib::utils::MathErrors;
+use pulstar::validation::{ParameterBox, ValidationReport};
+
+/// Errors that can be returned by the profile library.
+///
+/// The variants wrap the errors of the crates used by profile so that the calling program
+/// (e.g. the `profile` binary or `pulstar_py`) decides what to do with them instead of panicking.
+#[derive(Debug)]
+pub enum ProfileError{
+    /// A mathematical error, e.g. a requested point outside of the intensity grids.
+    Math(MathErrors),
+    /// An error produced while reading the intensity grids or the rasterized star, or while writing the output.
+    Polars(PolarsError),
+    /// An error reading or writing a file.
+    Io(std::io::Error),
+    /// The toml configuration could not be parsed.
+    Toml(toml::de::Error),
+    /// An intensity grid file listed in the configuration is not in the grid directory.
+    MissingGridFile(String),
+    /// An intensity grid file does not have the expected layout.
+    GridFormat(String),
+    /// The configuration was parsed but it does not describe a valid run.
+    InvalidConfig(String),
+    /// The configuration didn't pass [crate::ProfileConfig::validate], the report holds all of the problems found.
+    Validation(ValidationReport),
+    /// The star reaches temperatures or surface gravities outside of the loaded intensity grids.
+    GridCoverage{
+        /// Range of the visible cells of the star over all of the time points.
+        star: ParameterBox,
+        /// Range covered by the intensity grids.
+        grids: ParameterBox,
+    },
+    /// The star has a metallicity outside of the ones of the loaded intensity grids.
+    MetallicityCoverage{
+        /// Minimum and maximum metallicity [M/H] of the visible cells of the star.
+        star: [f64;2],
+        /// Minimum and maximum metallicity [M/H] of the intensity grids.
+        grids: [f64;2],
+    },
+}
+
+impl std::fmt::Display for ProfileError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            ProfileError::Math(e) => write!(f, "math error: {}", e),
+            ProfileError::Polars(e) => write!(f, "polars error: {}", e),
+            ProfileError::Io(e) => write!(f, "io error: {}", e),
+            ProfileError::Toml(e) => write!(f, "unable to parse the profile configuration: {}", e),
+            ProfileError::MissingGridFile(filename) => write!(f,
+                "intensity grid '{}' not found. Either load the file into the directory or check the toml file to see if there was a misspelling",
+                filename),
+            ProfileError::GridFormat(message) => write!(f, "malformed intensity grid: {}", message),
+            ProfileError::InvalidConfig(message) => write!(f, "invalid profile configuration: {}", message),
+            ProfileError::Validation(report) => write!(f, "invalid profile configuration, {}", report),
+            ProfileError::GridCoverage{star, grids} => write!(f,
+                "the star reaches {}, outside of the intensity grids that cover {}. Add intensity grids to cover {}",
+                star, grids, crate::validation::suggest_grid_box(star, grids)),
+            ProfileError::MetallicityCoverage{star, grids} => write!(f,
+                "the star has [M/H] in [{:.2}, {:.2}], outside of the intensity grids that cover [{:.2}, {:.2}]",
+                star[0], star[1], grids[0], grids[1]),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self{
+            ProfileError::Math(e) => Some(e),
+            ProfileError::Polars(e) => Some(e),
+            ProfileError::Io(e) => Some(e),
+            ProfileError::Toml(e) => Some(e),
+            ProfileError::MissingGridFile(_) | ProfileError::GridFormat(_) | ProfileError::InvalidConfig(_)
+            | ProfileError::Validation(_) | ProfileError::GridCoverage{..} | ProfileError::MetallicityCoverage{..} => None,
+        }
+    }
+}
+
+impl From<MathErrors> for ProfileError{
+    fn from(e: MathErrors) -> Self { ProfileError::Math(e) }
+}
+
+impl From<PolarsError> for ProfileError{
+    fn from(e: PolarsError) -> Self { ProfileError::Polars(e) }
+}
+
+impl From<std::io::Error> for ProfileError{
+    fn from(e: std::io::Error) -> Self { ProfileError::Io(e) }
+}
+
+impl From<toml::de::Error> for ProfileError{
+    fn from(e: toml::de::Error) -> Self { ProfileError::Toml(e) }
+}
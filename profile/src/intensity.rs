@@ -1,6 +1,7 @@
 
-use polars::{error::ErrString, prelude::*};
+use polars::error::ErrString;
 use super::*;
+use crate::analytic::{AnalyticAtmosphere,LimbDarkeningCoefficients};
 use std::fs::File;
 
 /// This module contains the methods and functions to process the intensity grids. 
@@ -22,34 +23,100 @@ use std::fs::File;
 impl ProfileConfig{
     /// This function checks within the collection of intensity grid files provided by the toml file
     /// to see if all of the grid files are loaded into the directory.
-    /// If they are not, it should return a ErrorKind::NotFound
-    pub fn intensity_grids_are_loaded(&self)->Result<(),std::io::Error>{
-        for grid in self.intensity_grids.iter(){
-            grid.is_there_a_file(&self.path_to_grids)?;
+    /// If they are not, it returns a [ProfileError::MissingGridFile] with the name of the first missing file.
+    /// With [GridBackend::Compressed] the grid files are replaced by the file of compressed grids.
+    pub fn intensity_grids_are_loaded(&self)->Result<(),ProfileError>{
+        if self.grid_backend == GridBackend::Compressed && self.intensity_grids.iter().any(|grid| grid.is_grid()){
+            if !self.compressed_grids_path().is_file(){
+                return Err(ProfileError::MissingGridFile(crate::utils::pca_grid::COMPRESSED_GRIDS_FILE.to_string()))
+            }
+            return Ok(())
+        }
+        for grid in self.intensity_grids.iter().filter(|grid| grid.needs_file()){
+            if grid.is_there_a_file(&self.path_to_grids).is_err(){
+                return Err(ProfileError::MissingGridFile(grid.filename().to_string()))
+            }
         }
         Ok(())
     }
 
+    /// Returns the analytic atmosphere of the configuration, if one of its intensity grids is [IntensityGrid::Analytic].
+    pub fn analytic_atmosphere(&self)->Option<&AnalyticAtmosphere>{
+        self.intensity_grids.iter().find_map(|grid| grid.analytic())
+    }
+
+    /// Returns the name of the weights file of the emulator, if one of the intensity grids is [IntensityGrid::Emulator].
+    pub fn emulator_model(&self)->Option<&str>{
+        self.intensity_grids.iter().find_map(|grid| grid.emulator())
+    }
+
 }
 
 impl IntensityGrid{
+    /// Returns the name of the intensity grid file.
+    /// For an [IntensityGrid::Analytic] atmosphere it's the name of its table of coefficients, or an empty string if it doesn't have one,
+    /// and for an [IntensityGrid::Emulator] the name of its weights file.
+    pub fn filename(&self)->&str{
+        match self{
+            Self::Joris{filename,..} => {filename}
+            Self::Nadya{filename,..} => {filename}
+            Self::Emulator{model} => {model}
+            Self::Analytic(atmosphere) => {
+                match &atmosphere.coefficients{
+                    LimbDarkeningCoefficients::Table(filename) => {filename}
+                    LimbDarkeningCoefficients::Fixed(_) => {""}
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the intensity grid needs a file in the grid directory.
+    pub fn needs_file(&self)->bool{
+        !self.filename().is_empty()
+    }
+
+    /// Returns `true` if it's an intensity grid file on a node of the [GridLattice], i.e. it's not an [IntensityGrid::Analytic]
+    /// atmosphere or an [IntensityGrid::Emulator].
+    pub fn is_grid(&self)->bool{
+        matches!(self, Self::Joris{..} | Self::Nadya{..})
+    }
+
+    /// Returns the effective temperature in K of the plane parallel atmosphere of the intensity grid.
+    /// It's NaN for an [IntensityGrid::Analytic] atmosphere or an [IntensityGrid::Emulator], which aren't nodes of the lattice.
+    pub fn temperature(&self)->f64{
+        match self{
+            Self::Joris{temperature,..} => {*temperature}
+            Self::Nadya{temperature,..} => {*temperature}
+            Self::Analytic(_) | Self::Emulator{..} => {f64::NAN}
+        }
+    }
+
+    /// Returns the logarithm of the surface gravity of the plane parallel atmosphere of the intensity grid.
+    /// It's NaN for an [IntensityGrid::Analytic] atmosphere or an [IntensityGrid::Emulator], which aren't nodes of the lattice.
+    pub fn log_gravity(&self)->f64{
+        match self{
+            Self::Joris{log_gravity,..} => {*log_gravity}
+            Self::Nadya{log_gravity,..} => {*log_gravity}
+            Self::Analytic(_) | Self::Emulator{..} => {f64::NAN}
+        }
+    }
+
+    /// Returns the metallicity [M/H] of the intensity grid, only Nadya's grids have one.
+    pub fn metallicity(&self)->Option<f64>{
+        match self{
+            Self::Joris{..} | Self::Analytic(_) | Self::Emulator{..} => {None}
+            Self::Nadya{metalicity,..} => {Some(*metalicity)}
+        }
+    }
+
     /// This function asses whether the purported intensity grid file is stored in a given directory.
     /// ### Argument:
     /// * path - a string that indicates the relative path to the directory of the intensity grid files.
     /// ### Returns:
     /// * Ok(File) if it's able to locate the intensity file and is able to open it.
     /// * Error - otherwise.
-    fn is_there_a_file(&self,path:&str)->Result<File,std::io::Error>{
-        let file_name = match self{
-            Self::Joris{filename,
-                log_gravity:_,
-                temperature:_} => { filename.clone()}
-            Self::Nadya{filename,
-                metalicity:_,
-                log_gravity:_,
-                temperature:_} => { filename.clone()}
-        };
-        let full_name = format!("{}{}",String::from(path), file_name);
+    pub(crate) fn is_there_a_file(&self,path:&str)->Result<File,std::io::Error>{
+        let full_name = format!("{}{}",String::from(path), self.filename());
         File::open(&full_name)
     }
 }
@@ -59,6 +126,16 @@ impl IntensityGrid{
 //---------------------------------------- 
 pub mod parse_intensity_grids;
 
+//----------------------------------------
+//------lattice of intensity grids--------
+//---------------------------------------- 
+pub mod grid_lattice;
+
+//--------------------------------------------------
+//-----Limb-darkening law fitted to the µ values----
+//--------------------------------------------------
+pub mod limb_darkening_fit;
+
 //--------------------------------------------------
 //-----Extracting Intensity and continuum fluxes----
 //--------------------------------------------------
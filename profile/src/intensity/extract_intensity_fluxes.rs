@@ -1,138 +1,156 @@
 use super::*;
-use temp_name_lib::interpolation::ParameterSpaceHypercube;
+use crate::provider::{IntensityProvider,LocalAtmosphere};
+use temp_name_lib::interpolation::BoundsHandling;
+use temp_name_lib::utils::MathErrors;
+use std::ops::Range;
+
+/// The largest deviation, in steps, of the wavelengths of the grids from a uniform sampling for them to be indexed in constant time,
+/// see [SpectralGrid::uniform_step]. It allows for the rounding of the wavelengths written in the grid files.
+pub const UNIFORM_SAMPLING_TOLERANCE:f64 = 1.0e-6;
 impl SpectralGrid{
-    pub fn new_hypercube(& self,dimension:usize)->ParameterSpaceHypercube{
-        //let dimension = 4usize;//T_eff,Log_g,mu,lambda
-        let mut cube = ParameterSpaceHypercube::new(dimension);
-        let (temps,log_g) = (self.t_eff.clone(),self.log_g.clone());
-        let wavelength:[f64;2]=[0.0,0.0];
-        let mu_vals:[f64;2]=[0.0,0.0];
-        match dimension{
-            4usize => {cube.fill_coordinates(& vec![temps,log_g,wavelength,mu_vals]).unwrap();}
-            3usize => {cube.fill_coordinates(& vec![temps,log_g,mu_vals]).unwrap();}//This is for when mu>=0.9636
-            _ => {panic!("never thought of this case")}
-        }
-        cube
+    /// Returns the index of the µ value of the grids right below `mu`, the µ values at the index and the next one bracket it.
+    /// `mu` must lie within the µ range of the grids.
+    pub(crate) fn return_mu_index(&self, mu:f64)->usize{
+        self.mu_values.partition_point(|mu_val| *mu_val < mu)
+            .saturating_sub(1)
+            .min(self.mu_values.len()-2)
     }
 
+    /// Returns the step between the wavelengths of the grids if they're uniformly sampled, i.e. every wavelength lies within
+    /// [UNIFORM_SAMPLING_TOLERANCE] steps of the uniform sampling between the first and last ones, or [None] otherwise.
+    pub(crate) fn uniform_step(wavelengths:&[f64])->Option<f64>{
+        let (first,last) = (*wavelengths.first()?,*wavelengths.last()?);
+        if wavelengths.len() < 2 || last <= first {return None}
+        let step = (last - first)/(wavelengths.len() - 1) as f64;
+        wavelengths.iter().enumerate()
+            .all(|(n,wavelength)| (wavelength - (first + n as f64 * step)).abs() <= UNIFORM_SAMPLING_TOLERANCE*step)
+            .then_some(step)
+    }
 
-    fn return_mu_index(&self, mu:f64)->usize{
-        let mut index:usize =0;
-        
-        for (n,mu_val) in self.mu_values.iter().enumerate(){
-            if mu<=*mu_val { index = n;
-            break;}
-        }
-        //println!("mu = {}; between {} and {}",mu,index,index+1);
-        index-1
+    /// Returns the step between the wavelengths of the grids if they're uniformly sampled, see [SpectralGrid::uniform_step].
+    pub fn wavelength_step(&self)->Option<f64>{
+        self.wavelength_step
     }
-    
-    /// This function is used to store for the observed wavelength the indices of the wavelengths in [GridsData] that will be used for interpolation. 
-    /// This function relies on the bisection algorithm to perform the query.
-    pub fn extract_important_rows(&mut self,global_flux: &mut FluxOfSpectra){
-        self.row_indices.fill(0);
 
-        let mut counter = 0usize;
-        for shifted_wavelength in global_flux.shifted_wavelength.iter() {
-            self.row_indices[counter] = search_geq(&self.wavelengths, *shifted_wavelength)-1;
-            self.row_indices[counter+1] = self.row_indices[counter]+1;
-            counter += 2usize;
+    /// This function returns the index of the grid wavelength right below a wavelength, the wavelengths at the index and the next one bracket it.
+    /// If the wavelengths of the grids are uniformly sampled the index is computed from the step between them, otherwise
+    /// this function relies on the bisection algorithm to perform the query. Both give the same index.
+    /// It returns an [Err] ([MathErrors::OutOfBounds]) variant if the wavelength is not covered by the grid wavelengths,
+    /// which is always the case if the grids keep less than two wavelengths.
+    fn wavelength_index(&self, wavelength:f64)->Result<usize,MathErrors>{
+        if self.wavelengths.len() < 2 {return Err(MathErrors::OutOfBounds)}
+        match self.wavelength_step{
+            Some(step) => {
+                let last_index = self.wavelengths.len()-1;
+                // The comparisons are false for NaN
+                if !(wavelength >= self.wavelengths[0] && wavelength <= self.wavelengths[last_index]) {return Err(MathErrors::OutOfBounds)}
+                let mut index = (((wavelength - self.wavelengths[0])/step) as usize).min(last_index-1);
+                // The wavelengths are only uniform up to the tolerance, so the index may be off by a row.
+                if index > 0 && self.wavelengths[index] >= wavelength {index -= 1}
+                else if self.wavelengths[index+1] < wavelength {index += 1}
+                Ok(index)
+            }
+            None => Ok(search_geq(&self.wavelengths, wavelength)?.saturating_sub(1)),
         }
     }
 
+    /// This function writes into `out` the intensities of the consecutive `rows` of a grid at the µ of the current surface cell, out of
+    /// the columns `first_column + index` of every µ value: their sums times the `mu_weights` of the scratch, or if it has a `mu_index`
+    /// their cubic interpolation (see [SpectralGrid::mu_weights]).
+    fn rows_at_mu(&self,grid_number:usize,rows:Range<usize>,first_column:usize,mu:f64,scratch:&mut GridScratch,out:&mut [f64]){
+        match scratch.mu_index{
+            None => {
+                self.grid_values.weighted_rows(grid_number, rows, first_column, &scratch.mu_weights, out);
+            }
+            Some(index) => {
+                let stencil = self.interpolation.mu.stencil(index, self.mu_values.len());
+                for (row,value) in rows.zip(out.iter_mut()){
+                    scratch.mu_stencil_values.clear();
+                    scratch.mu_stencil_values.extend(stencil.clone().map(|column| self.grid_values.value(grid_number,row,first_column+column)));
+                    *value = self.interpolation.mu.interpolate(&self.mu_values[stencil.clone()], &scratch.mu_stencil_values, index-stencil.start, mu);
+                }
+            }
+        }
+    }
 }
-impl FluxOfSpectra{
 
-/// This function that returns the intensity flux and the continuum flux interpolated from the intensity grids
-/// for each surface cell of the rasterized sphere.
-/// ### Arguments:
-/// * `cell` -  A &[SurfaceCell] that contains the `coschi`-Projection of the normal vector of the surface cell with the unit vector towards the observer;
-///  `temperature` - temperature value over the surface cell of the rasterized star (in kelvin);
-///  `log_gravity` - log g value over the surface cell of the rasterized star;
-///  `relative doppler shift` - relative doppler wavelength shift, this is of course related to the velocity.
-///  'area' - area of the surface cell of the rasterized star;
-/// * `spectral_grid` - a reference to an instance of [SpectralGrid] that contains all of the relevant information parsed from the intensity grid files, or the neural network regressor. 
-/// * `hypercube' - a reference to an instance of [ParameterSpaceHypercube]. The coordinates and values of the vertices in the parameter space that are used to perform the multilinear interpolation. 
-/// ### Returns:
-/// - This function adds the contribution of the observed specific intensities by a surface cell. 
-    pub fn collect_flux_from_cell(& mut self, cell: & SurfaceCell, spectral_grid: &mut SpectralGrid, hypercube:& mut ParameterSpaceHypercube){
-        spectral_grid.extract_important_rows(self);   
+impl IntensityProvider for SpectralGrid{
+    type Scratch = GridScratch;
 
-        let mu_index = if hypercube.fractional_coordinates.len()==4{
-            let index= spectral_grid.return_mu_index(cell.coschi.sqrt());
-            //fill coordinates of the hypercube in the parameter space
-            //mu value
-            hypercube.fractional_coordinates[3][0..=1]
-                .copy_from_slice(&spectral_grid.mu_values[index..=index+1]);
-            index
+    fn new_scratch(&self)->GridScratch{
+        GridScratch{
+            corner_weights: Vec::with_capacity(8),
+            mu_weights: Vec::with_capacity(self.mu_values.len()),
+            mu_index: None,
+            mu_stencil_values: Vec::with_capacity(4),
+            wavelength_indices: Vec::new(),
+            specific_rows: Vec::new(),
+            continuum_rows: Vec::new(),
         }
-        else{ 0usize};
-
-        for (n,wavelength) in self.shifted_wavelength.iter().enumerate(){
-            //------------------------------------------------
-            //------Get coordinates in parameter space--------
-            //------------------------------------------------
-
-            let coordinate_point = if hypercube.fractional_coordinates.len()==4
-                {vec![cell.t_eff,cell.log_g, *wavelength,cell.coschi.sqrt()]}
-                else{vec![cell.t_eff,cell.log_g,*wavelength]};
-
-
-            //----------------------------------------
-            //------------------fill hypercube--------
-            //----------------------------------------
-            //fill wavelength coordinate
-            let wavelength_index = spectral_grid.row_indices[2*n];
-            hypercube.fractional_coordinates[2][0..=1]
-                .copy_from_slice(&spectral_grid.wavelengths[wavelength_index..=wavelength_index+1]);
-            
-            spectral_grid.fill_corner_values(wavelength_index, mu_index, hypercube);
-            self.flux[n] += hypercube.multilinear_interpolation(&coordinate_point).unwrap() * cell.area;
+    }
 
-            //fill vertices values continuum
-            spectral_grid.fill_corner_values(wavelength_index, mu_index+7, hypercube);
-            self.continuum[n] += hypercube.multilinear_interpolation(&coordinate_point).unwrap() * cell.area;
-        }
+    /// Returns `true` if the atmosphere lies within the intensity grids, including its metallicity if the grids are interpolated in metallicity.
+    /// If some nodes of the [GridLattice] are missing, it must lie within the convex hull of the remaining ones.
+    fn covers(&self, atmosphere:&LocalAtmosphere)->bool{
+        self.lattice.contains(atmosphere.temperature, atmosphere.log_gravity) && self.lattice.contains_metallicity(atmosphere.metallicity)
     }
 
-}
+    /// This function interpolates the specific and continuum intensities out of the intensity grids.
+    /// The grids are looked up at µ = cos(χ), outside of their µ range the intensities are extrapolated with the limb-darkening law
+    /// fitted to them (see [SpectralGrid::mu_weights]). Along the wavelength and µ axes they're interpolated with the schemes of
+    /// [crate::GridInterpolation], and then linearly in the (T_eff, log g, [M/H]) space with the weights of [GridLattice::corner_weights].
+    ///
+    /// The weights of the cell are computed once for all of the wavelengths. For every grid of the corners, the rows needed by the
+    /// wavelengths are first computed at the µ of the cell in bulk, and the wavelengths are then interpolated along these rows.
+    /// ### Returns:
+    /// It returns an [Err] ([MathErrors]) variant if the atmosphere lies outside of the intensity grids and `bounds_handling` is [BoundsHandling::Error],
+    /// a wavelength is not covered by the grids, or the grids are interpolated in metallicity and the atmosphere doesn't have one.
+    fn intensities(&self, atmosphere:&LocalAtmosphere, wavelengths:&[f64], bounds_handling:BoundsHandling,
+        scratch:&mut GridScratch, specific:&mut [f64], continuum:&mut [f64])->Result<(),MathErrors>{
+        self.lattice.corner_weights(atmosphere.temperature, atmosphere.log_gravity, atmosphere.metallicity, bounds_handling, &mut scratch.corner_weights)?;
+        scratch.mu_index = self.mu_weights(atmosphere.mu, &mut scratch.mu_weights);
+        // The continuum intensities are stored after the specific intensities of every µ.
+        let continuum_column = self.mu_values.len();
+        let scheme = self.interpolation.wavelength;
 
-impl SpectralGrid{
-    
-    fn fill_corner_values_4d(&mut self,wavelength_index:usize,mu_index:usize,hypercube:&mut ParameterSpaceHypercube){
-        // Fill vertices values specific intensities
-        for i in 0..2usize{// effective temperature
-            for j in 0..2usize{// log gravity
-                let grid_number = 2*i+j;
-                for k in 0..2usize{//wavelength
-                    for l in 0..2usize{//mu value
-                        let corner_value_index = l+2*k+4*j+8*i;
-                        hypercube.corner_values[corner_value_index]=self.grid_values[[grid_number,wavelength_index+k,mu_index+l]].clone();
-                    }
-                }
-            }
+        // The wavelengths are indexed once, the rows needed are the ones between the lowest and the highest stencils.
+        scratch.wavelength_indices.clear();
+        let (mut first_row,mut end_row) = (usize::MAX,0);
+        for wavelength in wavelengths.iter(){
+            let wavelength_index = self.wavelength_index(*wavelength)?;
+            let stencil = scheme.stencil(wavelength_index, self.wavelengths.len());
+            first_row = first_row.min(stencil.start);
+            end_row = end_row.max(stencil.end);
+            scratch.wavelength_indices.push(wavelength_index);
         }
-    }
+        let rows = first_row..end_row;
+        specific[..wavelengths.len()].fill(0.0);
+        continuum[..wavelengths.len()].fill(0.0);
+        if rows.is_empty() {return Ok(())}
 
-    fn fill_corner_values_3d(&mut self,wavelength_index:usize,hypercube:&mut ParameterSpaceHypercube){
-        // Fill vertices values specific intensities
-        for i in 0..2usize{// effective temperature
-            for j in 0..2usize{// log gravity
-                let grid_number = 2*i+j;
-                for k in 0..2usize{//wavelength
-                    let corner_value_index = k+2*j+4*i;
-                    hypercube.corner_values[corner_value_index]=self.grid_values[[grid_number,wavelength_index+k,6]].clone();
-                }
+        let (mut specific_rows,mut continuum_rows) = (std::mem::take(&mut scratch.specific_rows),std::mem::take(&mut scratch.continuum_rows));
+        specific_rows.resize(rows.len(), 0.0);
+        continuum_rows.resize(rows.len(), 0.0);
+        for corner in 0..scratch.corner_weights.len(){// effective temperature, log gravity and metallicity
+            let (grid_number,weight) = scratch.corner_weights[corner];
+            self.rows_at_mu(grid_number, rows.clone(), 0, atmosphere.mu, scratch, &mut specific_rows);
+            self.rows_at_mu(grid_number, rows.clone(), continuum_column, atmosphere.mu, scratch, &mut continuum_rows);
+            for (n,(wavelength,wavelength_index)) in wavelengths.iter().zip(scratch.wavelength_indices.iter()).enumerate(){
+                let stencil = scheme.stencil(*wavelength_index, self.wavelengths.len());
+                let nodes = &self.wavelengths[stencil.clone()];
+                let window = stencil.start-rows.start..stencil.end-rows.start;
+                let index = wavelength_index-stencil.start;
+                specific[n] += weight*scheme.interpolate(nodes, &specific_rows[window.clone()], index, *wavelength);
+                continuum[n] += weight*scheme.interpolate(nodes, &continuum_rows[window], index, *wavelength);
             }
         }
+        scratch.specific_rows = specific_rows;
+        scratch.continuum_rows = continuum_rows;
+        Ok(())
     }
 
-    fn fill_corner_values(&mut self,wavelength_index:usize,mu_index:usize,hypercube:&mut ParameterSpaceHypercube){
-        match hypercube.fractional_coordinates.len(){
-            4usize=>{self.fill_corner_values_4d(wavelength_index, mu_index, hypercube);}
-            3usize=>{self.fill_corner_values_3d(wavelength_index, hypercube);}
-            _=>{panic!("never thought of this case; in extract intensity fluxes\n 
-the dimension of the hypercube in the parameter space is {}",{hypercube.fractional_coordinates.len()})}
-        }
+    /// Returns the wavelengths of the grids within the window loaded for the star and the scheme along them, see [crate::GridInterpolation].
+    fn tabulated_wavelengths(&self)->Option<(&[f64],InterpolationScheme)>{
+        Some((&self.wavelengths, self.interpolation.wavelength))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,222 @@
+use super::*;
+use pulstar::validation::ParameterBox;
+use temp_name_lib::interpolation::bracket;
+
+impl GridLattice{
+    /// This function arranges the intensity grids given in the configuration into a lattice on the (T_eff, log g, [M/H]) space.
+    /// The order of the grids in the configuration doesn't matter.
+    /// ### Arguments:
+    /// * `intensity_grids` - the collection of [IntensityGrid]s of the configuration.
+    /// ### Returns:
+    /// * `Ok(GridLattice)` - where the node `(i,j,m)` refers to `intensity_grids[lattice.grid_index(i,j,m)]`.
+    /// * `Err(ProfileError::InvalidConfig)` - if there are less than two temperatures or log g, two grids share a node,
+    ///   a node of the lattice is missing and the grids have several metallicities or the remaining nodes lie on a line,
+    ///   or only some of the grids have a metallicity.
+    pub fn from_intensity_grids(intensity_grids:&[IntensityGrid])->Result<Self,ProfileError>{
+        Self::build(intensity_grids).map_err(ProfileError::InvalidConfig)
+    }
+
+    /// Same as [GridLattice::from_intensity_grids], but the error is only the description of the problem.
+    pub(crate) fn build(intensity_grids:&[IntensityGrid])->Result<Self,String>{
+        let temperatures = sorted_nodes(intensity_grids.iter().map(|grid| grid.temperature()));
+        let log_gravities = sorted_nodes(intensity_grids.iter().map(|grid| grid.log_gravity()));
+        let metallicities = sorted_nodes(intensity_grids.iter().filter_map(|grid| grid.metallicity()));
+        if temperatures.len() < 2 || log_gravities.len() < 2{
+            return Err(format!("the intensity grids must have at least two temperatures and two log g, they have {} and {}",
+                temperatures.len(), log_gravities.len()))
+        }
+        if !metallicities.is_empty() && intensity_grids.iter().any(|grid| grid.metallicity().is_none()){
+            return Err(String::from("the intensity grids with a metallicity (Nadya) can't be mixed with grids without one (Joris)"))
+        }
+
+        let nmetallicities = metallicities.len().max(1);
+        let mut nodes:Vec<Option<usize>> = vec![None; temperatures.len()*log_gravities.len()*nmetallicities];
+        for (n,grid) in intensity_grids.iter().enumerate(){
+            let i = node_position(&temperatures, grid.temperature());
+            let j = node_position(&log_gravities, grid.log_gravity());
+            let m = grid.metallicity().map_or(0, |metallicity| node_position(&metallicities, metallicity));
+            let node = &mut nodes[(i*log_gravities.len()+j)*nmetallicities+m];
+            if let Some(other) = node{
+                return Err(format!("the intensity grids '{}' and '{}' share the same node, {}",
+                    intensity_grids[*other].filename(), grid.filename(), describe_node(grid.temperature(), grid.log_gravity(), grid.metallicity())))
+            }
+            *node = Some(n);
+        }
+
+        let missing:Vec<String> = nodes.iter().enumerate()
+            .filter(|(_,node)| node.is_none())
+            .map(|(k,_)| {
+                let (ij, m) = (k/nmetallicities, k%nmetallicities);
+                format!("({})", describe_node(temperatures[ij/log_gravities.len()], log_gravities[ij%log_gravities.len()],
+                    metallicities.get(m).copied()))
+            })
+            .collect();
+        let grid_indices:Vec<usize> = nodes.into_iter().flatten().collect();
+        let triangulation = match (missing.is_empty(), metallicities.len() > 1){
+            (true, _) => {None}
+            (false, true) => {
+                return Err(format!("the intensity grids with several metallicities must form a complete lattice, the nodes {} are missing",
+                    missing.join(", ")))
+            }
+            (false, false) => {
+                let coordinates:Vec<[f64;2]> = grid_indices.iter()
+                    .map(|n| [intensity_grids[*n].temperature(), intensity_grids[*n].log_gravity()])
+                    .collect();
+                Some(DelaunayInterpolator::new(&coordinates).map_err(|_| format!(
+                    "the intensity grids don't form a complete lattice (the nodes {} are missing) and the remaining ones lie on a line",
+                    missing.join(", ")))?)
+            }
+        };
+
+        Ok(Self{
+            temperatures,
+            log_gravities,
+            metallicities,
+            grid_indices,
+            triangulation,
+        })
+    }
+
+    /// Returns the effective temperatures of the nodes in increasing order.
+    pub fn temperatures(&self)->&[f64]{
+        &self.temperatures
+    }
+
+    /// Returns the logarithm of the surface gravity of the nodes in increasing order.
+    pub fn log_gravities(&self)->&[f64]{
+        &self.log_gravities
+    }
+
+    /// Returns the metallicities [M/H] of the nodes in increasing order, it's empty if the grids don't have a metallicity.
+    pub fn metallicities(&self)->&[f64]{
+        &self.metallicities
+    }
+
+    /// Returns `true` if there are at least two metallicities, i.e. the grids are interpolated in metallicity.
+    pub fn interpolates_metallicity(&self)->bool{
+        self.metallicities.len() > 1
+    }
+
+    /// Returns `true` if there is an intensity grid at every node of the lattice, otherwise the grids are interpolated
+    /// within the triangles of their Delaunay triangulation.
+    pub fn is_complete(&self)->bool{
+        self.triangulation.is_none()
+    }
+
+    /// Returns the index of the intensity grid at the node (`temperatures()[i]`, `log_gravities()[j]`, `metallicities()[m]`).
+    /// If the grids don't have a metallicity, `m` must be 0.
+    ///
+    /// It panics if the lattice is not complete, see [GridLattice::is_complete].
+    pub fn grid_index(&self, i:usize, j:usize, m:usize)->usize{
+        assert!(self.is_complete(), "the nodes of an incomplete lattice can't be indexed by their position");
+        self.grid_indices[(i*self.log_gravities.len()+j)*self.metallicities.len().max(1)+m]
+    }
+
+    /// Returns the box on the (T_eff, log g) plane covered by the lattice.
+    pub fn parameter_box(&self)->ParameterBox{
+        ParameterBox{
+            temperature: [self.temperatures[0], self.temperatures[self.temperatures.len()-1]],
+            log_gravity: [self.log_gravities[0], self.log_gravities[self.log_gravities.len()-1]],
+        }
+    }
+
+    /// Returns `true` if the point lies within the lattice on the (T_eff, log g) plane.
+    /// If the lattice is not complete, the point must lie within the convex hull of its nodes.
+    pub fn contains(&self, temperature:f64, log_gravity:f64)->bool{
+        match &self.triangulation{
+            None => {self.parameter_box().contains_point(temperature, log_gravity)}
+            Some(triangulation) => {triangulation.contains([temperature, log_gravity])}
+        }
+    }
+
+    /// Returns `true` if the metallicity lies within the metallicities of the lattice.
+    /// It's always `true` if the grids are not interpolated in metallicity.
+    pub fn contains_metallicity(&self, metallicity:Option<f64>)->bool{
+        match (self.interpolates_metallicity(), metallicity){
+            (false, _) => {true}
+            (true, Some(metallicity)) => {self.metallicities[0] <= metallicity && metallicity <= self.metallicities[self.metallicities.len()-1]}
+            (true, None) => {false}
+        }
+    }
+
+    /// This function finds the cell of the lattice that brackets a point on the (T_eff, log g, [M/H]) space.
+    /// ### Arguments:
+    /// * `temperature` - effective temperature of the point in K.
+    /// * `log_gravity` - logarithm of the surface gravity of the point.
+    /// * `metallicity` - metallicity of the point, it's only used if the grids are interpolated in metallicity.
+    /// ### Returns:
+    /// * `(i,j,m)` - the indices of the lower nodes, so the point lies within `temperatures()[i..=i+1]`, `log_gravities()[j..=j+1]`
+    ///   and `metallicities()[m..=m+1]`. `m` is 0 if the grids are not interpolated in metallicity.
+    ///   A point outside of the lattice gets the closest cell of the edge, so that it can be clamped or extrapolated.
+    pub fn bracket(&self, temperature:f64, log_gravity:f64, metallicity:Option<f64>)->(usize,usize,usize){
+        let m = match (self.interpolates_metallicity(), metallicity){
+            (true, Some(metallicity)) => {lower_node(&self.metallicities, metallicity)}
+            _ => {0}
+        };
+        (lower_node(&self.temperatures, temperature), lower_node(&self.log_gravities, log_gravity), m)
+    }
+
+    /// This function computes the weights of the intensity grids that interpolate a point on the (T_eff, log g, [M/H]) space.
+    /// On a complete lattice they're the multilinear weights of the corners of the cell that brackets the point (see [GridLattice::bracket]),
+    /// otherwise the barycentric weights of the vertices of the triangle that contains it.
+    /// ### Arguments:
+    /// * `temperature` - effective temperature of the point in K.
+    /// * `log_gravity` - logarithm of the surface gravity of the point.
+    /// * `metallicity` - metallicity of the point, it's only used if the grids are interpolated in metallicity.
+    /// * `bounds_handling` - what to do if the point lies outside of the lattice, see [BoundsHandling].
+    /// * `weights` - where the pairs `(index of the intensity grid, weight)` are written, its previous contents are discarded.
+    /// ### Returns:
+    /// * `Err(MathErrors::OutOfBounds)` - if the point lies outside of the lattice and `bounds_handling` is [BoundsHandling::Error],
+    ///   or the grids are interpolated in metallicity and the point doesn't have one.
+    pub fn corner_weights(&self, temperature:f64, log_gravity:f64, metallicity:Option<f64>, bounds_handling:BoundsHandling,
+        weights:&mut Vec<(usize,f64)>)->Result<(),MathErrors>{
+        weights.clear();
+        if let Some(triangulation) = &self.triangulation{
+            let vertices = triangulation.weights([temperature, log_gravity], bounds_handling)?;
+            weights.extend(vertices.iter().map(|(node,weight)| (self.grid_indices[*node], *weight)));
+            return Ok(())
+        }
+
+        let (i,t) = bracket(&self.temperatures, temperature, bounds_handling)?;
+        let (j,u) = bracket(&self.log_gravities, log_gravity, bounds_handling)?;
+        let (m,v) = if self.interpolates_metallicity(){
+            bracket(&self.metallicities, metallicity.ok_or(MathErrors::OutOfBounds)?, bounds_handling)?
+        }
+        else{ (0, 0.0)};
+        let metallicity_nodes = 1 + self.interpolates_metallicity() as usize;
+        for (di,weight_t) in [(0,1.0-t),(1,t)]{
+            for (dj,weight_u) in [(0,1.0-u),(1,u)]{
+                for (dm,weight_v) in [(0,1.0-v),(1,v)].into_iter().take(metallicity_nodes){
+                    weights.push((self.grid_index(i+di, j+dj, m+dm), weight_t*weight_u*weight_v));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the description of a node used on the error messages.
+fn describe_node(temperature:f64, log_gravity:f64, metallicity:Option<f64>)->String{
+    match metallicity{
+        Some(metallicity) => {format!("T_eff = {:.1} K, log g = {:.4}, [M/H] = {:.2}", temperature, log_gravity, metallicity)}
+        None => {format!("T_eff = {:.1} K, log g = {:.4}", temperature, log_gravity)}
+    }
+}
+
+/// Returns the different values of `values` in increasing order.
+fn sorted_nodes(values:impl Iterator<Item = f64>)->Vec<f64>{
+    let mut nodes:Vec<f64> = values.collect();
+    nodes.sort_by(f64::total_cmp);
+    nodes.dedup();
+    nodes
+}
+
+/// Returns the position of `value` within the nodes of an axis, it must be one of them.
+fn node_position(nodes:&[f64], value:f64)->usize{
+    nodes.partition_point(|node| *node < value)
+}
+
+/// Returns the index of the lower node of the interval of an axis (with at least two nodes) that contains `value`.
+fn lower_node(nodes:&[f64], value:f64)->usize{
+    nodes.partition_point(|node| *node <= value).saturating_sub(1).min(nodes.len()-2)
+}
@@ -0,0 +1,79 @@
+use super::*;
+use ndarray::Array2;
+
+/// Number of terms of the limb-darkening law fitted to the µ values of the grids, `I(µ) = a_0 + a_1 (1-µ) + a_2 (1-µ)^2`.
+/// Grids with only two µ values are fitted with the linear law.
+const LAW_TERMS: usize = 3;
+
+/// Returns the terms of the limb-darkening law at `mu`, `[1, (1-µ), (1-µ)^2]`.
+fn law_terms(mu:f64)->[f64;LAW_TERMS]{
+    [1.0, 1.0-mu, (1.0-mu).powi(2)]
+}
+
+/// This function computes the least-squares fit of the limb-darkening law to intensities given at some µ values.
+/// The fit is linear in the intensities, so it's returned as the matrix that maps them to the coefficients of the law.
+/// ### Arguments:
+/// * `mu_values` - the µ values of the intensities, there must be at least two distinct ones.
+/// ### Returns:
+/// * an [Array2] with one row per coefficient of the law and one column per µ value.
+pub(crate) fn limb_darkening_fit(mu_values:&[f64])->Array2<f64>{
+    let nterms = LAW_TERMS.min(mu_values.len());
+    let design = Array2::from_shape_fn((mu_values.len(),nterms), |(row,term)| law_terms(mu_values[row])[term]);
+
+    // Solve the normal equations (AᵀA) X = Aᵀ with Gauss-Jordan elimination, AᵀA is small and positive definite.
+    let mut normal = design.t().dot(&design);
+    let mut fit = design.t().to_owned();
+    for pivot in 0..nterms{
+        let best = (pivot..nterms).max_by(|a,b| normal[[*a,pivot]].abs().total_cmp(&normal[[*b,pivot]].abs())).unwrap();
+        for column in 0..nterms {normal.swap([pivot,column],[best,column])}
+        for column in 0..mu_values.len() {fit.swap([pivot,column],[best,column])}
+        let scale = normal[[pivot,pivot]];
+        normal.row_mut(pivot).mapv_inplace(|value| value/scale);
+        fit.row_mut(pivot).mapv_inplace(|value| value/scale);
+        for row in (0..nterms).filter(|row| *row != pivot){
+            let factor = normal[[row,pivot]];
+            let (normal_pivot,fit_pivot) = (normal.row(pivot).to_owned(),fit.row(pivot).to_owned());
+            normal.row_mut(row).scaled_add(-factor, &normal_pivot);
+            fit.row_mut(row).scaled_add(-factor, &fit_pivot);
+        }
+    }
+    fit
+}
+
+impl SpectralGrid{
+    /// This function returns the weights of the intensities of every µ value of the grids that give the intensity at `mu`,
+    /// as pairs `(index of the µ value, weight)`.
+    ///
+    /// Within the µ range of the grids the intensities are interpolated linearly between the two closest µ values.
+    /// With a cubic [crate::InterpolationScheme] along µ they aren't a weighted sum, so no weights are given and the index
+    /// of the µ value right below `mu` is returned instead.
+    /// Towards the limb (µ→0) and the disk centre (µ→1) they're extrapolated from the closest µ value with the limb-darkening law
+    /// fitted to all of them, `I(µ) = I(µ_edge) + L(µ) - L(µ_edge)`, which is continuous at the edges of the range.
+    /// ### Arguments:
+    /// * `mu` - cos(χ) of the surface cell.
+    /// * `weights` - where the weights are written, its previous contents are discarded.
+    /// ### Returns:
+    /// * the index of the µ value right below `mu` if it's interpolated with a cubic scheme, or [None] if the weights are given.
+    pub(crate) fn mu_weights(&self, mu:f64, weights:&mut Vec<(usize,f64)>)->Option<usize>{
+        weights.clear();
+        let highest_mu = self.mu_values.len()-1;
+        let edge = if mu < self.mu_values[0] {0}
+            else if mu > self.mu_values[highest_mu] {highest_mu}
+            else{
+                let index = self.return_mu_index(mu);
+                if self.interpolation.mu != InterpolationScheme::Linear {return Some(index)}
+                let fraction = (mu-self.mu_values[index])/(self.mu_values[index+1]-self.mu_values[index]);
+                weights.extend([(index,1.0-fraction),(index+1,fraction)]);
+                return None
+            };
+
+        let (terms,edge_terms) = (law_terms(mu),law_terms(self.mu_values[edge]));
+        for (index,coefficients) in self.limb_darkening_fit.columns().into_iter().enumerate(){
+            let law_difference:f64 = coefficients.iter().enumerate()
+                .map(|(term,coefficient)| coefficient*(terms[term]-edge_terms[term]))
+                .sum();
+            weights.push((index, law_difference + if index == edge {1.0} else {0.0}));
+        }
+        None
+    }
+}
@@ -39,27 +39,15 @@ use polars::prelude::*;
             }*/
 //
 
-pub fn convert_joris_grid_to_regular_grid(joris_lf:LazyFrame)->LazyFrame{
-    let mu_values=[0.9636,0.8864,0.8018,0.7071,0.5976,0.4629,0.2623];
-
-    joris_lf.clone().select([
-        col("wavelengths"),
-        append_specific_intensity(mu_values[0]).alias("mu1_s"),
-        append_specific_intensity(mu_values[1]).alias("mu2_s"),
-        append_specific_intensity(mu_values[2]).alias("mu3_s"),
-        append_specific_intensity(mu_values[3]).alias("mu4_s"),
-        append_specific_intensity(mu_values[4]).alias("mu5_s"),
-        append_specific_intensity(mu_values[5]).alias("mu6_s"),
-        append_specific_intensity(mu_values[6]).alias("mu7_s"),
-        append_continuum_intensity(mu_values[0]).alias("mu1_c"),
-        append_continuum_intensity(mu_values[1]).alias("mu2_c"),
-        append_continuum_intensity(mu_values[2]).alias("mu3_c"),
-        append_continuum_intensity(mu_values[3]).alias("mu4_c"),
-        append_continuum_intensity(mu_values[4]).alias("mu5_c"),
-        append_continuum_intensity(mu_values[5]).alias("mu6_c"),
-        append_continuum_intensity(mu_values[6]).alias("mu7_c"),
-    ])
-    
+/// This function evaluates the limb darkening law of a Joris grid on the given µ values, which produces the columns of a Nadya-like grid:
+/// `mu1_s`, `mu2_s`, ... for the specific intensity and `mu1_c`, `mu2_c`, ... for the continuum, in the order of `mu_values`.
+pub fn convert_joris_grid_to_regular_grid(joris_lf:LazyFrame, mu_values:&[f64])->LazyFrame{
+    let mut columns = vec![col("wavelengths")];
+    columns.extend(mu_values.iter().enumerate()
+        .map(|(n,mu)| append_specific_intensity(*mu).alias(format!("mu{}_s",n+1))));
+    columns.extend(mu_values.iter().enumerate()
+        .map(|(n,mu)| append_continuum_intensity(*mu).alias(format!("mu{}_c",n+1))));
+    joris_lf.clone().select(columns)
 }
 
 fn append_specific_intensity(mu:f64)->Expr{
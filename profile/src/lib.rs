@@ -1,7 +1,8 @@
 use polars::{error::ErrString, prelude::*};
 use serde::Deserialize;
 use temp_name_lib::type_def::{CLIGHT,N_FLUX_POINTS};//Velocity of light in m/s
-use ndarray;
+use temp_name_lib::utils::MathErrors;
+use temp_name_lib::interpolation::{BoundsHandling,DelaunayInterpolator,HermiteSlopes,cubic_hermite_interpolation,linear_interpolation};
 
 use std::fs;
 
@@ -14,8 +15,56 @@ use std::fs;
 
 mod intensity;
 pub mod utils;
+/// This module contains the [error::ProfileError] type returned by the public entry points of the library.
+pub mod error;
+use crate::error::ProfileError;
 pub mod profile_mkr;
-pub mod regresor_template;
+/// This module builds the catalogue of the intensity grids of a directory out of their file names,
+/// so that a [ProfileConfig] can reference the grid directory only.
+pub mod catalogue;
+/// This module contains the analytic atmospheres ([IntensityGrid::Analytic]), whose intensities follow a limb-darkening law
+/// and an intrinsic line profile instead of being read from intensity grid files.
+pub mod analytic;
+/// This module contains the [emulator::Emulator] ([IntensityGrid::Emulator]), a neural network that maps the parameters
+/// of an atmosphere to the coefficients of a principal component basis of its spectra.
+///
+/// The networks are small stacks of dense layers, so they are evaluated by the module itself instead of an inference crate, and their weights
+/// are read from a toml file, the supported format of the emulators. A trained network is exported by writing its arrays into the keys below:
+/// * `inputs` - the parameters of the atmosphere fed to the network, in order, out of `"Temperature"` (K), `"LogGravity"`,
+///   `"Metallicity"` ([M/H]) and `"Mu"` (cos χ), see [emulator::EmulatorInput].
+/// * `offset`, `scale` - the standardization of the inputs before the first layer, `(x - offset)/scale`, a value per input. The scales can't be zero.
+/// * `domain` - the range `[lower, upper]` of every input covered by the training set. The cells outside of it follow the `out_of_bounds` policy.
+/// * `[[layers]]` - the dense layers, `activation(weights · x + biases)`, from the first to the last one:
+///   * `weights` - a row per output of the layer with a value per output of the previous layer (or per input for the first one),
+///     the layout of the weights of the linear layers of PyTorch (the transpose of Keras' kernels),
+///   * `biases` - a value per output of the layer,
+///   * `activation` - `"Linear"`, `"Relu"`, `"Tanh"` or `"Sigmoid"`, see [emulator::Activation].
+/// * `[reconstruction]` - the principal component basis of the spectra. The outputs of the last layer are the coefficients `z_k` of
+///   the components, and the spectrum is `mean + Σ_k z_k √σ²_k c_k`:
+///   * `wavelengths` - at least two wavelengths in nm, in increasing order,
+///   * `mean` - the mean spectrum, the specific intensities at `wavelengths` followed by the continuum intensities,
+///   * `components` - the components `c_k`, a row per output of the last layer with as many values as `mean`,
+///   * `explained_variance` - optional, the variances `σ²_k` of the components if the coefficients are whitened (`whiten = True`
+///     in scikit-learn), otherwise `√σ²_k = 1`,
+///   * `log10` - optional, `true` if the spectra are log10 of the intensities. It defaults to `false`.
+///
+/// [emulator::Emulator::from_file] checks that the sizes of the arrays match and returns a [error::ProfileError::GridFormat] otherwise.
+/// The file `tests/fixtures/emulator/emulator.toml` is a complete example.
+pub mod emulator;
+/// This module contains the [provider::IntensityProvider] trait, the common interface of the sources of specific intensities
+/// over which the fluxes are integrated.
+pub mod provider;
+/// This module contains the checks performed on a [ProfileConfig] before loading the intensity grids,
+/// the check of a complete run against the star produced by a [pulstar::PulstarConfig],
+/// and the preflight check of a rasterized star against the loaded [SpectralGrid].
+pub mod validation;
+/// This module contains the [spectrum_cache::SpectrumCache], which evaluates the spectrum of an [provider::IntensityProvider] once
+/// for all of the surface cells with the same atmosphere up to a tolerance.
+pub mod spectrum_cache;
+
+/// Surface cells with `coschi` not above this value face away from the observer and are not integrated.
+/// The visible cells close to the limb are integrated with the intensities extrapolated to their µ, see [ProfileConfig::mu_values].
+pub const MIN_VISIBLE_COSCHI: f64 = 0.0;
 
 /// This structure holds the data to construct the synthetic normalized flux.
 #[derive(Clone)]
@@ -24,12 +73,14 @@ pub struct FluxOfSpectra{
     pub time: Vec<f64>,
     /// [Vec<f64>] containing the current observed wavelengths.  
     pub wavelengths: Vec<f64>,
-    /// [Vec<f64>] the doppler shifted wavelengths due to the total velocity.
-    pub shifted_wavelength:Vec<f64>,
     /// [Vec<f64>] cointaining the flux of the observed wavelengths.  
     pub flux: Vec<f64>,
     /// [Vec<f64>] containing the flux of the continuum expectra (i.e. blackbody radiation) if the requested wavelenghts. 
     pub continuum:Vec<f64>,
+    /// What to do with the surface cells that lie outside of the intensity grids.
+    pub out_of_bounds: OutOfBoundsPolicy,
+    /// Number of visible surface cells of the current phase that lie outside of the intensity grids.
+    pub out_of_bounds_cells: u32,
 }
 
 
@@ -47,42 +98,90 @@ pub struct SurfaceCell{
     rel_dlamb: f64,
     ///Total velocity. It is not 
     v_tot: f64,
+    ///Metallicity [M/H], if the star has one.
+    metallicity: Option<f64>,
 }
 
 ///Contains the relevant information to perform interpolation and get specific intensity values. 
 #[derive(Clone)]
 pub struct SpectralGrid {
-        /// Effective temperature of the plane parallel atmosphere.
-        t_eff:[f64;2],
-        /// Logarithm of the surface gravity of a plane parallel atmosphere. 
-        log_g:[f64;2],
-        /// Specific intensity and continuum intensity values dependant of the wavelength and χ.
-        grid_values:ndarray::Array3<f64>,
+        /// Effective temperatures and surface gravities of the plane parallel atmospheres, and the intensity grid of each node.
+        lattice:GridLattice,
+        /// Specific intensity and continuum intensity values dependant of the wavelength and χ, in memory or memory mapped.
+        grid_values:utils::grid_cache::GridValues,
         /// Array containing the wavelengths. 
         wavelengths: Vec<f64>,
-        /// µ=sqrt(cos(χ)),
+        /// The step between the `wavelengths` if they're uniformly sampled, they're then indexed in constant time instead of by bisection
+        /// (see [SpectralGrid::wavelength_step]).
+        wavelength_step: Option<f64>,
+        /// µ=cos(χ) in increasing order, they're read from the intensity grids (see [ProfileConfig::mu_values]),
         /// where χ is the angle of the normal of a parallel atmosphere plane with respect to the unit vector in direction of the observer.
-        mu_values:[f64;7],
+        mu_values:Vec<f64>,
+        /// Least-squares fit of the limb-darkening law to the intensities at `mu_values`, used to extrapolate them outside of the µ range.
+        limb_darkening_fit:ndarray::Array2<f64>,
+        /// How the intensities are interpolated along the wavelength and µ axes.
+        interpolation:GridInterpolation,
+}
+
+/// The working memory of the interpolation of the intensity grids, see [provider::IntensityProvider::new_scratch].
+pub struct GridScratch{
+        /// Indices in `grid_values` of the intensity grids that interpolate the current surface cell and their weights,
+        /// see [GridLattice::corner_weights].
+        corner_weights:Vec<(usize,f64)>,
+        /// Weights of the µ values of the grids that give the intensities at the µ of the current surface cell.
+        mu_weights:Vec<(usize,f64)>,
+        /// Index of the µ value of the grids right below the µ of the current surface cell, if the intensities are interpolated
+        /// along µ with a cubic scheme instead of `mu_weights`.
+        mu_index:Option<usize>,
+        /// Intensities at the nodes of the µ axis used by a cubic interpolation.
+        mu_stencil_values:Vec<f64>,
+        /// Index of the grid wavelength right below each wavelength of the current surface cell.
+        wavelength_indices:Vec<usize>,
+        /// Specific intensities at the µ of the current surface cell of the rows of a grid needed by the wavelengths.
+        specific_rows:Vec<f64>,
+        /// Continuum intensities at the µ of the current surface cell of the rows of a grid needed by the wavelengths.
+        continuum_rows:Vec<f64>,
+}
 
-        /// Important indices 
-        row_indices: Vec<usize>
+/// The nodes on the (T_eff, log g, [M/H]) space of the intensity grids.
+///
+/// The intensity grids usually form a complete lattice, i.e. every temperature is available for every log g and metallicity,
+/// with at least two nodes along the temperature and log g axes. Each surface cell is then interpolated within the lattice cell that brackets it.
+/// The grids are only interpolated in metallicity if there are at least two metallicities.
+///
+/// Collections of grids often miss some nodes, e.g. near the Eddington limit. If they have a single metallicity, the surface cells are
+/// interpolated within the triangles of the Delaunay triangulation of the nodes on the (T_eff, log g) plane instead.
+#[derive(Clone,Debug,PartialEq)]
+pub struct GridLattice{
+    /// Effective temperatures of the nodes in increasing order.
+    temperatures: Vec<f64>,
+    /// Logarithm of the surface gravity of the nodes in increasing order.
+    log_gravities: Vec<f64>,
+    /// Metallicities [M/H] of the nodes in increasing order. It's empty if the grids don't have a metallicity (Joris' grids).
+    metallicities: Vec<f64>,
+    /// Index of the intensity grid of each node, the node `(i,j,m)` is stored at `(i*log_gravities.len()+j)*metallicities.len().max(1)+m`.
+    /// If the lattice is incomplete, it holds the grid of every node of the triangulation in the same order, skipping the missing ones.
+    grid_indices: Vec<usize>,
+    /// The triangulation of the nodes on the (T_eff, log g) plane, only if some nodes of the lattice are missing.
+    triangulation: Option<DelaunayInterpolator>,
 }
 
 impl FluxOfSpectra{
     /// This function creates a new instance of [FluxOfSpectra] where all of its members contain values of only 0.0.
     /// This function should be used to construct a mutable instance at the beginning of the profile program. 
-    pub fn new(profile_input: &ProfileConfig)->FluxOfSpectra{
-        let wavelengths = profile_input.wavelength_range.get_wavelength_vector();
-        let shifted_wavelengths = wavelengths.clone();
+    /// It returns a [ProfileError] if the wavelength range of the configuration is ill defined.
+    pub fn new(profile_input: &ProfileConfig)->Result<FluxOfSpectra,ProfileError>{
+        let wavelengths = profile_input.wavelength_range.get_wavelength_vector()?;
         let time = vec![0.0;wavelengths.len()];
         let flux = vec![0.0;wavelengths.len()];
         let continuum = vec![0.0;wavelengths.len()];
 
-        FluxOfSpectra { time: time,
+        Ok(FluxOfSpectra { time: time,
 			wavelengths: wavelengths,
-			shifted_wavelength: shifted_wavelengths,
 			flux: flux,
-			continuum: continuum }
+			continuum: continuum,
+			out_of_bounds: profile_input.out_of_bounds,
+			out_of_bounds_cells: 0 })
     }
 
     /// This function sets the specific intensity flux and continuum specific intensity as 0.0, it also stores the new phase of pulsation of the calculation. 
@@ -90,13 +189,7 @@ impl FluxOfSpectra{
          self.time.fill(time_point);
          self.flux.fill(0.0);
          self.continuum.fill(0.0);
-    }
-
-    /// This function fills the `shifted_wavelength` member of [FluxOfSpectra] by multiplying the wavelength vector  with the relative doppler shift stored in a [SurfaceCell]. 
-    pub fn get_doppler_shifted_wavelengths(&mut self,cell:&SurfaceCell){
-        for (n,wavelength) in self.wavelengths.iter().enumerate(){
-            self.shifted_wavelength[n] = wavelength * cell.rel_dlamb;
-        }
+         self.out_of_bounds_cells = 0;
     }
 }
 
@@ -112,8 +205,131 @@ pub struct ProfileConfig{
     pub wavelength_range:WavelengthRange,
     /// This is the path to the directory containing the intensity grids
     pub path_to_grids: String,
-    /// This is a [Vec] collection of [IntensityGrid]s. 
+    /// This is a [Vec] collection of [IntensityGrid]s. It's optional, if it's not given the grids are taken from the catalogue of `path_to_grids`
+    /// (see [ProfileConfig::fill_intensity_grids_from_catalogue]).
+    #[serde(default)]
     pub intensity_grids:Vec<IntensityGrid>,
+    /// What to do with the surface cells that lie outside of the intensity grids. It's optional, by default the run stops with an error.
+    #[serde(default)]
+    pub out_of_bounds: OutOfBoundsPolicy,
+    /// How the intensity grids are stored during the run. It's optional, by default they're loaded into memory.
+    #[serde(default)]
+    pub grid_backend: GridBackend,
+    /// The µ values of the columns of the intensity grid files, in the order of the columns, which are also the µ values where the
+    /// limb darkening law of Joris' grids is evaluated. It's optional, by default the ones of Nadya's grids ([NADYA_MU_VALUES]).
+    /// A grid file can list its own µ values in a first line starting with `# mu`, e.g. `# mu 0.9636 0.8864 ... 0.2673`, which take precedence.
+    ///
+    /// µ is cos(χ) of the surface cells, it's interpolated between the µ values of the grids (see [ProfileConfig::interpolation]). Towards the limb and the disk centre
+    /// the intensities are extrapolated with the limb-darkening law `I(µ) = a_0 + a_1 (1-µ) + a_2 (1-µ)^2` fitted to them at every wavelength.
+    #[serde(default = "default_mu_values")]
+    pub mu_values: Vec<f64>,
+    /// How the intensities of the grids are interpolated along the wavelength and µ axes. It's optional, by default linearly.
+    #[serde(default)]
+    pub interpolation: GridInterpolation,
+    /// Share the spectra of the surface cells whose atmospheres agree within the tolerances of a [spectrum_cache::SpectrumCacheConfig],
+    /// only the Doppler shift is applied to every cell (see [spectrum_cache::SpectrumCache]). It's optional, by default every cell is evaluated on its own.
+    #[serde(default)]
+    pub spectrum_cache: Option<spectrum_cache::SpectrumCacheConfig>,
+}
+
+/// The µ values of the columns of Nadya's intensity grids, in the order of the columns.
+#[allow(clippy::approx_constant)] // 0.7071 is the tabulated µ value, not 1/sqrt(2).
+pub const NADYA_MU_VALUES: [f64;7] = [0.9636,0.8864,0.8018,0.7071,0.5976,0.4629,0.2673];
+
+fn default_mu_values()->Vec<f64>{
+    NADYA_MU_VALUES.to_vec()
+}
+
+/// This enum indicates how the intensities of the grids are stored during the run.
+/// On the toml file it's given as e.g. `grid_backend = "MemoryMapped"`.
+#[derive(Deserialize,Debug,PartialEq,Clone,Copy,Default)]
+pub enum GridBackend{
+    /// The rows of the grids needed for the observed wavelengths are loaded into memory.
+    #[default]
+    InMemory,
+    /// The binary caches of the grids (see [utils::grid_cache]) are mapped into memory, so that wide wavelength ranges
+    /// and large lattices don't need to fit into RAM. The missing caches are written before the run.
+    MemoryMapped,
+    /// The intensities are reconstructed from the principal components of the grids written by `profile compress`
+    /// (see [utils::pca_grid]), the grid files themselves aren't needed.
+    Compressed,
+}
+
+/// The interpolation scheme along each axis of the intensity grids that admits a choice.
+/// On the toml file it's given as e.g. `interpolation = {wavelength = "Monotone", mu = "CatmullRom"}`, the axes left out are interpolated linearly.
+/// The effective temperature, log g and metallicity are always interpolated linearly.
+#[derive(Deserialize,Debug,PartialEq,Clone,Copy,Default)]
+pub struct GridInterpolation{
+    /// Scheme along the wavelengths of the grids.
+    #[serde(default)]
+    pub wavelength: InterpolationScheme,
+    /// Scheme along the µ values of the grids, within their range. Outside of it the intensities are extrapolated with the fitted limb-darkening law.
+    #[serde(default)]
+    pub mu: InterpolationScheme,
+}
+
+/// This enum indicates how the intensities are interpolated between two nodes of an axis of the grids.
+#[derive(Deserialize,Debug,PartialEq,Clone,Copy,Default)]
+pub enum InterpolationScheme{
+    /// Linear interpolation between the two nodes.
+    #[default]
+    Linear,
+    /// Cubic Hermite interpolation with the slopes of the neighbouring nodes, see [HermiteSlopes::CatmullRom].
+    /// It follows sharp line cores better than the linear one, but it can overshoot next to steep changes.
+    CatmullRom,
+    /// Cubic Hermite interpolation that preserves the monotonicity of the intensities, see [HermiteSlopes::Monotone].
+    Monotone,
+}
+
+impl InterpolationScheme{
+    /// Returns the indices of the nodes used to interpolate between the nodes `index` and `index+1` of an axis with `len` nodes,
+    /// the two nodes for [InterpolationScheme::Linear] and also their neighbours, if there are any, for the cubic schemes.
+    pub fn stencil(&self, index:usize, len:usize)->std::ops::Range<usize>{
+        match self{
+            Self::Linear => {index..index+2}
+            Self::CatmullRom | Self::Monotone => {index.saturating_sub(1)..(index+3).min(len)}
+        }
+    }
+
+    /// This function interpolates the values at the nodes of a stencil.
+    /// ### Arguments:
+    /// * `nodes` and `values` - the coordinates of the nodes given by [InterpolationScheme::stencil] and the values at them.
+    /// * `index` - the position in `nodes` of the node right below `x`.
+    /// * `x` - the coordinate of the point, between `nodes[index]` and `nodes[index+1]`.
+    pub fn interpolate(&self, nodes:&[f64], values:&[f64], index:usize, x:f64)->f64{
+        match self{
+            Self::Linear => {linear_interpolation(&values[index..=index+1], (x-nodes[index])/(nodes[index+1]-nodes[index]))}
+            Self::CatmullRom => {cubic_hermite_interpolation(nodes, values, index, x, HermiteSlopes::CatmullRom)}
+            Self::Monotone => {cubic_hermite_interpolation(nodes, values, index, x, HermiteSlopes::Monotone)}
+        }
+    }
+}
+
+/// This enum indicates what to do with a surface cell whose temperature or log g lie outside of the intensity grids.
+/// On the toml file it's given as e.g. `out_of_bounds = "Clamp"`.
+#[derive(Deserialize,Debug,PartialEq,Clone,Copy,Default)]
+pub enum OutOfBoundsPolicy{
+    /// Stop the run with an error.
+    #[default]
+    Error,
+    /// Use the intensities at the closest edge of the grids.
+    Clamp,
+    /// Extend the multilinear interpolation beyond the grids.
+    Extrapolate,
+    /// Leave the cell out of the integration.
+    Skip,
+}
+
+impl OutOfBoundsPolicy{
+    /// Returns how the interpolation should treat a point outside of the grids. 
+    /// Cells are skipped before being interpolated, so [OutOfBoundsPolicy::Skip] is treated as an error.
+    pub fn bounds_handling(&self)->BoundsHandling{
+        match self{
+            Self::Error | Self::Skip => {BoundsHandling::Error}
+            Self::Clamp => {BoundsHandling::Clamp}
+            Self::Extrapolate => {BoundsHandling::Extrapolate}
+        }
+    }
 }
 /// The wave length range is defined in nanometers.
 /// The start should be bigger than the end and the step should be reasonable enough
@@ -128,6 +344,9 @@ pub struct WavelengthRange{
 /// - the file name stored as a string,
 /// - the temperature in Kelvin
 /// - the logarithm of the surface gravity
+///
+/// except for the [IntensityGrid::Analytic] atmospheres, which cover every temperature and surface gravity,
+/// and the [IntensityGrid::Emulator], which covers the parameters of its training set.
 #[derive(Deserialize,Debug,PartialEq)]
 pub enum IntensityGrid{
     Joris{
@@ -142,7 +361,15 @@ pub enum IntensityGrid{
         log_gravity: f64,
         metalicity: f64,
         filename:String,
-    }
+    },
+    /// An atmosphere described by a limb-darkening law and an intrinsic line profile, it doesn't need grid files.
+    /// It must be the only intensity grid of the configuration.
+    Analytic(analytic::AnalyticAtmosphere),
+    /// A neural network that emulates the intensities, `model` is the name of its weights file in `path_to_grids` (see [emulator::Emulator]).
+    /// It must be the only intensity grid of the configuration.
+    Emulator{
+        model: String,
+    },
 }
 
 //maybe I'll use this struct CommonGridId{ temp, logg, fname}
@@ -153,23 +380,15 @@ impl ProfileConfig {
     /// #### Arguments:
     /// * `path_to_file` - this is a string that indicates the path to the `profile_input.toml` file
     /// #### Returns:
-    // * new instance of the profile config structure.
-    pub fn read_from_toml(path_to_file:&str)->Self{
-        let contents = match fs::read_to_string(path_to_file){
-            Ok(c)=>c,
-             Err(_) => { panic!("Could not read file {}",path_to_file)}
-            };
-        let params: ProfileConfig = match toml::from_str(&contents){
-            Ok(d) => d,
-            Err(e) => {println!("Unable to load data from {}",path_to_file);
-                panic!("error {}",e)}
-        }; 
-
-        match params.intensity_grids_are_loaded(){
-            Ok(()) => { params }
-            Err(e) => { panic!("Intensity files are not properly loaded into the directory, '{}' not found.
- Either load the file into the directory or check the toml file to see if there was a misspelling",e)}
-        }
+    /// * new instance of the profile config structure, or a [ProfileError] if the file could not be read or parsed,
+    ///   if the catalogue of the grid directory could not be loaded, or if one of the intensity grid files is not in the grid directory.
+    pub fn read_from_toml(path_to_file:&str)->Result<Self,ProfileError>{
+        let contents = fs::read_to_string(path_to_file)?;
+        let mut params: ProfileConfig = toml::from_str(&contents)?; 
+        params.fill_intensity_grids_from_catalogue()?;
+
+        params.intensity_grids_are_loaded()?;
+        Ok(params)
     }
 
 }
@@ -177,17 +396,21 @@ impl ProfileConfig {
 impl WavelengthRange{
     /// This method returns the wavelength vector out of  the range specified on the toml file
     /// It also checks if the step size is reasonable enough
-    pub fn get_wavelength_vector(&self)->Vec<f64>{
-        if self.end < self.start {panic!("Wave length range is ill defined. Please correct the toml file")}
+    /// ### Returns:
+    /// * `Ok(Vec<f64>)` - the observed wavelengths.
+    /// * `Err(ProfileError::InvalidConfig)` - if the range is ill defined or too many flux points are requested.
+    pub fn get_wavelength_vector(&self)->Result<Vec<f64>,ProfileError>{
+        if self.end < self.start {return Err(ProfileError::InvalidConfig(String::from("wave length range is ill defined, the start is bigger than the end")))}
+        if self.step <= 0.0 {return Err(ProfileError::InvalidConfig(String::from("wave length step must be positive")))}
         let capacity = ((self.end-self.start)/self.step).floor() as usize + 1usize;
-        if capacity >= N_FLUX_POINTS as usize {panic!("Error, too many flux points requested.")}
+        if capacity >= N_FLUX_POINTS as usize {return Err(ProfileError::InvalidConfig(format!("too many flux points requested ({}), the maximum is {}",capacity,N_FLUX_POINTS)))}
 	    
         let mut wavelength:Vec<f64> = Vec::with_capacity(capacity);
 	    wavelength.push(self.start);
 	    for i in 0..=capacity {//<- inclussive loop so wavelength[capacity]==λ_f.
 	            wavelength.push( self.start + self.step * (i as f64) );
         }
-        wavelength
+        Ok(wavelength)
     }
 }
 
@@ -213,10 +436,10 @@ pub fn insert_col_relative_dlambda(lf:LazyFrame)// I take ownership of the data
 /// * `column_name` - a string slice that holds the name of a column. The column should hold f64 values.
 /// * `df`- a polars DataFrame
 /// ### Returns:
-/// * `Vec<f64>` - a vector that contains all of the values on the column.
-fn extract_column_as_vectorf64(column_name: &str,df:&DataFrame)->Vec<f64>{
-    let column = df.column(column_name).unwrap();
-    column.f64().unwrap().into_iter().flatten().collect()
+/// * `PolarsResult<Vec<f64>>` - a vector that contains all of the values on the column, or an error if the column is missing or doesn't hold f64 values.
+fn extract_column_as_vectorf64(column_name: &str,df:&DataFrame)->PolarsResult<Vec<f64>>{
+    let column = df.column(column_name)?;
+    Ok(column.f64()?.into_iter().flatten().collect())
 }
 
 /// This function takes a polars data frame and returns all of the values from a given column that holds string values. 
@@ -224,14 +447,14 @@ fn extract_column_as_vectorf64(column_name: &str,df:&DataFrame)->Vec<f64>{
 /// * `column_name` - a string slice that holds the name of a column. The column should hold String values.
 /// * `df`- a polars DataFrame
 /// ### Returns:
-/// * `Vec<String>` - a vector that contains all of the values on the column.
-fn extract_column_as_vector_string (column_name: &str, df:&DataFrame)->Vec<String>{
-    let column = df.column(column_name).unwrap();
-    let vec_str:Vec<&str> = column.str().unwrap().into_iter().flatten().collect();
+/// * `PolarsResult<Vec<String>>` - a vector that contains all of the values on the column, or an error if the column is missing or doesn't hold strings.
+fn extract_column_as_vector_string (column_name: &str, df:&DataFrame)->PolarsResult<Vec<String>>{
+    let column = df.column(column_name)?;
+    let vec_str:Vec<&str> = column.str()?.into_iter().flatten().collect();
 
     let vecc:Vec<String> = vec_str.iter().map(|s| s.to_string()).collect();
 
-    vecc
+    Ok(vecc)
 }
 
 
@@ -240,15 +463,20 @@ impl SurfaceCell {
     /// ### Arguments: 
     /// `star` - A [DataFrame] that contains the pulstar's output, which is a rasterized information of the parameters of the star over its surface. 
     /// ### Retruns: 
-    /// - A [Vec] of [SurfaceCell]s that hold all of the local values. 
-    pub fn extract_cells_from_df(star: DataFrame)-> Vec<Self>{
-
-        let rel_dlamb_vector = extract_column_as_vectorf64("relative shift", & star);
-        let area_vector = extract_column_as_vectorf64("area", & star);
-        let coschi_vector = extract_column_as_vectorf64("coschi", & star);
-        let temperature_vector = extract_column_as_vectorf64("temperature", & star);
-        let log_g_vector = extract_column_as_vectorf64("log gravity", & star);
-        let velocity_vector = extract_column_as_vectorf64("velocity", & star);
+    /// - A [Vec] of [SurfaceCell]s that hold all of the local values, or a [PolarsError] if one of the columns is missing. 
+    pub fn extract_cells_from_df(star: DataFrame)-> PolarsResult<Vec<Self>>{
+
+        let rel_dlamb_vector = extract_column_as_vectorf64("relative shift", & star)?;
+        let area_vector = extract_column_as_vectorf64("area", & star)?;
+        let coschi_vector = extract_column_as_vectorf64("coschi", & star)?;
+        let temperature_vector = extract_column_as_vectorf64("temperature", & star)?;
+        let log_g_vector = extract_column_as_vectorf64("log gravity", & star)?;
+        let velocity_vector = extract_column_as_vectorf64("velocity", & star)?;
+        // The metallicity is optional, pulstar only writes it if it was given for the star.
+        let metallicity_vector = match star.column("metallicity"){
+            Ok(_) => {Some(extract_column_as_vectorf64("metallicity", & star)?)}
+            Err(_) => {None}
+        };
 
         let mut cells: Vec<SurfaceCell> = Vec::new();
         for index in 0..area_vector.len(){
@@ -258,10 +486,11 @@ impl SurfaceCell {
 				area: area_vector[index],
 				coschi: coschi_vector[index],
 				v_tot: velocity_vector[index],
-				rel_dlamb: rel_dlamb_vector[index] }
+				rel_dlamb: rel_dlamb_vector[index],
+				metallicity: metallicity_vector.as_ref().map(|metallicities| metallicities[index]) }
             )
         }
-        cells
+        Ok(cells)
     }
 }
 
@@ -273,31 +502,27 @@ impl SurfaceCell {
 /// * `vector` - a borrowed vector of `f64` 
 /// * `key` - a reference value.
 /// ### Returns:
-/// * `index` - a `usize` value witht the property that  `vector[index-1]<key<vector[index]`
-fn search_geq(vector:&[f64],key:f64)-> usize {
-    //non empty vector
-    let first_element =vector.get(0);
-    if let Some(first_value) = first_element{
-        if key <= *first_value {0usize}
-        else{
-            let size = vector.len();
-            let mut top = size - 1;
-            let mut bottom = 0;
-            let mut middle = bottom + (top - bottom) / 2;
-            if key > vector[top]{panic!("key value is too large")}
-
-            while bottom<top{
-                if vector[middle] <key{
-                    bottom = middle + 1;
-                } else {
-                    top = middle;
-                }
-                middle = bottom + (top - bottom)/2;
-            }
-            top
+/// * `Ok(index)` - a `usize` value witht the property that  `vector[index-1]<key<=vector[index]`, it's `0` only if the key is equal to the first element.
+/// * `Err(MathErrors::OutOfBounds)` - if the key is smaller than the first element or bigger than the last element of the vector, or if it's NaN.
+/// * `Err(MathErrors::NotAdequateNumberOfElements)` - if the vector is empty.
+fn search_geq(vector:&[f64],key:f64)-> Result<usize,MathErrors> {
+    let (Some(first_value),Some(last_value)) = (vector.first(),vector.last()) else {
+        return Err(MathErrors::NotAdequateNumberOfElements)
+    };
+    // The comparisons are false for NaN
+    if !(key >= *first_value && key <= *last_value) {return Err(MathErrors::OutOfBounds)}
+
+    let mut top = vector.len() - 1;
+    let mut bottom = 0;
+    while bottom<top{
+        let middle = bottom + (top - bottom)/2;
+        if vector[middle] <key{
+            bottom = middle + 1;
+        } else {
+            top = middle;
         }
     }
-    else{ panic!("empty vector!")}
+    Ok(top)
 }
 
 /// This function returns the maximum or minimum value of a column of [f64] from a [DataFrame]. This function is adviced to be used seldomly (as in outside of loops).
@@ -319,7 +544,7 @@ pub fn extremal_val_from_col(
             true => {lf.select([col(column_name)]).max().collect()?}
             false => {lf.select([col(column_name)]).min().collect()?}
         };
-        let max_val = extract_column_as_vectorf64(column_name, &df_maxval);
+        let max_val = extract_column_as_vectorf64(column_name, &df_maxval)?;
         match max_val.get(0){
             Some(value) => { Ok(*value)}
             None => {Err(PolarsError::InvalidOperation
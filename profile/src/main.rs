@@ -2,6 +2,13 @@ use std::env;
 use profile::*;
 use std::time::Instant;
 use profile::profile_mkr::*;
+use profile::validation::validate_run;
+use profile::provider::IntensityProvider;
+use profile::emulator::Emulator;
+use profile::error::ProfileError;
+use profile::spectrum_cache::{SpectrumCache,SpectrumCacheConfig};
+use polars::prelude::LazyFrame;
+use pulstar::{PulstarConfig,ParsingFromToml};
 
 fn main() {
 
@@ -13,8 +20,37 @@ fn main() {
    // 3) The rasterized star parquet file.
    let env_args:Vec<String> = env::args().collect(); 
    if env_args.len() < 3usize {
-    println!("Not enough arguments.");
-    panic!("USAGE: profile -- profile_input.toml rasterized_star.parquet");
+    eprintln!("Not enough arguments.");
+    eprintln!("USAGE: profile -- profile_input.toml rasterized_star.parquet");
+    eprintln!("       profile -- validate profile_input.toml [pulstar_input.toml]");
+    eprintln!("       profile -- catalogue path_to_grids");
+    eprintln!("       profile -- cache profile_input.toml");
+    eprintln!("       profile -- compress profile_input.toml [number_of_components]");
+    std::process::exit(1)
+   }
+
+   // `profile validate` only checks the configuration files and reports all of the problems found.
+   if env_args[1] == "validate" {
+    validate(&env_args[2..]);
+    return
+   }
+
+   // `profile catalogue` scans the grid directory and writes the index of the intensity grids found.
+   if env_args[1] == "catalogue" {
+    catalogue(&env_args[2]);
+    return
+   }
+
+   // `profile cache` converts the intensity grids of the configuration into binary caches that the following runs load instead of the csv files.
+   if env_args[1] == "cache" {
+    cache(&env_args[2]);
+    return
+   }
+
+   // `profile compress` writes the principal components of the intensity grids of the configuration, the runs with `grid_backend = "Compressed"` use them instead of the grids.
+   if env_args[1] == "compress" {
+    compress(&env_args[2], env_args.get(3));
+    return
    }
 
 
@@ -33,40 +69,199 @@ fn main() {
    // |--> Check if the Intensity Grid files exist.
    // |--> Initialize the profile parameters.
     let profile_config_path = env_args[1].clone();
-    let profile_config = ProfileConfig::read_from_toml(&profile_config_path);
+    let profile_config = match ProfileConfig::read_from_toml(&profile_config_path){
+        Ok(config)=>config,
+        Err(e) => { eprintln!("{}",e);
+            std::process::exit(1)}
+        };
+    let report = profile_config.validate();
+    if !report.is_valid(){
+        eprintln!("Validation of {}: {}",profile_config_path,report);
+        std::process::exit(1)
+    }
    
-    let mut fluxes = FluxOfSpectra::new(&profile_config);
+    let mut fluxes = match FluxOfSpectra::new(&profile_config){
+        Ok(fluxes)=>fluxes,
+        Err(e) => { eprintln!("{}",e);
+            std::process::exit(1)}
+        };
 
    //---------------------------------------- 
    //----Parsing rasterized_star.parquet-----
    //----------------------------------------
    // Obtain the lazy frame of the parquet file, Obtain the time points, obtain the theta points
-   let (lf,time_points)=parsing_star(&env_args[2].clone());
-   let (
-        mut spectral_grid,
-        mut hypercube3d,
-        mut hypercube4d,
-    )= loading_intensity_grids(lf.clone(), & profile_config);
+   let (lf,time_points)=match parsing_star(&env_args[2].clone()){
+        Ok(star)=>star,
+        Err(e) => { eprintln!("Could not read the rasterized star {}: {}",env_args[2],e);
+            std::process::exit(1)}
+        };
+
+   // An analytic atmosphere or an emulator doesn't need the intensity grids.
+   match (profile_config.analytic_atmosphere(), profile_config.emulator_model()){
+    (Some(atmosphere), _) => {
+        let model = match atmosphere.load(&profile_config.path_to_grids){
+            Ok(model)=>model,
+            Err(e) => { eprintln!("Unable to load the analytic atmosphere: {}",e);
+                std::process::exit(1)}
+            };
+        run_time_points(lf, &time_points, &model, &mut fluxes, profile_config.spectrum_cache, start_computing_time);
+    }
+    (None, Some(model)) => {
+        let emulator = match Emulator::from_file(&format!("{}{}", profile_config.path_to_grids, model)){
+            Ok(emulator)=>emulator,
+            Err(e) => { eprintln!("Unable to load the emulator: {}",e);
+                std::process::exit(1)}
+            };
+        run_time_points(lf, &time_points, &emulator, &mut fluxes, profile_config.spectrum_cache, start_computing_time);
+    }
+    (None, None) => {
+        let spectral_grid = match loading_intensity_grids(lf.clone(), & profile_config){
+            Ok(grids)=>grids,
+            Err(e) => { eprintln!("Unable to load the intensity grids: {}",e);
+                std::process::exit(1)}
+            };
+        run_time_points(lf, &time_points, &spectral_grid, &mut fluxes, profile_config.spectrum_cache, start_computing_time);
+    }
+   }
+    println!("finished computation for a star's pulsation");
+    println!("Total computation time is {:#?}",start_computing_time.elapsed());
+}
+
+/// Integrates the fluxes of every time point with an [IntensityProvider], through a [SpectrumCache] if the configuration sets one,
+/// and writes them into parquet files. It exits with an error code if the cache could not be created.
+fn run_time_points<P:IntensityProvider>(lf:LazyFrame, time_points:&[f64], provider:&P, fluxes:&mut FluxOfSpectra,
+    spectrum_cache:Option<SpectrumCacheConfig>, start_computing_time:Instant){
+    let Some(config) = spectrum_cache else{
+        write_time_points(lf, time_points, provider, fluxes, start_computing_time);
+        return
+    };
+    let cache = match relative_shift_range(lf.clone()).map_err(ProfileError::from)
+        .and_then(|shift_range| SpectrumCache::new(provider, config, &fluxes.wavelengths, shift_range)){
+        Ok(cache)=>cache,
+        Err(e) => { eprintln!("Unable to create the spectrum cache: {}",e);
+            std::process::exit(1)}
+        };
+    write_time_points(lf, time_points, &cache, fluxes, start_computing_time);
+    println!("{} spectra cached",cache.len());
+}
+
+/// Integrates the fluxes of every time point with an [IntensityProvider] and writes them into parquet files.
+/// It exits with an error code if a time point could not be computed or written.
+fn write_time_points<P:IntensityProvider>(lf:LazyFrame, time_points:&[f64], provider:&P, fluxes:&mut FluxOfSpectra, start_computing_time:Instant){
     //----------------------------------------------------------------
     //-------------- Collect fluxes for each time point  -------------
     //----------------------------------------------------------------
 
     //time loop    
     for (time_point_number,pulsation_phase) in time_points.iter().enumerate() {
-        fluxes.integrate(
-            lf.clone(),
-            *pulsation_phase,
-            & mut spectral_grid,
-            & mut hypercube3d,
-            & mut hypercube4d);
+        if let Err(e) = fluxes.integrate(lf.clone(), *pulsation_phase, provider){
+            eprintln!("Unable to compute the flux for time point {}: {}",pulsation_phase,e);
+            std::process::exit(1)
+        }
         println!("done computing flux");
 
         println!("finished collecting fluxes {}",pulsation_phase);
         println!("time_elapsed is {:?} seconds",start_computing_time.elapsed());
         
-        fluxes.write_output(time_point_number as u16).expect(&format!("Unable to write parquet file for {} time point",*pulsation_phase));
+        if let Err(e) = fluxes.write_output(time_point_number as u16){
+            eprintln!("Unable to write parquet file for {} time point: {}",*pulsation_phase,e);
+            std::process::exit(1)
+        }
 
     }
-    println!("finished computation for a star's pulsation");
-    println!("Total computation time is {:#?}",start_computing_time.elapsed());
+}
+
+/// Validates a profile configuration file, and if a pulstar configuration file is given also the complete run.
+/// It prints the report and exits with an error code if any error is found.
+fn validate(paths:&[String]){
+    let mut profile_config:ProfileConfig = match std::fs::read_to_string(&paths[0]){
+        Ok(contents)=> match toml::from_str(&contents){
+            Ok(config)=>config,
+            Err(e)=>{ eprintln!("Unable to parse {}: {}",paths[0],e);
+                std::process::exit(1)}
+        },
+        Err(e)=>{ eprintln!("Could not read file {}: {}",paths[0],e);
+            std::process::exit(1)}
+    };
+    if let Err(e) = profile_config.fill_intensity_grids_from_catalogue(){
+        eprintln!("Unable to load the catalogue of '{}': {}",profile_config.path_to_grids,e);
+        std::process::exit(1)
+    }
+    let report = match paths.get(1){
+        Some(pulstar_path)=>{
+            let pulstar_config = match std::fs::read_to_string(pulstar_path){
+                Ok(contents) => match PulstarConfig::read_from_toml(&contents){
+                    Ok(config)=>config,
+                    Err(e)=>{ eprintln!("Unable to parse {}: {}",pulstar_path,e);
+                        std::process::exit(1)}
+                },
+                Err(e)=>{ eprintln!("Could not read file {}: {}",pulstar_path,e);
+                    std::process::exit(1)}
+            };
+            validate_run(&profile_config, &pulstar_config)
+        }
+        None => {profile_config.validate()}
+    };
+    println!("Validation: {}",report);
+    if !report.is_valid(){
+        std::process::exit(1)
+    }
+}
+
+/// Writes the catalogue of the intensity grids of a directory and prints the grids found.
+fn catalogue(path_to_grids:&str){
+    match profile::catalogue::build_catalogue(path_to_grids){
+        Ok(grids)=>{
+            println!("{} intensity grids found in {}:",grids.len(),path_to_grids);
+            for grid in grids.iter(){
+                println!("  {:?}",grid);
+            }
+        }
+        Err(e)=>{ eprintln!("Unable to build the catalogue of {}: {}",path_to_grids,e);
+            std::process::exit(1)}
+    }
+}
+
+/// Writes the binary caches of the intensity grids of a profile configuration file.
+fn cache(profile_config_path:&str){
+    let profile_config = match ProfileConfig::read_from_toml(profile_config_path){
+        Ok(config)=>config,
+        Err(e) => { eprintln!("{}",e);
+            std::process::exit(1)}
+        };
+    match profile_config.write_grid_caches(){
+        Ok(paths)=>{
+            for path in paths.iter(){
+                println!("  {}",path.display());
+            }
+            println!("{} grid caches written",paths.len());
+        }
+        Err(e)=>{ eprintln!("Unable to write the grid caches: {}",e);
+            std::process::exit(1)}
+    }
+}
+
+/// Writes the compressed intensity grids of a profile configuration file with the given number of principal components
+/// ([profile::utils::pca_grid::DEFAULT_COMPONENTS] if it's not given), and prints how well they reproduce the grids.
+fn compress(profile_config_path:&str, ncomponents:Option<&String>){
+    let ncomponents = match ncomponents.map(|n| n.parse::<usize>()){
+        None => {profile::utils::pca_grid::DEFAULT_COMPONENTS}
+        Some(Ok(n)) => {n}
+        Some(Err(e)) => { eprintln!("The number of components must be a positive integer: {}",e);
+            std::process::exit(1)}
+    };
+    let profile_config = match ProfileConfig::read_from_toml(profile_config_path){
+        Ok(config)=>config,
+        Err(e) => { eprintln!("{}",e);
+            std::process::exit(1)}
+        };
+    match profile_config.write_compressed_grids(ncomponents){
+        Ok((path,error))=>{
+            println!("  {}",path.display());
+            println!("{} intensity grids compressed, the largest relative error of the reconstructed intensities is {:e}",
+                profile_config.intensity_grids.iter().filter(|grid| grid.is_grid()).count(),error);
+        }
+        Err(e)=>{ eprintln!("Unable to compress the intensity grids: {}",e);
+            std::process::exit(1)}
+    }
 }
@@ -1,39 +1,43 @@
-use core::time;
-
 use crate::utils::IntensityFlux;
+use crate::error::ProfileError;
 
 use super::*;
-use temp_name_lib::interpolation::ParameterSpaceHypercube;
+use crate::validation::{star_metallicity_range, star_parameter_box};
+use crate::provider::IntensityProvider;
+use crate::spectrum_cache::{SpectrumCache,SpectrumCacheConfig};
+use crate::emulator::Emulator;
+use rayon::prelude::*;
 
-pub fn parsing_star(path_to_star:&str)->(LazyFrame,Vec<f64>){
+pub fn parsing_star(path_to_star:&str)->PolarsResult<(LazyFrame,Vec<f64>)>{
    //---------------------------------------- 
    //----Parsing rasterized_star.parquet-----
    //----------------------------------------
    // Obtain the lazy frame of the parquet file, Obtain the time points, obtain the theta points
     let rasterized_star_path = path_to_star;
-    let lf = LazyFrame::scan_parquet(rasterized_star_path, Default::default()).unwrap();
+    let lf = LazyFrame::scan_parquet(rasterized_star_path, Default::default())?;
     //get vector of time_points
     
-    let tf = lf.clone().select([col("time").unique(),]).collect().unwrap();
-    let extract_time_series = tf.column("time").unwrap();
-    let time_points:Vec<f64> = extract_time_series.f64().unwrap().into_iter().flatten().collect();
-    (lf.clone(),time_points)
+    let time_points = get_time_points(lf.clone())?;
+    Ok((lf.clone(),time_points))
+}
+
+/// This function returns the time points stored in the `time` column of the rasterized star.
+fn get_time_points(star_lf:LazyFrame)->PolarsResult<Vec<f64>>{
+    let tf = star_lf.select([col("time").unique(),]).collect()?;
+    let extract_time_series = tf.column("time")?;
+    Ok(extract_time_series.f64()?.into_iter().flatten().collect())
 }
 
 pub fn loading_intensity_grids(star_lf:LazyFrame,
-profile_config:& ProfileConfig)->(
-SpectralGrid,//SpectralGrid
-ParameterSpaceHypercube,//hypercube3d
-ParameterSpaceHypercube,//hypercube4d
-){
+profile_config:& ProfileConfig)->Result<SpectralGrid,ProfileError>{
     let max_vel = extremal_val_from_col(
         "velocity",
          star_lf.clone(),
-          true).unwrap();
+          true)?;
     let min_vel = extremal_val_from_col(
         "velocity",
          star_lf.clone(),
-          false).unwrap();
+          false)?;
     
     let maxval_rel_dopplershift =  1.0+max_vel/CLIGHT*1.0e3;
     let minval_rel_dopplershift = 1.0+min_vel/CLIGHT*1.0e3;
@@ -41,52 +45,73 @@ ParameterSpaceHypercube,//hypercube4d
     println!("max relative dopplershift is {}", maxval_rel_dopplershift);
 
     println!("creating the spectral grids data structures from csv files...or neural network regresor");
-    let spectral_grids = profile_config.init_spectral_grid_from_csv(maxval_rel_dopplershift, minval_rel_dopplershift);
-    println!("allocating memory for hypercube in the parameter space");
-    let hypercube4d= spectral_grids.new_hypercube(4usize);
-    let hypercube3d= spectral_grids.new_hypercube(3usize);
+    let spectral_grids = profile_config.init_spectral_grid_from_csv(maxval_rel_dopplershift, minval_rel_dopplershift)?;
 
-    (spectral_grids,hypercube3d,hypercube4d)
+    // Preflight check, the star must lie within the intensity grids for all of the time points
+    // unless the user chose what to do with the cells outside of them.
+    let star_box = star_parameter_box(star_lf.clone())?;
+    println!("the star reaches {}",star_box);
+    let star_metallicity = star_metallicity_range(star_lf.clone())?;
+    for coverage in [spectral_grids.check_coverage(&star_box), spectral_grids.check_metallicity_coverage(star_metallicity)]{
+        match (coverage, profile_config.out_of_bounds){
+            (Err(e @ ProfileError::InvalidConfig(_)), _) | (Err(e), OutOfBoundsPolicy::Error) => {return Err(e)}
+            (Err(e), policy) => {println!("warning: {}. The cells outside of the grids are treated with the {:?} policy",e,policy)}
+            (Ok(()), _) => {}
+        }
+    }
+    Ok(spectral_grids)
 }
 
+/// Returns the least and the largest relative Doppler shifts of the surface cells of the star over all of the time points,
+/// see [insert_col_relative_dlambda].
+pub fn relative_shift_range(star_lf:LazyFrame)->PolarsResult<(f64,f64)>{
+    let shifted_lf = insert_col_relative_dlambda(star_lf);
+    Ok((extremal_val_from_col("relative shift", shifted_lf.clone(), false)?,
+        extremal_val_from_col("relative shift", shifted_lf, true)?))
+}
+
+/// Least number of surface cells integrated by each task of the parallel integration.
+pub const MIN_CELLS_PER_TASK: usize = 256;
+/// Largest number of tasks of the parallel integration, it bounds the memory taken by their partial fluxes.
+pub const MAX_TASKS: usize = 256;
+
 impl FluxOfSpectra {
-    pub fn integrate(& mut self,
+    /// Integrates the specific intensities of an [IntensityProvider] over the visible surface cells of the rasterized star for a single pulsation phase.
+    /// The cells outside of the domain of the provider are treated with the `out_of_bounds` policy.
+    ///
+    /// The cells are split into tasks of consecutive cells that run in parallel on the threads of the global [rayon] pool
+    /// (`RAYON_NUM_THREADS` sets their number). Each thread reuses its own scratch of the provider, and each task adds up its own fluxes.
+    /// The fluxes of the tasks are added in the order of the cells, so the result doesn't depend on the number of threads.
+    /// It returns a [ProfileError] if the cells could not be extracted from the star or the intensities of one of them could not be evaluated.
+    pub fn integrate<P:IntensityProvider>(& mut self,
         star_lf:LazyFrame,
         pulsation_phase:f64,
-        spectral_grid:& mut SpectralGrid,
-        hypercube3d:& mut ParameterSpaceHypercube,
-        hypercube4d:& mut ParameterSpaceHypercube){
-        let expr = col("time").eq(lit(pulsation_phase));
-        let sphere_frame = star_lf.clone().filter(expr);
-        //--------------------------------------------------
-        //----Collect fluxes over the whole star------------
-        //--------------------------------------------------
-    
-        // Filter if surface cell is visible.
-        let expr = col("coschi").gt(lit(0.08));//.and(col("coschi").lt(lit(0.9285)));
-        let visible_lf =sphere_frame.filter(expr);
-            
-        // Append relative doppler wavelength shift 
-        let observed_sphere_df = insert_col_relative_dlambda(visible_lf).collect().unwrap();
-    
-        // Obtain the relevant quantities to compute the flux on each cell of the surface of the rasterized star
-        // |--> relative doppler wavelength shift
-        // |--> normalized area of each cell projected onto the unit vector of directed towards the observer
-        // |--> coschi is projection of the unit vector normal to the cell surface towards the observer.
-        // |--> temperature over the surface cell
-        // |--> log gravity value over the surface cell
-        let surface_cells = SurfaceCell::extract_cells_from_df(observed_sphere_df);
+        provider:&P)->Result<(),ProfileError>{
+        let surface_cells = visible_cells(star_lf, pulsation_phase)?;
     
         // Integrate specific intensity.        
         self.restart(pulsation_phase);
-        for cell in surface_cells.iter(){
-            self.get_doppler_shifted_wavelengths(cell);
-            match cell.coschi>0.9285{
-                true => {self.collect_flux_from_cell(cell,  spectral_grid, hypercube3d)}
-                false => {self.collect_flux_from_cell(cell,  spectral_grid, hypercube4d)}
+        let cells_per_task = surface_cells.len().div_ceil(MAX_TASKS).max(MIN_CELLS_PER_TASK);
+        let wavelengths = &self.wavelengths;
+        let out_of_bounds = self.out_of_bounds;
+        let partial_fluxes = surface_cells
+            .par_chunks(cells_per_task)
+            .map_init(
+                || CellWorkspace::new(provider, wavelengths.len()),
+                |workspace, cells| workspace.integrate(cells, wavelengths, provider, out_of_bounds))
+            .collect::<Result<Vec<PartialFlux>,MathErrors>>()?;
+        for partial in partial_fluxes.iter(){
+            for n in 0..self.wavelengths.len(){
+                self.flux[n] += partial.flux[n];
+                self.continuum[n] += partial.continuum[n];
             }
+            self.out_of_bounds_cells += partial.out_of_bounds_cells;
         }
-    
+        if self.out_of_bounds_cells > 0{
+            println!("{} cells outside of the domain of the atmosphere model at time {}, treated with the {:?} policy",
+                self.out_of_bounds_cells, pulsation_phase, self.out_of_bounds);
+        }
+        Ok(())
     }
 
     /// So far I've only coded the version to write into a parquet file. 
@@ -95,55 +120,171 @@ impl FluxOfSpectra {
     }
 }
 
-pub fn profile_main(toml_string:&str,star_df:DataFrame)->DataFrame{
+/// The working memory of a thread of the integration, reused by all of the tasks it runs.
+struct CellWorkspace<S>{
+    /// Scratch of the provider, see [IntensityProvider::new_scratch].
+    scratch: S,
+    /// Wavelengths at which the atmosphere of the current cell emits the observed ones.
+    shifted_wavelengths: Vec<f64>,
+    /// Specific intensities of the current cell.
+    specific: Vec<f64>,
+    /// Continuum intensities of the current cell.
+    continuum: Vec<f64>,
+}
+
+/// The fluxes of the cells of a task of the integration.
+struct PartialFlux{
+    /// Sum of the specific intensities times the area of the cells, see [FluxOfSpectra::flux].
+    flux: Vec<f64>,
+    /// Sum of the continuum intensities times the area of the cells, see [FluxOfSpectra::continuum].
+    continuum: Vec<f64>,
+    /// Number of cells outside of the domain of the provider.
+    out_of_bounds_cells: u32,
+}
+
+impl<S> CellWorkspace<S>{
+    fn new<P:IntensityProvider<Scratch=S>>(provider:&P, nwavelengths:usize)->Self{
+        Self{scratch: provider.new_scratch(), shifted_wavelengths: vec![0.0;nwavelengths], specific: vec![0.0;nwavelengths], continuum: vec![0.0;nwavelengths]}
+    }
+
+    /// This function adds up the specific and continuum intensities of some surface cells times their area, see [FluxOfSpectra::integrate].
+    /// It returns a [MathErrors] if the intensities of one of them could not be evaluated.
+    fn integrate<P:IntensityProvider<Scratch=S>>(&mut self, cells:&[SurfaceCell], wavelengths:&[f64], provider:&P,
+        out_of_bounds:OutOfBoundsPolicy)->Result<PartialFlux,MathErrors>{
+        let mut partial = PartialFlux{flux: vec![0.0;wavelengths.len()], continuum: vec![0.0;wavelengths.len()], out_of_bounds_cells: 0};
+        let bounds_handling = out_of_bounds.bounds_handling();
+        for cell in cells.iter(){
+            let atmosphere = cell.atmosphere();
+            if !provider.covers(&atmosphere){
+                partial.out_of_bounds_cells += 1;
+                if out_of_bounds == OutOfBoundsPolicy::Skip {continue}
+            }
+            cell.doppler_shifted_wavelengths(wavelengths, &mut self.shifted_wavelengths);
+            provider.intensities(&atmosphere, &self.shifted_wavelengths, bounds_handling, &mut self.scratch, &mut self.specific, &mut self.continuum)?;
+            for n in 0..wavelengths.len(){
+                partial.flux[n] += self.specific[n] * cell.area;
+                partial.continuum[n] += self.continuum[n] * cell.area;
+            }
+        }
+        Ok(partial)
+    }
+}
+
+/// This function returns the surface cells of the rasterized star that are visible at a pulsation phase.
+/// It returns a [PolarsError] if the cells could not be extracted from the star.
+fn visible_cells(star_lf:LazyFrame, pulsation_phase:f64)->PolarsResult<Vec<SurfaceCell>>{
+    let expr = col("time").eq(lit(pulsation_phase));
+    let sphere_frame = star_lf.filter(expr);
+    //--------------------------------------------------
+    //----Collect fluxes over the whole star------------
+    //--------------------------------------------------
+
+    // Filter if surface cell is visible.
+    let expr = col("coschi").gt(lit(MIN_VISIBLE_COSCHI));
+    let visible_lf =sphere_frame.filter(expr);
+        
+    // Append relative doppler wavelength shift 
+    let observed_sphere_df = insert_col_relative_dlambda(visible_lf).collect()?;
+
+    // Obtain the relevant quantities to compute the flux on each cell of the surface of the rasterized star
+    // |--> relative doppler wavelength shift
+    // |--> normalized area of each cell projected onto the unit vector of directed towards the observer
+    // |--> coschi is projection of the unit vector normal to the cell surface towards the observer.
+    // |--> temperature over the surface cell
+    // |--> log gravity value over the surface cell
+    SurfaceCell::extract_cells_from_df(observed_sphere_df)
+}
+
+/// This function is used to get the output of the profile code.
+/// ### Arguments:
+/// * `toml_string` - the contents of a `profile_input.toml` file.
+/// * `star_df` - the rasterized star produced by pulstar.
+/// ### Returns:
+/// This function returns a [Result] with the following variants:
+/// * `Ok(DataFrame)` - the fluxes for all of the time points.
+/// * `Err(ProfileError)` - if the configuration could not be parsed, the grids could not be loaded, or the integration failed.
+pub fn profile_main(toml_string:&str,star_df:DataFrame)->Result<DataFrame,ProfileError>{
+    profile_output(toml_string, star_df).map(|output| output.data_frame)
+}
+
+/// This function runs the profile code like [profile_main], but it also returns the number of visible cells outside of the intensity grids
+/// for each time point.
+/// ### Returns:
+/// This function returns a [Result] with the following variants:
+/// * `Ok(IntensityFlux)` - the fluxes and the out of bounds cells for all of the time points.
+/// * `Err(ProfileError)` - if the configuration could not be parsed, the grids could not be loaded, or the integration failed.
+pub fn profile_output(toml_string:&str,star_df:DataFrame)->Result<IntensityFlux,ProfileError>{
    //---------------------------------------- 
    //------Parsing profile_input.toml--------
    //----------------------------------------
    // |--> Check that the toml file exists
    // |--> Check if the Profile_input.toml is well written.
+   // |--> Take the intensity grids from the catalogue of the grid directory if they're not listed.
    // |--> Check if the Intensity Grid files exist.
    // |--> Initialize the profile parameters.
     //let profile_config = ProfileConfig::read_from_toml(toml_string);
-    let profile_config:ProfileConfig=match toml::from_str(toml_string){
-        Ok(config)=>{config}
-        _=>{panic!("error parsing toml for profile config")}
-    };
-    let mut fluxes = FluxOfSpectra::new(&profile_config);
-    let mut intensity_collection = IntensityFlux::new();
+    let mut profile_config:ProfileConfig=toml::from_str(toml_string)?;
+    profile_config.fill_intensity_grids_from_catalogue()?;
+    let report = profile_config.validate();
+    if !report.is_valid(){
+        return Err(ProfileError::Validation(report))
+    }
+    let fluxes = FluxOfSpectra::new(&profile_config)?;
    //---------------------------------------- 
    //----Parsing rasterized_star.parquet-----
    //----------------------------------------
     let lf = star_df.lazy();
-    let tf = lf.clone().select([col("time").unique(),]).collect().unwrap();
-    let extract_time_series = tf.column("time").unwrap();
-    let time_points:Vec<f64> = extract_time_series.f64().unwrap().into_iter().flatten().collect();
-   // Obtain the lazy frame of the parquet file, Obtain the time points, obtain the theta points
-   let (
-        mut spectral_grid,
-        mut hypercube3d,
-        mut hypercube4d,
-    )= loading_intensity_grids(lf.clone(), & profile_config);
+    let time_points = get_time_points(lf.clone())?;
+
+    // An analytic atmosphere or an emulator doesn't need the intensity grids.
+    match (profile_config.analytic_atmosphere(), profile_config.emulator_model()){
+        (Some(atmosphere), _) => {
+            let model = atmosphere.load(&profile_config.path_to_grids)?;
+            collect_fluxes(lf, &time_points, &model, fluxes, profile_config.spectrum_cache)
+        }
+        (None, Some(model)) => {
+            let emulator = Emulator::from_file(&format!("{}{}", profile_config.path_to_grids, model))?;
+            collect_fluxes(lf, &time_points, &emulator, fluxes, profile_config.spectrum_cache)
+        }
+        (None, None) => {
+            let spectral_grid = loading_intensity_grids(lf.clone(), & profile_config)?;
+            collect_fluxes(lf, &time_points, &spectral_grid, fluxes, profile_config.spectrum_cache)
+        }
+    }
+}
+
+/// This function integrates the fluxes of every time point with an [IntensityProvider], through a [SpectrumCache] if the configuration sets one,
+/// and collects them into an [IntensityFlux].
+fn collect_fluxes<P:IntensityProvider>(lf:LazyFrame, time_points:&[f64], provider:&P, fluxes:FluxOfSpectra,
+    spectrum_cache:Option<SpectrumCacheConfig>)->Result<IntensityFlux,ProfileError>{
+    match spectrum_cache{
+        Some(config) => {
+            let cache = SpectrumCache::new(provider, config, &fluxes.wavelengths, relative_shift_range(lf.clone())?)?;
+            let output = integrate_time_points(lf, time_points, &cache, fluxes)?;
+            println!("{} spectra cached", cache.len());
+            Ok(output)
+        }
+        None => {integrate_time_points(lf, time_points, provider, fluxes)}
+    }
+}
+
+/// This function integrates the fluxes of every time point with an [IntensityProvider] and collects them into an [IntensityFlux].
+fn integrate_time_points<P:IntensityProvider>(lf:LazyFrame, time_points:&[f64], provider:&P, mut fluxes:FluxOfSpectra)->Result<IntensityFlux,ProfileError>{
+    let mut intensity_collection = IntensityFlux::new()?;
     //----------------------------------------------------------------
     //-------------- Collect fluxes for each time point  -------------
     //----------------------------------------------------------------
 
     //time loop    
-    let mut last_timepoint= 0u16;
-    for (time_point_number,pulsation_phase) in time_points.iter().enumerate() {
-        fluxes.integrate(
-            lf.clone(),
-            *pulsation_phase,
-            & mut spectral_grid,
-            & mut hypercube3d,
-            & mut hypercube4d);
+    for pulsation_phase in time_points.iter() {
+        fluxes.integrate(lf.clone(), *pulsation_phase, provider)?;
         println!("done computing flux");
 
         println!("finished collecting fluxes {}",pulsation_phase);
         //fluxes.write_output(time_point_number as u16).expect(&format!("Unable to write parquet file for {} time point",*pulsation_phase));
-        intensity_collection = intensity_collection.append_fluxes(fluxes.clone());
-        last_timepoint=time_point_number as u16;
+        intensity_collection = intensity_collection.append_fluxes(fluxes.clone())?;
     }
-    intensity_collection.data_frame
+    Ok(intensity_collection)
     //if let Ok(_)= intensity_collection.write_output(last_timepoint){
     //println!("finished computation for a star's pulsation")}
     //else{panic!("unable to write parquetfile")};
@@ -0,0 +1,66 @@
+use crate::{InterpolationScheme, SurfaceCell};
+use temp_name_lib::interpolation::BoundsHandling;
+use temp_name_lib::utils::MathErrors;
+
+/// The local parameters of the atmosphere of a surface cell, at which the intensities are evaluated.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct LocalAtmosphere{
+    /// Effective temperature in K.
+    pub temperature: f64,
+    /// Logarithm of the surface gravity.
+    pub log_gravity: f64,
+    /// Metallicity [M/H], if the star has one.
+    pub metallicity: Option<f64>,
+    /// Cosine of the angle between the normal of the surface and the line of sight.
+    pub mu: f64,
+}
+
+/// A source of specific and continuum intensities, e.g. the intensity grids ([crate::SpectralGrid]) or an analytic atmosphere ([crate::analytic::AnalyticModel]).
+///
+/// The integration over the surface of the star, [crate::FluxOfSpectra::integrate], is written against this trait,
+/// so a new kind of atmosphere only needs to implement it. The surface cells are integrated in parallel, so the provider is shared
+/// between the threads and each of them evaluates the intensities with its own scratch.
+pub trait IntensityProvider: Sync{
+    /// Working memory reused between the evaluations, e.g. the weights of the grid interpolation.
+    type Scratch: Send;
+
+    /// Creates the working memory used by [IntensityProvider::intensities].
+    fn new_scratch(&self)->Self::Scratch;
+
+    /// Returns `true` if the atmosphere lies within the domain of the provider.
+    /// The surface cells outside of it are treated with the `out_of_bounds` policy of the [crate::ProfileConfig].
+    fn covers(&self, atmosphere:&LocalAtmosphere)->bool;
+
+    /// This function evaluates the specific and continuum intensities of an atmosphere.
+    /// ### Arguments:
+    /// * `atmosphere` - the parameters of the atmosphere, see [LocalAtmosphere].
+    /// * `wavelengths` - the wavelengths in nm, in increasing order.
+    /// * `bounds_handling` - what to do if the atmosphere lies outside of the domain of the provider.
+    /// * `scratch` - the working memory created by [IntensityProvider::new_scratch].
+    /// * `specific` and `continuum` - where the intensities of every wavelength are written.
+    /// ### Returns:
+    /// * `Err(MathErrors)` - if the atmosphere or a wavelength lies outside of the domain of the provider and `bounds_handling` doesn't allow it.
+    fn intensities(&self, atmosphere:&LocalAtmosphere, wavelengths:&[f64], bounds_handling:BoundsHandling,
+        scratch:&mut Self::Scratch, specific:&mut [f64], continuum:&mut [f64])->Result<(),MathErrors>;
+
+    /// Returns the wavelengths in nm at which the provider tabulates its spectra, in increasing order, and the scheme it interpolates them with.
+    /// The [crate::spectrum_cache::SpectrumCache] samples the rest-frame spectra at them. By default the provider isn't tabulated.
+    fn tabulated_wavelengths(&self)->Option<(&[f64],InterpolationScheme)>{
+        None
+    }
+}
+
+impl SurfaceCell{
+    /// Returns the parameters of the atmosphere of the cell.
+    pub fn atmosphere(&self)->LocalAtmosphere{
+        LocalAtmosphere{temperature: self.t_eff, log_gravity: self.log_g, metallicity: self.metallicity, mu: self.coschi}
+    }
+
+    /// This function fills `shifted` with the observed `wavelengths` multiplied by the relative Doppler shift of the cell,
+    /// i.e. the wavelengths at which the atmosphere of the cell emits the observed ones.
+    pub fn doppler_shifted_wavelengths(&self, wavelengths:&[f64], shifted:&mut [f64]){
+        for (shifted_wavelength,wavelength) in shifted.iter_mut().zip(wavelengths.iter()){
+            *shifted_wavelength = wavelength * self.rel_dlamb;
+        }
+    }
+}
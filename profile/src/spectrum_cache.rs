@@ -0,0 +1,265 @@
+use crate::InterpolationScheme;
+use crate::error::ProfileError;
+use crate::provider::{IntensityProvider, LocalAtmosphere};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock};
+use temp_name_lib::interpolation::BoundsHandling;
+use temp_name_lib::utils::MathErrors;
+
+/// Default tolerance on the effective temperature, in K.
+pub const DEFAULT_TEMPERATURE_TOLERANCE: f64 = 1.0;
+/// Default tolerance on log g.
+pub const DEFAULT_LOG_GRAVITY_TOLERANCE: f64 = 1.0e-3;
+/// Default tolerance on the metallicity [M/H].
+pub const DEFAULT_METALLICITY_TOLERANCE: f64 = 1.0e-3;
+/// Default tolerance on µ.
+pub const DEFAULT_MU_TOLERANCE: f64 = 1.0e-3;
+/// Default largest number of cached spectra.
+pub const DEFAULT_CAPACITY: usize = 10_000;
+
+/// The tolerances of the [SpectrumCache]. On the toml file it's given as e.g. `spectrum_cache = {temperature = 5.0, mu = 0.002}`,
+/// the values left out take their defaults.
+///
+/// Every parameter of an atmosphere is rounded to the closest multiple of its tolerance, and the surface cells whose atmospheres round
+/// to the same values share the spectrum evaluated at the rounded ones. The parameters of a cell are then evaluated at most half of
+/// their tolerance away.
+#[derive(Deserialize,Debug,PartialEq,Clone,Copy)]
+#[serde(default)]
+pub struct SpectrumCacheConfig{
+    /// Tolerance on the effective temperature in K, by default [DEFAULT_TEMPERATURE_TOLERANCE].
+    pub temperature: f64,
+    /// Tolerance on log g, by default [DEFAULT_LOG_GRAVITY_TOLERANCE].
+    pub log_gravity: f64,
+    /// Tolerance on the metallicity [M/H], by default [DEFAULT_METALLICITY_TOLERANCE].
+    pub metallicity: f64,
+    /// Tolerance on µ, by default [DEFAULT_MU_TOLERANCE].
+    pub mu: f64,
+    /// Step in nm of the rest-frame wavelengths of the providers that don't tabulate their spectra (see [IntensityProvider::tabulated_wavelengths]).
+    /// It's optional, by default the step of the observed wavelengths.
+    pub wavelength_step: Option<f64>,
+    /// Largest number of cached spectra, by default [DEFAULT_CAPACITY]. Each of them takes 16 bytes per rest-frame wavelength.
+    /// Once it's reached, the spectra of the new atmospheres are evaluated for every cell.
+    pub capacity: usize,
+}
+
+impl Default for SpectrumCacheConfig{
+    fn default()->Self{
+        SpectrumCacheConfig{
+            temperature: DEFAULT_TEMPERATURE_TOLERANCE,
+            log_gravity: DEFAULT_LOG_GRAVITY_TOLERANCE,
+            metallicity: DEFAULT_METALLICITY_TOLERANCE,
+            mu: DEFAULT_MU_TOLERANCE,
+            wavelength_step: None,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+/// The parameters of an atmosphere in units of the tolerances of the [SpectrumCacheConfig].
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+struct AtmosphereKey{
+    temperature: i64,
+    log_gravity: i64,
+    metallicity: Option<i64>,
+    mu: i64,
+}
+
+impl SpectrumCacheConfig{
+    /// This function checks the tolerances and returns the description of every problem found.
+    pub fn check(&self)->Vec<String>{
+        let mut problems:Vec<String> = Vec::new();
+        let tolerances = [("temperature", self.temperature), ("log_gravity", self.log_gravity),
+            ("metallicity", self.metallicity), ("mu", self.mu)];
+        for (name, tolerance) in tolerances{
+            if !(tolerance > 0.0 && tolerance.is_finite()){
+                problems.push(format!("the tolerance on {} must be positive, it is {}", name, tolerance));
+            }
+        }
+        if let Some(step) = self.wavelength_step
+            && !(step > 0.0 && step.is_finite()){
+            problems.push(format!("the wavelength step must be positive, it is {} nm", step));
+        }
+        problems
+    }
+
+    /// Returns the atmosphere with every parameter rounded to the closest multiple of its tolerance,
+    /// which is the one evaluated for all of the atmospheres that round to it.
+    pub fn quantised(&self, atmosphere:&LocalAtmosphere)->LocalAtmosphere{
+        self.key(atmosphere).atmosphere(self)
+    }
+
+    fn key(&self, atmosphere:&LocalAtmosphere)->AtmosphereKey{
+        AtmosphereKey{
+            temperature: (atmosphere.temperature/self.temperature).round() as i64,
+            log_gravity: (atmosphere.log_gravity/self.log_gravity).round() as i64,
+            metallicity: atmosphere.metallicity.map(|metallicity| (metallicity/self.metallicity).round() as i64),
+            mu: (atmosphere.mu/self.mu).round() as i64,
+        }
+    }
+}
+
+impl AtmosphereKey{
+    fn atmosphere(&self, config:&SpectrumCacheConfig)->LocalAtmosphere{
+        LocalAtmosphere{
+            temperature: self.temperature as f64 * config.temperature,
+            log_gravity: self.log_gravity as f64 * config.log_gravity,
+            metallicity: self.metallicity.map(|metallicity| metallicity as f64 * config.metallicity),
+            mu: self.mu as f64 * config.mu,
+        }
+    }
+}
+
+/// The specific and continuum intensities of an atmosphere at the rest-frame wavelengths of a [SpectrumCache].
+struct RestSpectrum{
+    specific: Vec<f64>,
+    continuum: Vec<f64>,
+}
+
+/// An [IntensityProvider] that evaluates another one once for all of the surface cells whose atmospheres agree within the tolerances
+/// of a [SpectrumCacheConfig] (see [SpectrumCacheConfig::quantised]).
+///
+/// The spectrum of an atmosphere is evaluated at rest-frame wavelengths that cover the observed ones for all of the Doppler shifts of the star,
+/// and it's kept for the following cells and time points. For every cell it's only interpolated at the Doppler-shifted wavelengths.
+/// The rest-frame wavelengths are the ones the provider is tabulated at, if it is, and it's interpolated with its own scheme.
+/// Otherwise they're sampled uniformly with the step of the configuration and interpolated linearly.
+///
+/// The cache is shared between the threads of the integration. A spectrum only depends on the rounded atmosphere,
+/// so the fluxes don't depend on which cell evaluates it first nor on the number of threads.
+pub struct SpectrumCache<'a,P:IntensityProvider>{
+    provider: &'a P,
+    config: SpectrumCacheConfig,
+    /// Rest-frame wavelengths in nm, in increasing order.
+    wavelengths: Vec<f64>,
+    /// How the spectra are interpolated at the Doppler-shifted wavelengths.
+    scheme: InterpolationScheme,
+    spectra: RwLock<HashMap<AtmosphereKey,RestSpectrum>>,
+}
+
+/// The working memory of a [SpectrumCache], see [IntensityProvider::new_scratch].
+pub struct CacheScratch<S>{
+    /// Scratch of the cached provider.
+    provider: S,
+    /// Rest-frame spectrum of the current atmosphere when it's not cached.
+    specific: Vec<f64>,
+    continuum: Vec<f64>,
+}
+
+impl<'a,P:IntensityProvider> SpectrumCache<'a,P>{
+    /// This function creates an empty cache for a provider.
+    /// ### Arguments:
+    /// * `provider` - the [IntensityProvider] whose spectra are cached.
+    /// * `config` - the tolerances, see [SpectrumCacheConfig].
+    /// * `wavelengths` - the observed wavelengths in nm, in increasing order.
+    /// * `shift_range` - the least and the largest relative Doppler shifts of the surface cells, see [crate::profile_mkr::relative_shift_range].
+    /// ### Returns:
+    /// * `Err(ProfileError::InvalidConfig)` - if there are less than two rest-frame wavelengths, or the step of the rest-frame wavelengths
+    ///   is not given and there's a single observed wavelength.
+    pub fn new(provider:&'a P, config:SpectrumCacheConfig, wavelengths:&[f64], shift_range:(f64,f64))->Result<Self,ProfileError>{
+        let (Some(first), Some(last)) = (wavelengths.first(), wavelengths.last()) else{
+            return Err(ProfileError::InvalidConfig(String::from("the spectrum cache needs at least one observed wavelength")))
+        };
+        let (min_wavelength, max_wavelength) = (first*shift_range.0, last*shift_range.1);
+        let (rest_wavelengths, scheme) = match provider.tabulated_wavelengths(){
+            Some((tabulated, scheme)) => {
+                // One more node at each side, so that the cubic schemes have their whole stencil at the ends.
+                let start = tabulated.partition_point(|wavelength| *wavelength <= min_wavelength).saturating_sub(2);
+                let end = (tabulated.partition_point(|wavelength| *wavelength < max_wavelength) + 2).min(tabulated.len());
+                (tabulated[start..end.max(start)].to_vec(), scheme)
+            }
+            None => {
+                let step = match (config.wavelength_step, wavelengths.len()){
+                    (Some(step), _) => step,
+                    (None, 2..) => (last - first)/(wavelengths.len() - 1) as f64,
+                    (None, _) => {return Err(ProfileError::InvalidConfig(String::from(
+                        "the spectrum cache needs a wavelength_step with a single observed wavelength")))}
+                };
+                let start = min_wavelength - step;
+                let nwavelengths = ((max_wavelength + step - start)/step).ceil() as usize + 1;
+                ((0..nwavelengths).map(|n| start + n as f64*step).collect(), InterpolationScheme::Linear)
+            }
+        };
+        if rest_wavelengths.len() < 2{
+            return Err(ProfileError::InvalidConfig(format!("the spectrum cache needs at least two rest-frame wavelengths between {} and {} nm, {} found",
+                min_wavelength, max_wavelength, rest_wavelengths.len())))
+        }
+        Ok(SpectrumCache{provider, config, wavelengths: rest_wavelengths, scheme, spectra: RwLock::new(HashMap::new())})
+    }
+
+    /// Returns the number of cached spectra.
+    pub fn len(&self)->usize{
+        self.spectra.read().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    /// Returns `true` if no spectrum has been cached yet.
+    pub fn is_empty(&self)->bool{
+        self.len() == 0
+    }
+
+    /// This function interpolates a rest-frame spectrum at the Doppler-shifted wavelengths of a surface cell.
+    /// ### Returns:
+    /// * `Err(MathErrors::OutOfBounds)` - if a wavelength lies outside of the rest-frame wavelengths.
+    fn resample(&self, rest_specific:&[f64], rest_continuum:&[f64], wavelengths:&[f64], specific:&mut [f64], continuum:&mut [f64])->Result<(),MathErrors>{
+        let rest = &self.wavelengths;
+        let (first, last) = (rest[0], rest[rest.len()-1]);
+        let mut index = 0;
+        for (n,wavelength) in wavelengths.iter().enumerate(){
+            if !(first <= *wavelength && *wavelength <= last){
+                return Err(MathErrors::OutOfBounds)
+            }
+            // The wavelengths are in increasing order, so the node right below them only moves forward.
+            if rest[index] > *wavelength{
+                index = rest.partition_point(|node| node < wavelength).saturating_sub(1);
+            }
+            while index + 2 < rest.len() && rest[index+1] < *wavelength{
+                index += 1;
+            }
+            let stencil = self.scheme.stencil(index, rest.len());
+            let nodes = &rest[stencil.clone()];
+            specific[n] = self.scheme.interpolate(nodes, &rest_specific[stencil.clone()], index - stencil.start, *wavelength);
+            continuum[n] = self.scheme.interpolate(nodes, &rest_continuum[stencil.clone()], index - stencil.start, *wavelength);
+        }
+        Ok(())
+    }
+}
+
+impl<P:IntensityProvider> IntensityProvider for SpectrumCache<'_,P>{
+    type Scratch = CacheScratch<P::Scratch>;
+
+    fn new_scratch(&self)->CacheScratch<P::Scratch>{
+        CacheScratch{provider: self.provider.new_scratch(), specific: Vec::new(), continuum: Vec::new()}
+    }
+
+    /// Returns `true` if the provider covers the rounded atmosphere, see [SpectrumCacheConfig::quantised].
+    fn covers(&self, atmosphere:&LocalAtmosphere)->bool{
+        self.provider.covers(&self.config.quantised(atmosphere))
+    }
+
+    fn tabulated_wavelengths(&self)->Option<(&[f64],InterpolationScheme)>{
+        Some((&self.wavelengths, self.scheme))
+    }
+
+    /// This function interpolates the cached spectrum of the rounded atmosphere at the wavelengths. If it's not cached yet, it's evaluated with the provider
+    /// and cached while there's room for it. The `bounds_handling` is the one of the whole run, it's not part of the key of the spectra.
+    /// ### Returns:
+    /// * `Err(MathErrors)` - if the provider can't evaluate the rounded atmosphere, or a wavelength lies outside of the rest-frame wavelengths.
+    fn intensities(&self, atmosphere:&LocalAtmosphere, wavelengths:&[f64], bounds_handling:BoundsHandling,
+        scratch:&mut CacheScratch<P::Scratch>, specific:&mut [f64], continuum:&mut [f64])->Result<(),MathErrors>{
+        let key = self.config.key(atmosphere);
+        if let Some(spectrum) = self.spectra.read().unwrap_or_else(PoisonError::into_inner).get(&key){
+            return self.resample(&spectrum.specific, &spectrum.continuum, wavelengths, specific, continuum)
+        }
+
+        scratch.specific.resize(self.wavelengths.len(), 0.0);
+        scratch.continuum.resize(self.wavelengths.len(), 0.0);
+        self.provider.intensities(&key.atmosphere(&self.config), &self.wavelengths, bounds_handling,
+            &mut scratch.provider, &mut scratch.specific, &mut scratch.continuum)?;
+        self.resample(&scratch.specific, &scratch.continuum, wavelengths, specific, continuum)?;
+
+        let mut spectra = self.spectra.write().unwrap_or_else(PoisonError::into_inner);
+        if spectra.len() < self.config.capacity{
+            spectra.entry(key).or_insert_with(|| RestSpectrum{specific: scratch.specific.clone(), continuum: scratch.continuum.clone()});
+        }
+        Ok(())
+    }
+}
@@ -2,45 +2,85 @@ use std::path::PathBuf;
 use polars:: prelude::*;
 use crate::FluxOfSpectra;
 pub mod csv_to_ndarray;
+/// This module contains the binary cache of the intensity grids.
+///
+/// Parsing the csv files of the intensity grids dominates the short profile runs, so every grid can be converted once
+/// into a binary file stored next to it, with the name of the grid file plus the [grid_cache::CACHE_EXTENSION] (e.g. `t20000g35.txt.grid`).
+/// The binary file is written in little endian and holds:
+/// * a header with the [grid_cache::CACHE_MAGIC] bytes, the number of rows and the number of µ values as `u64`,
+/// * the µ values in increasing order as `f64`,
+/// * the wavelengths in increasing order as `f64`,
+/// * the intensities as `f64` in row major order, a row per wavelength with the columns of [grid_cache::ParsedGrid::values].
+///
+/// Joris' grids are stored with their limb darkening law already evaluated on the µ values, so both kinds of grids share the same layout.
+/// A cache written for other µ values (see [crate::ProfileConfig::mu_values]) is written again.
+pub mod grid_cache;
+/// This module contains the compression of the intensity grids into principal components.
+///
+/// The spectra of the grids of a lattice are very similar, so each column of the grids (a µ value of the specific or continuum intensities)
+/// is stored as the mean spectrum over the grids plus a few principal components over wavelength, and every grid as its coefficients on them.
+/// `profile compress` writes them into a single file of the grid directory ([pca_grid::COMPRESSED_GRIDS_FILE]), in little endian:
+/// * a header with the [pca_grid::PCA_MAGIC] bytes, the number of rows (wavelengths), µ values, grids and components as `u64`,
+/// * the µ values and the wavelengths in increasing order as `f64`,
+/// * the file name of every grid, as its length in bytes (`u64`) followed by its UTF-8 bytes,
+/// * the mean intensities, the components and the coefficients as `f64`, in the row major order of [pca_grid::CompressedGrids].
+///
+/// With [crate::GridBackend::Compressed] the runs read this file instead of the grid files. The coefficients are interpolated between the nodes
+/// of the lattice, which is the same as interpolating the reconstructed intensities since the reconstruction is linear in them.
+pub mod pca_grid;
 
 
 
+/// Name of the parquet files with the fluxes, see [output_path].
+pub const FLUX_OUTPUT: &str = "wavelengths";
+/// Name of the parquet files with the number of visible surface cells outside of the intensity grids of every time point, see [output_path].
+pub const OUT_OF_BOUNDS_OUTPUT: &str = "out_of_bounds";
+
+/// Returns the path of the parquet file `name` written after `time_points` time points, e.g. `wavelengths_tp3.parquet`.
+pub fn output_path(name:&str, time_points:u16)->PathBuf{
+    PathBuf::from(format!("{}_tp{}.parquet",name,time_points))
+}
+
 pub struct IntensityFlux{
     pub data_frame:DataFrame,
+    /// The number of visible surface cells that were outside of the intensity grids, a row per time point with the columns
+    /// `|time|out of bounds cells|`.
+    pub out_of_bounds_cells:DataFrame,
 }
 
 impl IntensityFlux{
-    pub fn new()->Self{
-        Self{data_frame: df!(
+    pub fn new()->PolarsResult<Self>{
+        Ok(Self{data_frame: df!(
             "time"=>Vec::<f64>::new(),
             "wave length" => Vec::<f64>::new(),
             "flux" => Vec::<f64>::new(),
             "continuum" => Vec::<f64>::new(),
             "normalized flux" => Vec::<f64>::new()
-        ).unwrap(),}
+        )?,
+        out_of_bounds_cells: df!(
+            "time" => Vec::<f64>::new(),
+            "out of bounds cells" => Vec::<u32>::new()
+        )?,})
     }
 
-    pub fn append_fluxes(self,fluxes:FluxOfSpectra)->Self{
-        let flux_df=df!(
-            "time" => fluxes.time,
-            "wave length" => fluxes.wavelengths,
-            "flux" => fluxes.flux,
-            "continuum" => fluxes.continuum
-        ).unwrap();
+    pub fn append_fluxes(self,fluxes:FluxOfSpectra)->PolarsResult<Self>{
+        let out_of_bounds_df = create_out_of_bounds_dataframe(&fluxes)?;
+        let flux_df=create_spectra_dataframe(fluxes)?;
 
         // construct the mean flux expresion for the lazy data frame flux/cont
         let expr = (col("flux") / col("continuum")).alias("normalized flux");
         let flux_lf=flux_df.lazy().with_column(expr);
-        let result_lf=append_current_lf_into_collection_lf(flux_lf, self.data_frame.lazy()).unwrap();
+        let result_lf=append_current_lf_into_collection_lf(flux_lf, self.data_frame.lazy())?;
+        let out_of_bounds_lf=append_current_lf_into_collection_lf(out_of_bounds_df.lazy(), self.out_of_bounds_cells.lazy())?;
 
-        IntensityFlux { data_frame:result_lf.collect().unwrap()}
+        Ok(IntensityFlux { data_frame:result_lf.collect()?, out_of_bounds_cells:out_of_bounds_lf.collect()?})
     }
 
+    /// This function writes the fluxes into `wavelengths_tp{time_points}.parquet` and the out of bounds cells of every time point
+    /// into `out_of_bounds_tp{time_points}.parquet`, with the columns `|time|out of bounds cells|`.
     pub fn write_output(self, time_points:u16)->PolarsResult<()>{
         // write lazy frame into parquet
-        let new_path = PathBuf::from(
-            format!("wavelengths_tp{}.parquet",time_points)
-        );
+        let new_path = output_path(FLUX_OUTPUT, time_points);
         let lf_to_write = self.data_frame.lazy();
 
         if let Ok(lf) = lf_to_write.sink_parquet(
@@ -50,6 +90,15 @@ impl IntensityFlux{
             SinkOptions::default()){
                 lf.collect()?;
             }else {eprint!("unable to sink to a parket in {} time_point",time_points)};
+
+        let out_of_bounds_path = output_path(OUT_OF_BOUNDS_OUTPUT, time_points);
+        if let Ok(lf) = self.out_of_bounds_cells.lazy().sink_parquet(
+            SinkTarget::Path(Arc::new(out_of_bounds_path.clone())),
+            ParquetWriteOptions::default(),
+            None,
+            SinkOptions::default()){
+                lf.collect()?;
+            }else {eprint!("unable to sink the out of bounds cells to a parket in {} time_point",time_points)};
     
         // print 5 rows of the parquet output
         let llf = LazyFrame::scan_parquet(new_path,
@@ -99,15 +148,16 @@ fn append_current_lf_into_collection_lf(spectra_lazyframe:LazyFrame,parquet_file
 /// This function removes the parquet file that holds the old collection of rasterized stars
 /// 
 /// ### Arguments: 
-/// * `path_to_parquet` - A [std::path::PathBuf] path to the old parquet file. 
+/// * `name` - the name of the parquet file, see [output_path].
+/// * `time_points` - the number of time points of the new parquet file, the old one has one less. 
 /// ### Returns: 
 /// This function returns a [Result] with the following variants:
 /// * `Ok(_)` - if everything went ok.
 /// * `Err(std::io::Error)` - where the error is passed to the calling function to indicate that it could not remove the file. 
-fn remove_temp_parquet_file(time_points:u16)->Result<(), std::io::Error>{
+fn remove_temp_parquet_file(name:&str,time_points:u16)->Result<(), std::io::Error>{
     //let old_path = std::path::PathBuf::from(format!("rasterized_star_{}tp.parquet",time_points-1));
-    let old_file =format!("wavelengths_tp{}.parquet",time_points-1); 
-    println!("deletting {}",old_file);
+    let old_file =output_path(name, time_points-1); 
+    println!("deletting {}",old_file.display());
     std::fs::remove_file(old_file)?;
     Ok(())
 }
@@ -131,19 +181,29 @@ fn create_spectra_dataframe(fluxes: FluxOfSpectra)->PolarsResult<DataFrame>{
     )
 }
 
+/// This function creates a [DataFrame] with a single row, the time point of a [FluxOfSpectra] and its number of visible surface cells outside of
+/// the intensity grids, with the columns `|time|out of bounds cells|`.
+fn create_out_of_bounds_dataframe(fluxes: &FluxOfSpectra)->PolarsResult<DataFrame>{
+    df!(
+        "time" => [fluxes.time[0]],
+        "out of bounds cells" => [fluxes.out_of_bounds_cells]
+    )
+}
+
 
 ///This function creates the [LazyFrame] that will be used to create the parquet file 
 /// 
 /// ### Arguments:
+/// * `name` - the name of the parquet file, see [output_path].
 /// * `time_points` - a [u16] integer that indicates the time_point to be added. 
 /// * `flux_lf` - the [LazyFrame] of the [FluxOfSpectra] [DataFrame]
 /// ### Returns:
 /// * [LazyFrame] - This lazyframe will be sinked ([polars::prelude::LazyFrame::sink_parquet]) into a parquet file
-fn lazyframe_to_be_written (time_points:u16,flux_lf:LazyFrame)->PolarsResult<LazyFrame>{
+fn lazyframe_to_be_written (name:&str,time_points:u16,flux_lf:LazyFrame)->PolarsResult<LazyFrame>{
     if time_points == 1{
         Ok(flux_lf)
     }else{
-        let old_path = std::path::PathBuf::from(format!("wavelengths_tp{}.parquet",time_points-1));
+        let old_path = output_path(name, time_points-1);
         let old_lf = open_collecting_parquet_file_as_lazyframe(&old_path)?;
         Ok(append_current_lf_into_collection_lf(flux_lf, old_lf)?)
     }
@@ -163,12 +223,16 @@ fn lazyframe_to_be_written (time_points:u16,flux_lf:LazyFrame)->PolarsResult<Laz
 /// This function doesn't return a value however, it creates an output_file.parquet that has the information stored as columns with the headers 
 /// * `|time|wave length|flux|continuum|mean flux|`
 /// where `mean flux` is `flux / continuum`.
+///
+/// Next to it, an `out_of_bounds_tp{time_points}.parquet` file holds the number of visible surface cells
+/// outside of the intensity grids of every time point, with the columns `|time|out of bounds cells|`.
 pub fn write_into_parquet(
     time_points:u16,
     fluxes: FluxOfSpectra,
 )->PolarsResult<()>{
     //Initialize output_df
     let time_0 = fluxes.time[0];
+    let out_of_bounds_df = create_out_of_bounds_dataframe(&fluxes)?;
     let output_df = create_spectra_dataframe(fluxes).expect("something went very wrong while creating output");
     let lf = output_df.lazy();
     
@@ -177,10 +241,8 @@ pub fn write_into_parquet(
     let flux_lf = lf.with_column(expr);//<--Here's an error, it should be something like select... let's see How I fix it. However it's weird That it functions when I just present the fluxes...
 
     // write lazy frame into parquet
-    let new_path = PathBuf::from(
-        format!("wavelengths_tp{}.parquet",time_points)
-    );
-    let lf_to_write = lazyframe_to_be_written(time_points,
+    let new_path = output_path(FLUX_OUTPUT, time_points);
+    let lf_to_write = lazyframe_to_be_written(FLUX_OUTPUT, time_points,
          flux_lf.clone())?;
 
     if let Ok(lf) = lf_to_write.sink_parquet(
@@ -191,6 +253,17 @@ pub fn write_into_parquet(
             lf.collect()?;
         }else {eprint!("unable to sink to a parket in {} time_point",time_points)};
 
+    // write the out of bounds cells of the time points next to the fluxes
+    let out_of_bounds_path = output_path(OUT_OF_BOUNDS_OUTPUT, time_points);
+    let out_of_bounds_lf = lazyframe_to_be_written(OUT_OF_BOUNDS_OUTPUT, time_points, out_of_bounds_df.lazy())?;
+    if let Ok(lf) = out_of_bounds_lf.sink_parquet(
+        SinkTarget::Path(Arc::new(out_of_bounds_path.clone())),
+        ParquetWriteOptions::default(),
+        None,
+        SinkOptions::default()){
+            lf.collect()?;
+        }else {eprint!("unable to sink the out of bounds cells to a parket in {} time_point",time_points)};
+
     // print 5 rows of the parquet output
     let llf = LazyFrame::scan_parquet(new_path,
     ScanArgsParquet::default()).unwrap();
@@ -202,7 +275,8 @@ pub fn write_into_parquet(
     .unwrap().head(Some(5usize)));
     
     if time_points > 1u16
-    {remove_temp_parquet_file(time_points)?};
+    {remove_temp_parquet_file(FLUX_OUTPUT, time_points)?;
+    remove_temp_parquet_file(OUT_OF_BOUNDS_OUTPUT, time_points)?};
 
     Ok(())
 }
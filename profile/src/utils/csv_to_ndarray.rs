@@ -1,12 +1,14 @@
-use crate::IntensityGrid;
+use crate::{GridLattice,IntensityGrid};
 use crate::ProfileConfig;
 use crate::SpectralGrid;
-use crate::extract_column_as_vectorf64;
-use crate::intensity::parse_intensity_grids::{
-    filter_wavelength_range,
-    joris_grids::convert_joris_grid_to_regular_grid};
+use crate::error::ProfileError;
+use crate::intensity::parse_intensity_grids::wavelength_window;
+use crate::intensity::limb_darkening_fit::limb_darkening_fit;
+use crate::GridBackend;
+use crate::utils::grid_cache::{GridValues,MappedGrid};
+use crate::utils::pca_grid::CompressedGrids;
 use polars::prelude::*;
-use ndarray::{Array2,Array3};
+use ndarray::Array3;
 
 /// This module is used to obtain a [SpectralGrid] out of the Csv files provided by Nadya and Joris. 
 
@@ -14,9 +16,11 @@ use ndarray::{Array2,Array3};
 
 impl IntensityGrid {
     /// This function is used to provide an schema to the csv files read from disk
+    /// ### Arguments:
+    /// * `nmu` - the number of µ values of Nadya's grids.
     /// ### Returns:
     /// * - This function returns a [Vec] collection of the [Field]s used on [polars] to indicate the headers of the columns;
-    fn get_schema(& self)->Vec<Field>{
+    fn get_schema(& self, nmu:usize)->Vec<Field>{
         match self{
             // In Joris grids the first column is the wavelenght array,
             // the other 4 columns are the coefficients of the fourth order limb darkening law used to compute the specific intensity and the last four are for the continuum intensity.
@@ -34,25 +38,16 @@ impl IntensityGrid {
                 ]
             }
             // In  Nadya's grids the first column is the wavelenght array,
-            // the other 7 columns are the specific intensity values for  a given mu, and the last seven are for the continuum intensity for these same mu values.
+            // the next `nmu` columns are the specific intensity values for the µ values of the grid (mu1_s, mu2_s, ...),
+            // and the last `nmu` are the continuum intensity for these same µ values (mu1_c, mu2_c, ...).
+            // Analytic atmospheres and emulators don't have a grid file.
+            IntensityGrid::Analytic(_) | IntensityGrid::Emulator{..}=>{Vec::new()}
             IntensityGrid::Nadya { temperature:_, log_gravity:_, metalicity:_, filename:_ }=>{
-                vec![
-                    Field::new("wavelengths".into(), DataType::Float64),
-                    Field::new("mu1_s".into(),DataType::Float64),//specific intensity for mu=0.9636
-                    Field::new("mu2_s".into(),DataType::Float64),//specific intensity for mu=0.8864
-                    Field::new("mu3_s".into(),DataType::Float64),//specific intensity for mu=0.8018
-                    Field::new("mu4_s".into(),DataType::Float64),//specific intensity for mu=0.7071
-                    Field::new("mu5_s".into(),DataType::Float64),//specific intensity for mu=0.5976
-                    Field::new("mu6_s".into(),DataType::Float64),//specific intensity for mu=0.4629
-                    Field::new("mu7_s".into(),DataType::Float64),//specific intensity for mu=0.2673
-                    Field::new("mu1_c".into(),DataType::Float64),//continuum intensity for mu=0.9636
-                    Field::new("mu2_c".into(),DataType::Float64),//continuum intensity for mu=0.8864
-                    Field::new("mu3_c".into(),DataType::Float64),//continuum intensity for mu=0.8018
-                    Field::new("mu4_c".into(),DataType::Float64),//continuum intensity for mu=0.7071
-                    Field::new("mu5_c".into(),DataType::Float64),//continuum intensity for mu=0.5976
-                    Field::new("mu6_c".into(),DataType::Float64),//continuum intensity for mu=0.4629
-                    Field::new("mu7_c".into(),DataType::Float64),//continuum intensity for mu=0.2673
-                ]
+                let mut fields = vec![Field::new("wavelengths".into(), DataType::Float64)];
+                for kind in ["s","c"]{
+                    fields.extend((1..=nmu).map(|n| Field::new(format!("mu{}_{}",n,kind).into(), DataType::Float64)));
+                }
+                fields
             }
         }
 
@@ -61,167 +56,106 @@ impl IntensityGrid {
 
 
      /// This function is used to create a polars LazyFrame out of the intensity grid file in order to perform 
-     /// column wise operations faster. 
+     /// column wise operations faster. The lines starting with `#` are comments.
      /// ### Arguments:
      /// * `path` - a reference to a string slice that contains the relative path to the intensity grid file
+     /// * `nmu` - the number of µ values of Nadya's grids.
      /// ### Returns:
      /// * `PolarsResult<LazyFrame>` - where the lazy frame has as headers
      /// * `|wavelength|a|b|c|d|ac|bc|cc|dc|`- In case we're dealing with Joris intensity grids.
-     /// * `|wavelength|I_s(mu1)|I_s(mu2)|I_s(mu3)|I_s(mu4)|I_s(mu5)|I_s(mu6)|I_s(mu7)|I_c(mu1)|I_c(mu2)|I_c(mu3)|I_c(mu4)|I_c(mu5)|I_c(mu6)|I_c(mu7)|`- In case they're Nadya's intensity grids.
-     fn read_intensity_grid_file(& self,path_to_grid: &str) -> PolarsResult<LazyFrame> {
-        let filename = match self {
-            Self::Joris { temperature:_, log_gravity:_, filename:path_to_grid}=>{path_to_grid} 
-            Self::Nadya { temperature:_, log_gravity:_, metalicity:_, filename: path_to_grid }=>{path_to_grid}
-        };
-        let path = format!("{}{}",path_to_grid,filename);
-        let schema = Schema::from_iter(self.get_schema());
+     /// * `|wavelength|I_s(mu1)|...|I_s(mu_nmu)|I_c(mu1)|...|I_c(mu_nmu)|`- In case they're Nadya's intensity grids.
+     pub(crate) fn read_intensity_grid_file(& self,path_to_grid: &str, nmu:usize) -> PolarsResult<LazyFrame> {
+        let path = format!("{}{}",path_to_grid,self.filename());
+        let schema = Schema::from_iter(self.get_schema(nmu));
         let lf= LazyCsvReader::new(path)
         .with_separator(b' ')
         .with_has_header(false)
+        .with_comment_prefix(Some("#".into()))
         .with_schema(Some(Arc::new(schema))).finish()?;
         Ok(lf)
     }
-
-
-    /// This function extracts all of the csv data binded to a [IntensityGrid] into a [Vec<f64>]
-    fn extract_grid_into_vector(& self, 
-        wavelengths:&[f64],
-        maxval_rel_dopplershift:f64,
-        minval_rel_dopplershift:f64,
-        path_to_grid: &str)->(usize,Vec<f64>){
-
-        let filtered_lf = filter_wavelength_range(
-            self.read_intensity_grid_file(path_to_grid).unwrap(),
-            wavelengths,
-            maxval_rel_dopplershift,
-            minval_rel_dopplershift);
-        let grid_df = match self{
-                Self::Nadya { temperature:_, log_gravity:_, metalicity:_, filename:_ }=>{filtered_lf.collect().unwrap()}
-                Self::Joris { temperature:_, log_gravity:_, filename:_ }=>{convert_joris_grid_to_regular_grid(filtered_lf.clone()).collect().unwrap()}
-                _=>{panic!("this grid type {:?} hasn't been coded!",self)}
-        };
-        let mut collection:Vec<f64>= Vec::new();
-            //I'm going to order the mu values from lower to greater.
-        collection.append(&mut extract_column_as_vectorf64("mu7_s", &grid_df));
-        let nwavelengths = collection.len();
-        collection.append(&mut extract_column_as_vectorf64("mu6_s", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu5_s", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu4_s", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu3_s", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu2_s", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu1_s", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu7_c", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu6_c", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu5_c", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu4_c", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu3_c", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu2_c", &grid_df));
-        collection.append(&mut extract_column_as_vectorf64("mu1_c", &grid_df));
-            
-        (nwavelengths,collection)
-    }
-
-    ///This function extracts the data contained in the csv file binded to a [IntensityGrid] into a 2D array
-    /// ### Returns:
-    /// * - A [Array2<f64>] that has the same tabular structure as the csv file of the specific intensity grid. 
-    fn extract_grid_into_array2(& self,
-        wavelengths:&[f64],
-        maxval_rel_dopplershift:f64,
-        minval_rel_dopplershift:f64,
-        path_to_grid: &str
-        )->Array2<f64>{
-        let ncols= 14usize;
-        let (nrows, collection) = self.extract_grid_into_vector(wavelengths,maxval_rel_dopplershift,minval_rel_dopplershift,path_to_grid);
-        //the shape of the matrix is inverted because polars stores data column wise, and ndarray does it row wise.
-        let shape = (ncols,nrows);
-        //Then the array obtained used ndarray's from_shape_vec method is the transpose of the one we want
-        let transposed = ndarray::Array2::from_shape_vec(shape, collection).unwrap();
-        //So the final array must have the axes reversed.
-        transposed.reversed_axes()
-    }
 }
 
 impl ProfileConfig{
     /// This function is used to obtain a [SpectralGrid] data structure from the appropriately loaded specific intensity grids.
+    /// The grids are read from their binary caches when they're up to date, see [crate::utils::grid_cache], or parsed from the csv files otherwise.
+    /// Only the rows needed to interpolate the Doppler shifted observed wavelengths are kept.
+    /// With [GridBackend::MemoryMapped] the binary caches are mapped into memory instead, and the missing ones are written first.
+    /// With [GridBackend::Compressed] the rows are reconstructed from the file of compressed grids, see [crate::utils::pca_grid].
     /// ### Arguments:
     /// * This is an implementation on the [ProfileConfig] data structure that contains the user's inputs. 
     /// ### Returns:
-    /// * This implementation returns a [SpectralGrid] that contains the domain on the parameter space as well as the tabular data of the intensity grids. 
+    /// * This implementation returns a [SpectralGrid] that contains the domain on the parameter space as well as the tabular data of the intensity grids,
+    ///   or a [ProfileError] if the grids could not be loaded, they don't share the same wavelengths and µ values or they don't form a complete [GridLattice]. 
     pub fn init_spectral_grid_from_csv(&self,
         maxval_rel_dopplershift:f64,
-        minval_rel_dopplershift:f64)->SpectralGrid{
+        minval_rel_dopplershift:f64)->Result<SpectralGrid,ProfileError>{
 
         let intensity_grids = &self.intensity_grids;
+        let lattice = GridLattice::from_intensity_grids(intensity_grids)?;
         
-        //nested array2 of the data in the grid files. 
-        let obs_wavelengths= self.wavelength_range.get_wavelength_vector();
-        let wavelengths = self.extract_wavelength_array_from_grid(
-            &obs_wavelengths,
-            maxval_rel_dopplershift,
-            minval_rel_dopplershift
-        );
-        let mut t_eff:[f64;2] = [0.0;2];
-        let mut log_g:[f64;2] = [0.0;2];
-
-        let nrows=wavelengths.len();
-        let ncols= 14usize;
-        
-        let mut nested:Vec<Array2<f64>>=Vec::new();
-
-        for (n,grid) in intensity_grids.iter().enumerate(){
-            nested.push(grid.extract_grid_into_array2(&wavelengths,
-            maxval_rel_dopplershift,
-            minval_rel_dopplershift,
-        &self.path_to_grids));
-            if n>3{panic!("Exceeded the number of spectral grids.")}
-        }
-        
-        let flat:Vec<f64> = nested.iter().flatten().cloned().collect();
-        let shape = (4usize,nrows,ncols);        
-        let array3 = Array3::from_shape_vec(shape, flat).expect("There was an error loading the spectral grids.");
-
-        //Fill last members of coordinate space
-        match self.intensity_grids[3]{
-            IntensityGrid::Joris { temperature , log_gravity , filename:_ }=>{
-                t_eff[1]=temperature;
-                log_g[1]=log_gravity;
+        let obs_wavelengths= self.wavelength_range.get_wavelength_vector()?;
+        let (min_wavelength, max_wavelength) = wavelength_window(&obs_wavelengths, maxval_rel_dopplershift, minval_rel_dopplershift);
+
+        let mut window_wavelengths:Vec<Vec<f64>> = Vec::with_capacity(intensity_grids.len());
+        let mut grids_mu_values:Vec<Vec<f64>> = Vec::with_capacity(intensity_grids.len());
+        let grid_values = match self.grid_backend{
+            GridBackend::InMemory => {
+                let mut flat:Vec<f64> = Vec::new();
+                for grid in intensity_grids.iter(){
+                    let parsed = grid.load_grid(&self.path_to_grids, &self.mu_values)?;
+                    let rows = parsed.rows_within(min_wavelength, max_wavelength);
+                    window_wavelengths.push(parsed.wavelengths[rows.clone()].to_vec());
+                    flat.extend(parsed.rows(rows).iter());
+                    grids_mu_values.push(parsed.mu_values);
+                }
+                let shape = (intensity_grids.len(),window_wavelengths[0].len(),2*grids_mu_values[0].len());
+                match Array3::from_shape_vec(shape, flat){
+                    Ok(array) => GridValues::InMemory(array),
+                    Err(e) => {return Err(ProfileError::GridFormat(format!("the intensity grids don't share the same wavelengths and µ values: {}",e)))}
+                }
             }
-            IntensityGrid::Nadya { temperature , log_gravity , metalicity:_ , filename:_}=>{
-                t_eff[1]=temperature;
-                log_g[1]=log_gravity;
+            GridBackend::MemoryMapped => {
+                let mut mapped_grids:Vec<MappedGrid> = Vec::with_capacity(intensity_grids.len());
+                for grid in intensity_grids.iter(){
+                    let mut mapped = grid.map_grid(&self.path_to_grids, &self.mu_values)?;
+                    let rows = mapped.rows_within(min_wavelength, max_wavelength);
+                    window_wavelengths.push(rows.clone().map(|row| mapped.wavelength(row)).collect());
+                    mapped.set_window(rows);
+                    grids_mu_values.push(mapped.mu_values());
+                    mapped_grids.push(mapped);
+                }
+                GridValues::MemoryMapped(mapped_grids)
             }
-        }
+            GridBackend::Compressed => {
+                let compressed = CompressedGrids::read_from(&self.compressed_grids_path())?;
+                let rows = compressed.rows_within(min_wavelength, max_wavelength);
+                let filenames:Vec<&str> = intensity_grids.iter().map(|grid| grid.filename()).collect();
+                let selected = compressed.select(&filenames, rows)?;
+                // All of the compressed grids share the same wavelengths and µ values.
+                window_wavelengths.push(selected.wavelengths.clone());
+                grids_mu_values.push(selected.mu_values.clone());
+                GridValues::Compressed(Box::new(selected))
+            }
+        };
 
-        //Fill first members of coordinate space and create spectral grids;
-        let mu_values=[0.2673,0.4629,0.5976,0.7071,0.8018,0.8864,0.9636];
-        match self.intensity_grids[0]{
-            IntensityGrid::Joris { temperature , log_gravity , filename:_ }=>{
-                t_eff[0]=temperature;
-                log_g[0]=log_gravity;
+        // All of the grids must have the same rows and columns, they're indexed with the wavelengths and µ values of the first one.
+        let wavelengths = window_wavelengths.remove(0);
+        let mu_values = grids_mu_values.remove(0);
+        for ((grid,grid_wavelengths),grid_mu_values) in intensity_grids.iter().skip(1).zip(window_wavelengths.iter()).zip(grids_mu_values.iter()){
+            if *grid_wavelengths != wavelengths{
+                return Err(ProfileError::GridFormat(format!("the intensity grids don't share the same wavelengths, {} differs from {}",
+                    grid.filename(), intensity_grids[0].filename())))
             }
-            IntensityGrid::Nadya { temperature , log_gravity , metalicity:_ , filename:_  }=>{
-                t_eff[0]=temperature;
-                log_g[0]=log_gravity; 
+            if *grid_mu_values != mu_values{
+                return Err(ProfileError::GridFormat(format!("the intensity grids don't share the same µ values, {} has {:?} and {} has {:?}",
+                    grid.filename(), grid_mu_values, intensity_grids[0].filename(), mu_values)))
             }
         }
-        let row_indices = vec![0usize;2*wavelengths.len()];
-        SpectralGrid{ t_eff:t_eff, log_g:log_g, grid_values: array3, wavelengths:wavelengths, mu_values:mu_values, row_indices:row_indices}
-    }
 
-    ///This function is used to obtain the wavelengths of the specific intensity grids. 
-    fn extract_wavelength_array_from_grid(&self,
-        obs_wavelengths:&[f64],
-        maxval_rel_dopplershift:f64,
-        minval_rel_dopplershift:f64)->Vec<f64>{
-        let df = filter_wavelength_range(self.intensity_grids[0].read_intensity_grid_file(&self.path_to_grids).unwrap(),
-                    obs_wavelengths,
-                    maxval_rel_dopplershift,
-                    minval_rel_dopplershift
-                    )
-                    .collect()
-                    .unwrap();
-
-        extract_column_as_vectorf64("wavelengths", &df)
+        //Create spectral grids
+        let limb_darkening_fit = limb_darkening_fit(&mu_values);
+        let wavelength_step = SpectralGrid::uniform_step(&wavelengths);
+        Ok(SpectralGrid{ lattice, grid_values, wavelengths, wavelength_step, mu_values, limb_darkening_fit, interpolation: self.interpolation})
     }
 }
-
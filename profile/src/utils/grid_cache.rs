@@ -0,0 +1,424 @@
+use crate::IntensityGrid;
+use crate::ProfileConfig;
+use crate::extract_column_as_vectorf64;
+use crate::error::ProfileError;
+use crate::validation::check_mu_values;
+use crate::utils::pca_grid::CompressedGrids;
+use crate::intensity::parse_intensity_grids::joris_grids::convert_joris_grid_to_regular_grid;
+use polars::prelude::*;
+use ndarray::{Array2,Array3,ArrayView2,s};
+use memmap2::Mmap;
+use std::io::{BufRead,Read,Write};
+use std::ops::Range;
+use std::path::{Path,PathBuf};
+
+/// Extension added to the name of an intensity grid file to get the name of its binary cache.
+pub const CACHE_EXTENSION: &str = "grid";
+/// First bytes of a binary cache, they identify the format and its version.
+pub const CACHE_MAGIC: [u8;8] = *b"PRFGRID2";
+
+/// Size of the header of a binary cache in bytes.
+const HEADER_BYTES: usize = 24;
+
+/// The contents of an intensity grid file, sorted by wavelength.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ParsedGrid{
+    /// µ values of the grid in increasing order.
+    pub mu_values: Vec<f64>,
+    /// Wavelengths of the grid in increasing order.
+    pub wavelengths: Vec<f64>,
+    /// A row per wavelength with the specific intensities for the µ values in increasing order (columns `0..nmu`),
+    /// followed by the continuum intensities for the same µ values (columns `nmu..2*nmu`).
+    pub values: Array2<f64>,
+}
+
+impl ParsedGrid{
+    /// This function reads a binary cache.
+    /// ### Arguments:
+    /// * `path` - the path to the binary cache.
+    /// ### Returns:
+    /// * `Ok(ParsedGrid)` - the contents of the cache.
+    /// * `Err(ProfileError::Io)` - if the file could not be read.
+    /// * `Err(ProfileError::GridFormat)` - if the file is not a binary cache of an intensity grid or it's truncated.
+    pub fn read_from(path:&Path)->Result<Self,ProfileError>{
+        let mut bytes:Vec<u8> = Vec::new();
+        std::fs::File::open(path)?.read_to_end(&mut bytes)?;
+        let (nrows, nmu) = check_layout(path, &bytes)?;
+
+        let mut numbers = bytes[HEADER_BYTES..]
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().expect("chunks of eight bytes")));
+        let mu_values:Vec<f64> = numbers.by_ref().take(nmu).collect();
+        let wavelengths:Vec<f64> = numbers.by_ref().take(nrows).collect();
+        let values = Array2::from_shape_vec((nrows,2*nmu), numbers.collect())
+            .map_err(|e| ProfileError::GridFormat(format!("{} is not a valid grid cache, {}", path.display(), e)))?;
+        Ok(Self{mu_values, wavelengths, values})
+    }
+
+    /// This function writes the grid into a binary cache, see the layout in the documentation of the module.
+    /// It returns a [ProfileError::Io] if the file could not be written.
+    pub fn write_to(&self, path:&Path)->Result<(),ProfileError>{
+        let (nrows, ncols) = self.values.dim();
+        let nmu = self.mu_values.len();
+        let mut bytes:Vec<u8> = Vec::with_capacity(HEADER_BYTES + 8*(nmu + nrows*(ncols+1)));
+        bytes.extend_from_slice(&CACHE_MAGIC);
+        bytes.extend_from_slice(&(nrows as u64).to_le_bytes());
+        bytes.extend_from_slice(&(nmu as u64).to_le_bytes());
+        for mu in self.mu_values.iter(){
+            bytes.extend_from_slice(&mu.to_le_bytes());
+        }
+        for wavelength in self.wavelengths.iter(){
+            bytes.extend_from_slice(&wavelength.to_le_bytes());
+        }
+        // The iterator of an array follows the logical (row major) order regardless of its memory layout.
+        for value in self.values.iter(){
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        std::fs::File::create(path)?.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// This function returns the rows of the grid that are needed to interpolate the wavelengths within `[min_wavelength, max_wavelength]`.
+    /// These are the rows inside of the window plus the closest row on each side, so that every wavelength of the window is bracketed.
+    pub fn rows_within(&self, min_wavelength:f64, max_wavelength:f64)->Range<usize>{
+        rows_within(self.wavelengths.len(), |row| self.wavelengths[row], min_wavelength, max_wavelength)
+    }
+
+    /// Returns the intensities of the given rows.
+    pub fn rows(&self, rows:Range<usize>)->ArrayView2<'_,f64>{
+        self.values.slice(s![rows,..])
+    }
+}
+
+/// This function finds the rows within `[min_wavelength, max_wavelength]`, plus the closest row on each side,
+/// of a grid with `nrows` wavelengths in increasing order given by `wavelength`.
+fn rows_within(nrows:usize, wavelength:impl Fn(usize)->f64, min_wavelength:f64, max_wavelength:f64)->Range<usize>{
+    let first = partition_point(nrows, |row| wavelength(row) < min_wavelength);
+    let last = partition_point(nrows, |row| wavelength(row) <= max_wavelength);
+    first.saturating_sub(1)..(last+1).min(nrows)
+}
+
+/// Same as [slice::partition_point] on the rows `0..nrows`, it returns the first row for which `predicate` is `false`.
+fn partition_point(nrows:usize, predicate:impl Fn(usize)->bool)->usize{
+    let (mut bottom, mut top) = (0usize, nrows);
+    while bottom < top{
+        let middle = bottom + (top-bottom)/2;
+        if predicate(middle) {bottom = middle + 1} else {top = middle}
+    }
+    bottom
+}
+
+/// This function checks that `bytes` hold a binary cache of an intensity grid, see the layout in the documentation of the module.
+/// ### Returns:
+/// * `Ok((usize,usize))` - the number of rows (wavelengths) and µ values of the grid.
+/// * `Err(ProfileError::GridFormat)` - if the header is not valid, the grid doesn't have µ values or the size doesn't match the header.
+fn check_layout(path:&Path, bytes:&[u8])->Result<(usize,usize),ProfileError>{
+    let invalid = |reason:&str| ProfileError::GridFormat(format!("{} is not a valid grid cache, {}", path.display(), reason));
+
+    let (nrows, nmu) = parse_header(bytes).ok_or_else(|| invalid("the header is missing or corrupted"))?;
+    if nmu == 0{
+        return Err(invalid("it doesn't have µ values"))
+    }
+    let expected_bytes = nmu.checked_mul(2)
+        .and_then(|ncols| nrows.checked_mul(ncols+1))
+        .and_then(|n| n.checked_add(nmu))
+        .and_then(|n| n.checked_mul(8))
+        .and_then(|n| n.checked_add(HEADER_BYTES));
+    if expected_bytes != Some(bytes.len()){
+        return Err(invalid("its size doesn't match its header"))
+    }
+    Ok((nrows, nmu))
+}
+
+/// Returns the number of rows and µ values written in the header of a binary cache, or [None] if the header is not valid.
+fn parse_header(bytes:&[u8])->Option<(usize,usize)>{
+    if bytes.len() < HEADER_BYTES || bytes[0..8] != CACHE_MAGIC{
+        return None
+    }
+    let nrows = u64::from_le_bytes(bytes[8..16].try_into().ok()?);
+    let nmu = u64::from_le_bytes(bytes[16..24].try_into().ok()?);
+    Some((usize::try_from(nrows).ok()?, usize::try_from(nmu).ok()?))
+}
+
+/// A binary cache of an intensity grid mapped into memory, the intensities are only read from disk when they're accessed.
+///
+/// The grid keeps a window of rows (see [MappedGrid::set_window]) so that its rows are numbered as the ones of an in-memory grid
+/// loaded for the same wavelengths.
+#[derive(Clone)]
+pub struct MappedGrid{
+    /// The memory map of the binary cache.
+    map: Arc<Mmap>,
+    /// Number of rows (wavelengths) of the whole grid.
+    nrows: usize,
+    /// Number of µ values of the grid.
+    nmu: usize,
+    /// First row of the window.
+    first_row: usize,
+}
+
+impl MappedGrid{
+    /// This function maps a binary cache into memory.
+    /// The cache must not be modified while the grid is in use, e.g. by running `profile cache` at the same time.
+    /// ### Arguments:
+    /// * `path` - the path to the binary cache.
+    /// ### Returns:
+    /// * `Ok(MappedGrid)` - the mapped grid, with a window that covers the whole grid.
+    /// * `Err(ProfileError::Io)` - if the file could not be opened or mapped.
+    /// * `Err(ProfileError::GridFormat)` - if the file is not a binary cache of an intensity grid or it's truncated.
+    pub fn open(path:&Path)->Result<Self,ProfileError>{
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the cache files are only written by `write_to`, which isn't called on a cache that is being used by a run.
+        let map = unsafe {Mmap::map(&file)?};
+        let (nrows, nmu) = check_layout(path, &map)?;
+        Ok(Self{map: Arc::new(map), nrows, nmu, first_row: 0})
+    }
+
+    /// Returns the number of rows (wavelengths) of the whole grid.
+    pub fn nrows(&self)->usize{
+        self.nrows
+    }
+
+    /// Returns the µ values of the grid in increasing order.
+    pub fn mu_values(&self)->Vec<f64>{
+        (0..self.nmu).map(|n| self.read_f64(HEADER_BYTES + 8*n)).collect()
+    }
+
+    /// Returns the wavelength of a row of the whole grid.
+    pub fn wavelength(&self, row:usize)->f64{
+        self.read_f64(HEADER_BYTES + 8*(self.nmu + row))
+    }
+
+    /// Same as [ParsedGrid::rows_within], the rows are the ones of the whole grid.
+    pub fn rows_within(&self, min_wavelength:f64, max_wavelength:f64)->Range<usize>{
+        rows_within(self.nrows, |row| self.wavelength(row), min_wavelength, max_wavelength)
+    }
+
+    /// Restricts the grid to the given rows, [MappedGrid::value] counts the rows from the start of the window.
+    pub fn set_window(&mut self, rows:Range<usize>){
+        self.first_row = rows.start;
+    }
+
+    /// Returns the intensity at a row of the window and a column, see [ParsedGrid::values].
+    pub fn value(&self, row:usize, column:usize)->f64{
+        self.read_f64(HEADER_BYTES + 8*(self.nmu + self.nrows + (self.first_row+row)*2*self.nmu + column))
+    }
+
+    /// Returns the sum of the columns `first_column + index` of a row of the window times their weights, see [GridValues::weighted_rows].
+    pub fn weighted_row(&self, row:usize, first_column:usize, weights:&[(usize,f64)])->f64{
+        let start = HEADER_BYTES + 8*(self.nmu + self.nrows + (self.first_row+row)*2*self.nmu);
+        let row_bytes = &self.map[start..start+16*self.nmu];
+        weights.iter()
+            .map(|(index,weight)| {
+                let offset = 8*(first_column+index);
+                weight*f64::from_le_bytes(row_bytes[offset..offset+8].try_into().expect("slices of eight bytes"))
+            })
+            .sum()
+    }
+
+    fn read_f64(&self, offset:usize)->f64{
+        f64::from_le_bytes(self.map[offset..offset+8].try_into().expect("slices of eight bytes"))
+    }
+}
+
+/// The intensities of the loaded intensity grids, indexed by (grid, row, column) where the rows are the wavelengths
+/// kept for the run and the columns the ones of [ParsedGrid::values].
+#[derive(Clone)]
+pub enum GridValues{
+    /// The rows of all of the grids loaded into memory.
+    InMemory(Array3<f64>),
+    /// The binary caches of the grids mapped into memory, in the order of the intensity grids.
+    MemoryMapped(Vec<MappedGrid>),
+    /// The rows of the compressed grids, in the order of the intensity grids, see [crate::utils::pca_grid].
+    Compressed(Box<CompressedGrids>),
+}
+
+impl GridValues{
+    /// Returns the intensity of a grid at a row and column.
+    pub fn value(&self, grid:usize, row:usize, column:usize)->f64{
+        match self{
+            Self::InMemory(values) => {values[[grid,row,column]]}
+            Self::MemoryMapped(grids) => {grids[grid].value(row, column)}
+            Self::Compressed(grids) => {grids.value(grid, row, column)}
+        }
+    }
+
+    /// Writes into `out` the sums of the columns `first_column + index` of the consecutive `rows` of a grid times their weights,
+    /// given as pairs `(index, weight)`, e.g. the intensities of the rows at the µ of a surface cell. The sums are the same as the ones
+    /// of [GridValues::value], but the rows loaded into memory are read as contiguous slices.
+    pub fn weighted_rows(&self, grid:usize, rows:Range<usize>, first_column:usize, weights:&[(usize,f64)], out:&mut [f64]){
+        match self{
+            Self::InMemory(values) => {
+                let ncolumns = values.dim().2;
+                let grid_values = values.slice(s![grid,rows.clone(),..]);
+                match grid_values.as_slice(){
+                    Some(flat) => {
+                        for (row_values,value) in flat.chunks_exact(ncolumns).zip(out.iter_mut()){
+                            let columns = &row_values[first_column..];
+                            *value = weights.iter().map(|(index,weight)| weight*columns[*index]).sum();
+                        }
+                    }
+                    None => {
+                        for (row,value) in rows.zip(out.iter_mut()){
+                            *value = weights.iter().map(|(index,weight)| weight*values[[grid,row,first_column+index]]).sum();
+                        }
+                    }
+                }
+            }
+            Self::MemoryMapped(grids) => {
+                for (row,value) in rows.zip(out.iter_mut()){
+                    *value = grids[grid].weighted_row(row, first_column, weights);
+                }
+            }
+            Self::Compressed(grids) => {
+                for (row,value) in rows.zip(out.iter_mut()){
+                    *value = weights.iter().map(|(index,weight)| weight*grids.value(grid,row,first_column+index)).sum();
+                }
+            }
+        }
+    }
+}
+
+impl IntensityGrid{
+    /// Returns the path of the binary cache of the intensity grid, see [CACHE_EXTENSION].
+    pub fn cache_path(&self, path_to_grids:&str)->PathBuf{
+        PathBuf::from(format!("{}{}.{}", path_to_grids, self.filename(), CACHE_EXTENSION))
+    }
+
+    /// Returns `true` if the binary cache of the grid exists and it was written after the grid file was last modified.
+    pub fn has_cache(&self, path_to_grids:&str)->bool{
+        let modified = |path:&Path| std::fs::metadata(path).and_then(|metadata| metadata.modified());
+        let grid_file = PathBuf::from(format!("{}{}", path_to_grids, self.filename()));
+        match (modified(&self.cache_path(path_to_grids)), modified(&grid_file)){
+            (Ok(cache), Ok(grid)) => {cache >= grid}
+            _ => {false}
+        }
+    }
+
+    /// This function returns the µ values of the columns of the intensity grid file, in the order of the columns.
+    /// They're the ones listed in the first line of Nadya's grids if it starts with `# mu`, or `mu_values` otherwise (see [ProfileConfig::mu_values]).
+    /// The limb darkening law of Joris' grids is evaluated on `mu_values`.
+    /// It returns a [ProfileError] if the file could not be read, or the µ values are not valid (see [check_mu_values]).
+    pub fn column_mu_values(&self, path_to_grids:&str, mu_values:&[f64])->Result<Vec<f64>,ProfileError>{
+        let invalid = |reason:String| ProfileError::GridFormat(format!("{}: {}", self.filename(), reason));
+        let column_mu_values = match self{
+            Self::Nadya{..} => {
+                let mut first_line = String::new();
+                std::io::BufReader::new(std::fs::File::open(format!("{}{}", path_to_grids, self.filename()))?).read_line(&mut first_line)?;
+                match first_line.trim_start().strip_prefix('#').map(|comment| comment.split_whitespace().collect::<Vec<&str>>()){
+                    Some(words) if words.first() == Some(&"mu") => {
+                        words[1..].iter()
+                            .map(|word| word.parse::<f64>().map_err(|e| invalid(format!("the µ value '{}' of the first line is not a number, {}", word, e))))
+                            .collect::<Result<Vec<f64>,ProfileError>>()?
+                    }
+                    _ => {mu_values.to_vec()}
+                }
+            }
+            Self::Joris{..} => {mu_values.to_vec()}
+            Self::Analytic(_) | Self::Emulator{..} => {
+                return Err(ProfileError::GridFormat(String::from("an analytic atmosphere or an emulator doesn't have a grid file")))
+            }
+        };
+        check_mu_values(&column_mu_values).map_err(invalid)?;
+        Ok(column_mu_values)
+    }
+
+    /// This function parses the whole csv file of the intensity grid, sorted by wavelength and µ.
+    /// ### Arguments:
+    /// * `path_to_grids` - the directory of the intensity grid files.
+    /// * `mu_values` - the µ values of the columns of the grid files, see [IntensityGrid::column_mu_values].
+    /// ### Returns:
+    /// It returns a [ProfileError] if the file could not be read or doesn't have the expected columns, or if the grid is [IntensityGrid::Analytic]
+    /// or [IntensityGrid::Emulator].
+    pub fn parse_grid_file(&self, path_to_grids:&str, mu_values:&[f64])->Result<ParsedGrid,ProfileError>{
+        let column_mu_values = self.column_mu_values(path_to_grids, mu_values)?;
+        let nmu = column_mu_values.len();
+        let lf = self.read_intensity_grid_file(path_to_grids, nmu)?;
+        let regular_lf = match self{
+            Self::Nadya{..} => {lf}
+            Self::Joris{..} => {convert_joris_grid_to_regular_grid(lf, &column_mu_values)}
+            Self::Analytic(_) | Self::Emulator{..} => {unreachable!("an analytic atmosphere or an emulator doesn't have µ values")}
+        };
+        let grid_df = regular_lf.sort(["wavelengths"], SortMultipleOptions::default()).collect()?;
+
+        let wavelengths = extract_column_as_vectorf64("wavelengths", &grid_df)?;
+        //I'm going to order the mu values from lower to greater.
+        let mut order:Vec<usize> = (0..nmu).collect();
+        order.sort_by(|a,b| column_mu_values[*a].total_cmp(&column_mu_values[*b]));
+        let columns:Vec<String> = ["s","c"].iter()
+            .flat_map(|kind| order.iter().map(move |n| format!("mu{}_{}",n+1,kind)))
+            .collect();
+        let mut values = Array2::<f64>::zeros((wavelengths.len(), 2*nmu));
+        for (n,column) in columns.iter().enumerate(){
+            let column_values = extract_column_as_vectorf64(column, &grid_df)?;
+            if column_values.len() != wavelengths.len(){
+                return Err(ProfileError::GridFormat(format!("{}: the column {} has missing values", self.filename(), column)))
+            }
+            values.column_mut(n).assign(&ndarray::Array1::from(column_values));
+        }
+        let mu_values = order.iter().map(|n| column_mu_values[*n]).collect();
+        Ok(ParsedGrid{mu_values, wavelengths, values})
+    }
+
+    /// This function writes the binary cache of the intensity grid out of its csv file, see [IntensityGrid::parse_grid_file].
+    /// It returns a [ProfileError] if the grid could not be parsed or the cache could not be written.
+    pub fn write_cache(&self, path_to_grids:&str, mu_values:&[f64])->Result<ParsedGrid,ProfileError>{
+        let grid = self.parse_grid_file(path_to_grids, mu_values)?;
+        grid.write_to(&self.cache_path(path_to_grids))?;
+        Ok(grid)
+    }
+
+    /// This function returns the contents of the intensity grid, out of its binary cache if it's up to date (see [IntensityGrid::has_cache])
+    /// and it has the same µ values, or out of its csv file otherwise (see [IntensityGrid::parse_grid_file]).
+    /// An invalid cache is reported and the csv file is used instead.
+    pub fn load_grid(&self, path_to_grids:&str, mu_values:&[f64])->Result<ParsedGrid,ProfileError>{
+        if self.has_cache(path_to_grids){
+            let expected_mu_values = sorted(self.column_mu_values(path_to_grids, mu_values)?);
+            match ParsedGrid::read_from(&self.cache_path(path_to_grids)){
+                Ok(grid) if grid.mu_values == expected_mu_values => {return Ok(grid)}
+                Ok(_) => {eprintln!("warning: the cache of {} has other µ values. Parsing {} instead", self.filename(), self.filename())}
+                Err(e) => {eprintln!("warning: {}. Parsing {} instead", e, self.filename())}
+            }
+        }
+        self.parse_grid_file(path_to_grids, mu_values)
+    }
+
+    /// This function maps the binary cache of the intensity grid into memory, see [MappedGrid].
+    /// The cache is written first if it's not up to date, it's invalid, or it has other µ values, see [IntensityGrid::load_grid].
+    /// It returns a [ProfileError] if the cache could not be written or mapped.
+    pub fn map_grid(&self, path_to_grids:&str, mu_values:&[f64])->Result<MappedGrid,ProfileError>{
+        let path = self.cache_path(path_to_grids);
+        if self.has_cache(path_to_grids){
+            let expected_mu_values = sorted(self.column_mu_values(path_to_grids, mu_values)?);
+            match MappedGrid::open(&path){
+                Ok(grid) if grid.mu_values() == expected_mu_values => {return Ok(grid)}
+                Ok(_) => {eprintln!("warning: the cache of {} has other µ values. Writing it again", self.filename())}
+                Err(e) => {eprintln!("warning: {}. Writing it again", e)}
+            }
+        }
+        self.write_cache(path_to_grids, mu_values)?;
+        MappedGrid::open(&path)
+    }
+}
+
+/// Returns the values in increasing order.
+fn sorted(mut values:Vec<f64>)->Vec<f64>{
+    values.sort_by(f64::total_cmp);
+    values
+}
+
+impl ProfileConfig{
+    /// This function converts all of the intensity grids of the configuration into their binary caches,
+    /// so that the following runs don't need to parse the csv files. An [IntensityGrid::Analytic] atmosphere or an [IntensityGrid::Emulator]
+    /// doesn't have a cache.
+    /// ### Returns:
+    /// * `Ok(Vec<PathBuf>)` - the paths of the binary caches written.
+    /// * `Err(ProfileError)` - if a grid could not be parsed or its cache could not be written.
+    pub fn write_grid_caches(&self)->Result<Vec<PathBuf>,ProfileError>{
+        let mut paths:Vec<PathBuf> = Vec::with_capacity(self.intensity_grids.len());
+        for grid in self.intensity_grids.iter().filter(|grid| grid.is_grid()){
+            grid.write_cache(&self.path_to_grids, &self.mu_values)?;
+            paths.push(grid.cache_path(&self.path_to_grids));
+        }
+        Ok(paths)
+    }
+}
@@ -0,0 +1,297 @@
+use crate::ProfileConfig;
+use crate::error::ProfileError;
+use crate::validation::check_mu_values;
+use crate::utils::grid_cache::ParsedGrid;
+use nalgebra::{DMatrix,DVector,SymmetricEigen};
+use ndarray::{Array2,Array3,s};
+use std::io::{Read,Write};
+use std::ops::Range;
+use std::path::{Path,PathBuf};
+
+/// Name of the file in the grid directory that holds the compressed intensity grids.
+pub const COMPRESSED_GRIDS_FILE: &str = "intensity_grids.pca";
+/// First bytes of a file of compressed intensity grids, they identify the format and its version.
+pub const PCA_MAGIC: [u8;8] = *b"PRFPCA01";
+/// Number of principal components kept by `profile compress` if it's not given.
+pub const DEFAULT_COMPONENTS: usize = 8;
+
+/// Size of the header of a file of compressed intensity grids in bytes.
+const HEADER_BYTES: usize = 40;
+
+/// Principal components whose norm is below this fraction of the norm of the spectra of a column minus their mean are numerical noise,
+/// they're stored as zeros.
+const NEGLIGIBLE_NORM: f64 = 1.0e-12;
+
+/// The intensity grids of a lattice compressed into a principal component basis over wavelength, one per column (see [ParsedGrid::values]).
+///
+/// The intensity of the grid `g` at a row and column is `mean[column,row] + Σ_k coefficients[g,column,k] components[column,k,row]`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CompressedGrids{
+    /// µ values of the grids in increasing order.
+    pub mu_values: Vec<f64>,
+    /// Wavelengths of the grids in increasing order.
+    pub wavelengths: Vec<f64>,
+    /// File names of the compressed intensity grids, in the order of their coefficients.
+    pub filenames: Vec<String>,
+    /// Mean intensities over the grids, indexed by (column, row).
+    means: Array2<f64>,
+    /// Principal components of each column, indexed by (column, component, row). They have a unit norm.
+    components: Array3<f64>,
+    /// Coefficients of each grid, indexed by (grid, column, component).
+    coefficients: Array3<f64>,
+}
+
+impl CompressedGrids{
+    /// This function computes the principal components of the intensity grids.
+    /// ### Arguments:
+    /// * `filenames` - the names of the intensity grid files, in the order of `grids`.
+    /// * `grids` - the contents of the intensity grids, see [crate::IntensityGrid::load_grid].
+    /// * `ncomponents` - the number of principal components kept for each column. At most as many as grids are kept,
+    ///   with one less the grids are reproduced exactly up to rounding.
+    /// ### Returns:
+    /// * `Ok(CompressedGrids)` - the mean intensities, components and coefficients of the grids.
+    /// * `Err(ProfileError::GridFormat)` - if there are no grids, `ncomponents` is zero, or the grids don't share the same wavelengths and µ values.
+    pub fn compress(filenames:Vec<String>, grids:&[ParsedGrid], ncomponents:usize)->Result<Self,ProfileError>{
+        let Some(first) = grids.first() else {
+            return Err(ProfileError::GridFormat(String::from("there are no intensity grids to compress")))
+        };
+        if ncomponents == 0{
+            return Err(ProfileError::GridFormat(String::from("at least one principal component must be kept")))
+        }
+        for (filename,grid) in filenames.iter().zip(grids.iter()).skip(1){
+            if grid.wavelengths != first.wavelengths || grid.mu_values != first.mu_values{
+                return Err(ProfileError::GridFormat(format!("the intensity grids don't share the same wavelengths and µ values, {} differs from {}",
+                    filename, filenames[0])))
+            }
+        }
+
+        let (ngrids, nrows, ncols) = (grids.len(), first.wavelengths.len(), 2*first.mu_values.len());
+        let ncomponents = ncomponents.min(ngrids);
+        let mut means = Array2::<f64>::zeros((ncols, nrows));
+        let mut components = Array3::<f64>::zeros((ncols, ncomponents, nrows));
+        let mut coefficients = Array3::<f64>::zeros((ngrids, ncols, ncomponents));
+        for column in 0..ncols{
+            // The spectra of the column, a column per grid, minus their mean.
+            let mut centered = DMatrix::<f64>::from_fn(nrows, ngrids, |row,grid| grids[grid].values[[row,column]]);
+            for row in 0..nrows{
+                let mean = centered.row(row).mean();
+                means[[column,row]] = mean;
+                centered.row_mut(row).add_scalar_mut(-mean);
+            }
+            // There are less grids than wavelengths, so the components come out of the eigenvectors of the Gram matrix of the grids.
+            let eigen = SymmetricEigen::new(centered.transpose() * &centered);
+            let mut order:Vec<usize> = (0..ngrids).collect();
+            order.sort_by(|a,b| eigen.eigenvalues[*b].total_cmp(&eigen.eigenvalues[*a]));
+            // The eigenvectors of the small eigenvalues only hold a few significant digits, so the components are orthonormalized again
+            // and the coefficients are the projections of the spectra on them.
+            let negligible_norm = NEGLIGIBLE_NORM*centered.norm();
+            let mut basis:Vec<DVector<f64>> = Vec::with_capacity(ncomponents);
+            for n in order{
+                if basis.len() == ncomponents{
+                    break
+                }
+                let mut component = &centered * eigen.eigenvectors.column(n);
+                for _ in 0..2{
+                    for previous in basis.iter(){
+                        component -= previous * previous.dot(&component);
+                    }
+                }
+                let norm = component.norm();
+                if norm > negligible_norm{
+                    basis.push(component / norm);
+                }
+            }
+            for (k,component) in basis.iter().enumerate(){
+                for row in 0..nrows{
+                    components[[column,k,row]] = component[row];
+                }
+                for grid in 0..ngrids{
+                    coefficients[[grid,column,k]] = component.dot(&centered.column(grid));
+                }
+            }
+        }
+        Ok(Self{mu_values: first.mu_values.clone(), wavelengths: first.wavelengths.clone(), filenames, means, components, coefficients})
+    }
+
+    /// Returns the number of principal components of each column.
+    pub fn ncomponents(&self)->usize{
+        self.components.dim().1
+    }
+
+    /// Returns the intensity of a grid at a row and column, see [CompressedGrids].
+    pub fn value(&self, grid:usize, row:usize, column:usize)->f64{
+        self.means[[column,row]] + (0..self.ncomponents())
+            .map(|k| self.coefficients[[grid,column,k]]*self.components[[column,k,row]])
+            .sum::<f64>()
+    }
+
+    /// Returns the intensities of a grid reconstructed out of its coefficients, with the layout of [ParsedGrid::values].
+    pub fn reconstruct(&self, grid:usize)->Array2<f64>{
+        Array2::from_shape_fn((self.wavelengths.len(), self.means.nrows()), |(row,column)| self.value(grid,row,column))
+    }
+
+    /// This function returns the largest difference between the intensities of the grids and the reconstructed ones,
+    /// relative to the largest intensity of each grid. `grids` must be the ones that were compressed, in the same order.
+    pub fn largest_relative_error(&self, grids:&[ParsedGrid])->f64{
+        grids.iter().enumerate()
+            .map(|(n,grid)| {
+                let largest = grid.values.iter().fold(0.0f64, |largest,value| largest.max(value.abs()));
+                let error = (&self.reconstruct(n) - &grid.values).iter().fold(0.0f64, |error,value| error.max(value.abs()));
+                if largest > 0.0 {error/largest} else {error}
+            })
+            .fold(0.0, f64::max)
+    }
+
+    /// Same as [ParsedGrid::rows_within].
+    pub fn rows_within(&self, min_wavelength:f64, max_wavelength:f64)->Range<usize>{
+        let first = self.wavelengths.partition_point(|wavelength| *wavelength < min_wavelength);
+        let last = self.wavelengths.partition_point(|wavelength| *wavelength <= max_wavelength);
+        first.saturating_sub(1)..(last+1).min(self.wavelengths.len())
+    }
+
+    /// This function keeps the given rows of the mean intensities and of the components, and the coefficients of the given grids in their order,
+    /// so that the grids and rows are numbered as the ones of an in-memory grid loaded for the same wavelengths.
+    /// It returns a [ProfileError::GridFormat] if one of the grids was not compressed.
+    pub fn select(&self, filenames:&[&str], rows:Range<usize>)->Result<Self,ProfileError>{
+        let mut coefficients = Array3::<f64>::zeros((filenames.len(), self.means.nrows(), self.ncomponents()));
+        for (n,filename) in filenames.iter().enumerate(){
+            let Some(grid) = self.filenames.iter().position(|compressed| compressed == filename) else {
+                return Err(ProfileError::GridFormat(format!("{} is not one of the compressed intensity grids of {}", filename, COMPRESSED_GRIDS_FILE)))
+            };
+            coefficients.slice_mut(s![n,..,..]).assign(&self.coefficients.slice(s![grid,..,..]));
+        }
+        Ok(Self{
+            mu_values: self.mu_values.clone(),
+            wavelengths: self.wavelengths[rows.clone()].to_vec(),
+            filenames: filenames.iter().map(|filename| filename.to_string()).collect(),
+            means: self.means.slice(s![..,rows.clone()]).to_owned(),
+            components: self.components.slice(s![..,..,rows]).to_owned(),
+            coefficients,
+        })
+    }
+
+    /// This function reads a file of compressed intensity grids, see the layout in the documentation of the module.
+    /// ### Arguments:
+    /// * `path` - the path to the file.
+    /// ### Returns:
+    /// * `Ok(CompressedGrids)` - the contents of the file.
+    /// * `Err(ProfileError::Io)` - if the file could not be read.
+    /// * `Err(ProfileError::GridFormat)` - if the file doesn't hold compressed intensity grids, it's truncated or its µ values are not valid.
+    pub fn read_from(path:&Path)->Result<Self,ProfileError>{
+        let invalid = |reason:String| ProfileError::GridFormat(format!("{} is not a valid file of compressed grids, {}", path.display(), reason));
+        let mut bytes:Vec<u8> = Vec::new();
+        std::fs::File::open(path)?.read_to_end(&mut bytes)?;
+        let mut reader = ByteReader{bytes: &bytes, position: 0};
+        if reader.take(8) != Some(&PCA_MAGIC[..]){
+            return Err(invalid(String::from("the header is missing or corrupted")))
+        }
+        let mut sizes = [0usize;4];
+        for size in sizes.iter_mut(){
+            *size = reader.read_usize().ok_or_else(|| invalid(String::from("the header is missing or corrupted")))?;
+        }
+        let [nrows, nmu, ngrids, ncomponents] = sizes;
+        let truncated = || invalid(String::from("it's truncated"));
+
+        let mu_values = reader.read_f64s(nmu).ok_or_else(truncated)?;
+        check_mu_values(&mu_values).map_err(invalid)?;
+        let wavelengths = reader.read_f64s(nrows).ok_or_else(truncated)?;
+        let mut filenames:Vec<String> = Vec::with_capacity(ngrids);
+        for _ in 0..ngrids{
+            let length = reader.read_usize().ok_or_else(truncated)?;
+            let filename = reader.take(length).ok_or_else(truncated)?;
+            filenames.push(String::from_utf8(filename.to_vec()).map_err(|e| invalid(format!("a file name is not valid UTF-8, {}", e)))?);
+        }
+        let ncols = 2*nmu;
+        let means = reader.read_f64s(ncols*nrows).ok_or_else(truncated)?;
+        let components = reader.read_f64s(ncols*ncomponents*nrows).ok_or_else(truncated)?;
+        let coefficients = reader.read_f64s(ngrids*ncols*ncomponents).ok_or_else(truncated)?;
+        if reader.position != bytes.len(){
+            return Err(invalid(String::from("its size doesn't match its header")))
+        }
+        let shape_error = |e:ndarray::ShapeError| invalid(e.to_string());
+        Ok(Self{
+            mu_values,
+            wavelengths,
+            filenames,
+            means: Array2::from_shape_vec((ncols,nrows), means).map_err(shape_error)?,
+            components: Array3::from_shape_vec((ncols,ncomponents,nrows), components).map_err(shape_error)?,
+            coefficients: Array3::from_shape_vec((ngrids,ncols,ncomponents), coefficients).map_err(shape_error)?,
+        })
+    }
+
+    /// This function writes the compressed grids into a file, see the layout in the documentation of the module.
+    /// It returns a [ProfileError::Io] if the file could not be written.
+    pub fn write_to(&self, path:&Path)->Result<(),ProfileError>{
+        let mut bytes:Vec<u8> = Vec::with_capacity(HEADER_BYTES + 8*(self.mu_values.len() + self.wavelengths.len() + self.means.len() + self.components.len() + self.coefficients.len()));
+        bytes.extend_from_slice(&PCA_MAGIC);
+        for size in [self.wavelengths.len(), self.mu_values.len(), self.filenames.len(), self.ncomponents()]{
+            bytes.extend_from_slice(&(size as u64).to_le_bytes());
+        }
+        let numbers = self.mu_values.iter().chain(self.wavelengths.iter());
+        for number in numbers{
+            bytes.extend_from_slice(&number.to_le_bytes());
+        }
+        for filename in self.filenames.iter(){
+            bytes.extend_from_slice(&(filename.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(filename.as_bytes());
+        }
+        // The iterator of an array follows the logical (row major) order regardless of its memory layout.
+        for number in self.means.iter().chain(self.components.iter()).chain(self.coefficients.iter()){
+            bytes.extend_from_slice(&number.to_le_bytes());
+        }
+        std::fs::File::create(path)?.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+/// Reads the numbers of a file of compressed grids in order, every method returns [None] if the file ends before.
+struct ByteReader<'a>{
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a>{
+    fn take(&mut self, length:usize)->Option<&'a [u8]>{
+        let end = self.position.checked_add(length)?;
+        let taken = self.bytes.get(self.position..end)?;
+        self.position = end;
+        Some(taken)
+    }
+
+    fn read_usize(&mut self)->Option<usize>{
+        usize::try_from(u64::from_le_bytes(self.take(8)?.try_into().ok()?)).ok()
+    }
+
+    fn read_f64s(&mut self, count:usize)->Option<Vec<f64>>{
+        let taken = self.take(count.checked_mul(8)?)?;
+        Some(taken.chunks_exact(8).map(|chunk| f64::from_le_bytes(chunk.try_into().expect("chunks of eight bytes"))).collect())
+    }
+}
+
+impl ProfileConfig{
+    /// Returns the path of the file of compressed intensity grids, see [COMPRESSED_GRIDS_FILE].
+    pub fn compressed_grids_path(&self)->PathBuf{
+        PathBuf::from(format!("{}{}", self.path_to_grids, COMPRESSED_GRIDS_FILE))
+    }
+
+    /// This function compresses all of the intensity grids of the configuration (see [CompressedGrids::compress]) and writes them
+    /// into [ProfileConfig::compressed_grids_path]. The grids are read from their binary caches if they're up to date.
+    /// ### Arguments:
+    /// * `ncomponents` - the number of principal components kept for each column.
+    /// ### Returns:
+    /// * `Ok((PathBuf,f64))` - the path of the file written and the largest relative error of the reconstructed grids,
+    ///   see [CompressedGrids::largest_relative_error].
+    /// * `Err(ProfileError)` - if a grid could not be loaded, the grids could not be compressed or the file could not be written.
+    pub fn write_compressed_grids(&self, ncomponents:usize)->Result<(PathBuf,f64),ProfileError>{
+        let mut filenames:Vec<String> = Vec::with_capacity(self.intensity_grids.len());
+        let mut grids:Vec<ParsedGrid> = Vec::with_capacity(self.intensity_grids.len());
+        for grid in self.intensity_grids.iter().filter(|grid| grid.is_grid()){
+            grids.push(grid.load_grid(&self.path_to_grids, &self.mu_values)?);
+            filenames.push(grid.filename().to_string());
+        }
+        let compressed = CompressedGrids::compress(filenames, &grids, ncomponents)?;
+        let path = self.compressed_grids_path();
+        compressed.write_to(&path)?;
+        Ok((path, compressed.largest_relative_error(&grids)))
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{GridBackend, GridLattice, OutOfBoundsPolicy, ProfileConfig, SpectralGrid, MIN_VISIBLE_COSCHI, extremal_val_from_col};
+use crate::error::ProfileError;
+use crate::utils::pca_grid::COMPRESSED_GRIDS_FILE;
+use polars::prelude::*;
+use pulstar::PulstarConfig;
+use pulstar::validation::{ParameterBox, ValidationReport};
+use temp_name_lib::type_def::N_FLUX_POINTS;
+
+/// Spacing in K used to suggest new grid nodes when the loaded grids have a single temperature.
+const DEFAULT_TEMPERATURE_SPACING: f64 = 1000.0;
+
+/// Spacing in dex used to suggest new grid nodes when the loaded grids have a single log g.
+const DEFAULT_LOG_GRAVITY_SPACING: f64 = 0.5;
+
+impl ProfileConfig{
+    /// This function returns the box on the (T_eff, log g) plane covered by the intensity grids.
+    /// ### Returns:
+    /// * `Some(ParameterBox)` - with the minimum and maximum temperature and log g of the grids.
+    /// * `None` - if there are no intensity grids, or the atmosphere is analytic or an emulator.
+    pub fn grid_parameter_box(&self)->Option<ParameterBox>{
+        if self.intensity_grids.iter().any(|grid| !grid.is_grid()){
+            return None
+        }
+        let first = self.intensity_grids.first()?;
+        let mut grid_box = ParameterBox{
+            temperature: [first.temperature();2],
+            log_gravity: [first.log_gravity();2],
+        };
+        for grid in self.intensity_grids.iter(){
+            grid_box.temperature[0] = grid_box.temperature[0].min(grid.temperature());
+            grid_box.temperature[1] = grid_box.temperature[1].max(grid.temperature());
+            grid_box.log_gravity[0] = grid_box.log_gravity[0].min(grid.log_gravity());
+            grid_box.log_gravity[1] = grid_box.log_gravity[1].max(grid.log_gravity());
+        }
+        Some(grid_box)
+    }
+
+    /// This function checks the configuration before any grid is loaded and reports every problem found.
+    ///
+    /// The checks are:
+    /// * the wavelength range starts before it ends, has a positive step and less than [N_FLUX_POINTS] points.
+    /// * the intensity grids form a [GridLattice] (see [GridLattice::from_intensity_grids]) and all of them are present in `path_to_grids`,
+    ///   or the file of compressed grids with [GridBackend::Compressed]. It's only a warning if some of the nodes of the lattice are missing,
+    ///   or there's a single analytic atmosphere with valid parameters (see [crate::analytic::AnalyticAtmosphere::check]),
+    ///   or a single emulator.
+    /// * the µ values of the intensity grids are valid, see [check_mu_values].
+    /// ### Returns:
+    /// * A [ValidationReport] with all of the errors and warnings found.
+    pub fn validate(&self)->ValidationReport{
+        let mut report = ValidationReport::new();
+
+        let range = &self.wavelength_range;
+        if range.start >= range.end{
+            report.error(format!("the wavelength range must start before it ends, it goes from {} to {} nm", range.start, range.end));
+        }
+        if range.step <= 0.0{
+            report.error(format!("the wavelength step must be positive, it is {} nm", range.step));
+        }
+        else if range.start < range.end{
+            let npoints = ((range.end - range.start)/range.step).floor() as usize + 1usize;
+            if npoints >= N_FLUX_POINTS as usize{
+                report.error(format!("{} wavelengths requested, they must be less than {}", npoints, N_FLUX_POINTS));
+            }
+        }
+
+        if let Some(spectrum_cache) = &self.spectrum_cache{
+            for problem in spectrum_cache.check(){
+                report.error(format!("spectrum_cache: {}", problem));
+            }
+        }
+
+        match (self.analytic_atmosphere(), self.emulator_model()){
+            (Some(atmosphere), _) => {
+                if self.intensity_grids.len() > 1{
+                    report.error(String::from("an analytic atmosphere can't be combined with other intensity grids"));
+                }
+                for problem in atmosphere.check(){
+                    report.error(problem);
+                }
+            }
+            (None, Some(_)) => {
+                if self.intensity_grids.len() > 1{
+                    report.error(String::from("an emulator can't be combined with other intensity grids"));
+                }
+            }
+            (None, None) => {
+                match GridLattice::build(&self.intensity_grids){
+                    Err(message) => {report.error(message)}
+                    Ok(lattice) if !lattice.is_complete() => {report.warning(String::from(
+                        "the intensity grids don't form a complete lattice, they're interpolated within the triangles of their nodes"))}
+                    Ok(_) => {}
+                }
+                if let Err(message) = check_mu_values(&self.mu_values){
+                    report.error(format!("mu_values: {}", message));
+                }
+            }
+        }
+        if self.grid_backend == GridBackend::Compressed && self.intensity_grids.iter().any(|grid| grid.is_grid()){
+            if !self.compressed_grids_path().is_file(){
+                report.error(format!("the compressed intensity grids '{}' are not in '{}', they're written by `profile compress`",
+                    COMPRESSED_GRIDS_FILE, self.path_to_grids));
+            }
+            return report
+        }
+        for grid in self.intensity_grids.iter().filter(|grid| grid.needs_file()){
+            if grid.is_there_a_file(&self.path_to_grids).is_err(){
+                report.error(format!("intensity grid '{}' not found in '{}'", grid.filename(), self.path_to_grids));
+            }
+        }
+        report
+    }
+}
+
+/// This function checks that the µ values of the intensity grids can be used for the interpolation:
+/// there must be at least two of them, all of them different and within (0, 1].
+/// It returns the description of the problem found otherwise.
+pub fn check_mu_values(mu_values:&[f64])->Result<(),String>{
+    if mu_values.len() < 2{
+        return Err(format!("at least two µ values are needed, {} given", mu_values.len()))
+    }
+    if let Some(mu) = mu_values.iter().find(|mu| !(**mu > 0.0 && **mu <= 1.0)){
+        return Err(format!("the µ values must be within (0, 1], {} given", mu))
+    }
+    let mut sorted = mu_values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]){
+        return Err(format!("the µ values must be different, {} is repeated", pair[0]))
+    }
+    Ok(())
+}
+
+/// This function validates a complete run: the pulstar configuration, the profile configuration,
+/// and whether the intensity grids cover the temperatures, surface gravities and metallicity the star will reach.
+/// The latter is only a warning if the configuration sets an `out_of_bounds` policy other than [OutOfBoundsPolicy::Error].
+///
+/// ### Arguments:
+/// * `profile_config` - the configuration of the profile program.
+/// * `pulstar_config` - the configuration used to rasterize the star.
+/// ### Returns:
+/// * A [ValidationReport] with all of the errors and warnings found.
+pub fn validate_run(profile_config:&ProfileConfig, pulstar_config:&PulstarConfig)->ValidationReport{
+    let mut report = pulstar_config.validate();
+    let star_is_valid = report.is_valid();
+    report.append(profile_config.validate());
+
+    if let (true, Some(grid_box)) = (star_is_valid, profile_config.grid_parameter_box()){
+        let star_box = pulstar_config.perturbed_parameter_box();
+        if !grid_box.contains(&star_box){
+            report_out_of_bounds(&mut report, profile_config.out_of_bounds, format!("the intensity grids cover {}, but the star might reach {}", grid_box, star_box));
+        }
+    }
+
+    if let Ok(lattice) = GridLattice::build(&profile_config.intensity_grids){
+        let metallicities = lattice.metallicities();
+        match (lattice.interpolates_metallicity(), pulstar_config.star_data.metallicity){
+            (true, None) => {
+                report.error(format!("the intensity grids have metallicities from {:.2} to {:.2}, but the star doesn't have one",
+                    metallicities[0], metallicities[metallicities.len()-1]));
+            }
+            (true, Some(metallicity)) => {
+                if !lattice.contains_metallicity(Some(metallicity)){
+                    report_out_of_bounds(&mut report, profile_config.out_of_bounds, format!("the intensity grids have metallicities from {:.2} to {:.2}, but the star has {:.2}",
+                        metallicities[0], metallicities[metallicities.len()-1], metallicity));
+                }
+            }
+            (false, Some(metallicity)) => {
+                match metallicities.first(){
+                    None => {report.warning(format!("the intensity grids don't have a metallicity, the metallicity of the star ({:.2}) is not used",
+                        metallicity))}
+                    Some(grid_metallicity) if *grid_metallicity != metallicity => {report.warning(format!(
+                        "the intensity grids only have the metallicity {:.2}, the metallicity of the star ({:.2}) is not used",
+                        grid_metallicity, metallicity))}
+                    Some(_) => {}
+                }
+            }
+            (false, None) => {}
+        }
+    }
+    report
+}
+
+/// This function scans a rasterized star and returns the range of temperatures and surface gravities
+/// over the visible cells (those that are integrated by profile) of all of the time points.
+/// ### Arguments:
+/// * `star_lf` - a [LazyFrame] with the output of pulstar.
+/// ### Returns:
+/// * `Ok(ParameterBox)` - the minimum and maximum temperature and log g of the star.
+/// * `Err(PolarsError)` - if the columns are missing or there are no visible cells.
+pub fn star_parameter_box(star_lf:LazyFrame)->PolarsResult<ParameterBox>{
+    let visible_lf = star_lf.filter(col("coschi").gt(lit(MIN_VISIBLE_COSCHI)));
+    Ok(ParameterBox{
+        temperature: [extremal_val_from_col("temperature", visible_lf.clone(), false)?,
+            extremal_val_from_col("temperature", visible_lf.clone(), true)?],
+        log_gravity: [extremal_val_from_col("log gravity", visible_lf.clone(), false)?,
+            extremal_val_from_col("log gravity", visible_lf, true)?],
+    })
+}
+
+/// Adds a problem of coverage of the intensity grids to the report, as an error or as a warning depending on the `out_of_bounds` policy.
+fn report_out_of_bounds(report:&mut ValidationReport, policy:OutOfBoundsPolicy, message:String){
+    match policy{
+        OutOfBoundsPolicy::Error => {report.error(message)}
+        policy => {report.warning(format!("{}, those cells are treated with the {:?} policy", message, policy))}
+    }
+}
+
+/// This function returns the range of metallicities of the visible cells of a rasterized star.
+/// ### Arguments:
+/// * `star_lf` - a [LazyFrame] with the output of pulstar.
+/// ### Returns:
+/// * `Ok(Some([min,max]))` - the minimum and maximum metallicity [M/H] of the star.
+/// * `Ok(None)` - if the star doesn't have a `metallicity` column, i.e. it wasn't given to pulstar.
+/// * `Err(PolarsError)` - if the star could not be read or there are no visible cells.
+pub fn star_metallicity_range(mut star_lf:LazyFrame)->PolarsResult<Option<[f64;2]>>{
+    if !star_lf.collect_schema()?.contains("metallicity"){
+        return Ok(None)
+    }
+    let visible_lf = star_lf.filter(col("coschi").gt(lit(MIN_VISIBLE_COSCHI)));
+    Ok(Some([extremal_val_from_col("metallicity", visible_lf.clone(), false)?,
+        extremal_val_from_col("metallicity", visible_lf, true)?]))
+}
+
+/// This function suggests the box the intensity grids should cover so that the star lies within them.
+///
+/// The current box is extended outwards in steps of its own size along each axis, so the suggested nodes lie on the same lattice
+/// as the loaded grids. If the loaded grids have a single temperature or log g, the steps are 1000 K and 0.5 dex respectively.
+pub fn suggest_grid_box(star_box:&ParameterBox, grid_box:&ParameterBox)->ParameterBox{
+    let extend = |grid:[f64;2], star:[f64;2], default_spacing:f64|->[f64;2]{
+        let spacing = if grid[1] > grid[0] {grid[1] - grid[0]} else {default_spacing};
+        let mut extended = grid;
+        if star[0] < grid[0]{
+            extended[0] = grid[0] - ((grid[0] - star[0])/spacing).ceil() * spacing;
+        }
+        if star[1] > grid[1]{
+            extended[1] = grid[1] + ((star[1] - grid[1])/spacing).ceil() * spacing;
+        }
+        extended
+    };
+    ParameterBox{
+        temperature: extend(grid_box.temperature, star_box.temperature, DEFAULT_TEMPERATURE_SPACING),
+        log_gravity: extend(grid_box.log_gravity, star_box.log_gravity, DEFAULT_LOG_GRAVITY_SPACING),
+    }
+}
+
+impl SpectralGrid{
+    /// Returns the box on the (T_eff, log g) plane used for the interpolation of the specific intensities.
+    pub fn parameter_box(&self)->ParameterBox{
+        self.lattice.parameter_box()
+    }
+
+    /// This function checks that the star lies within the loaded intensity grids before integrating the fluxes.
+    /// Only the box covered by the nodes is checked, if some nodes of the [GridLattice] are missing the cells that fall
+    /// in the holes are treated with the `out_of_bounds` policy while integrating.
+    /// ### Arguments:
+    /// * `star_box` - the range of the star, see [star_parameter_box].
+    /// ### Returns:
+    /// * `Ok(())` - if the star is covered by the grids.
+    /// * `Err(ProfileError::GridCoverage)` - otherwise, its message suggests the grids to add.
+    pub fn check_coverage(&self, star_box:&ParameterBox)->Result<(),ProfileError>{
+        let grid_box = self.parameter_box();
+        if grid_box.contains(star_box){
+            Ok(())
+        }
+        else{
+            Err(ProfileError::GridCoverage{star: *star_box, grids: grid_box})
+        }
+    }
+
+    /// This function checks that the metallicity of the star lies within the loaded intensity grids before integrating the fluxes.
+    /// It's only checked if the grids are interpolated in metallicity.
+    /// ### Arguments:
+    /// * `star_metallicity` - the range of metallicities of the star, see [star_metallicity_range].
+    /// ### Returns:
+    /// * `Ok(())` - if the star is covered by the grids.
+    /// * `Err(ProfileError::InvalidConfig)` - if the grids are interpolated in metallicity but the star doesn't have one.
+    /// * `Err(ProfileError::MetallicityCoverage)` - if the metallicity of the star is outside of the grids.
+    pub fn check_metallicity_coverage(&self, star_metallicity:Option<[f64;2]>)->Result<(),ProfileError>{
+        if !self.lattice.interpolates_metallicity(){
+            return Ok(())
+        }
+        let metallicities = self.lattice.metallicities();
+        let grids = [metallicities[0], metallicities[metallicities.len()-1]];
+        match star_metallicity{
+            None => {Err(ProfileError::InvalidConfig(format!(
+                "the intensity grids have metallicities from {:.2} to {:.2}, but the star doesn't have one. Set the metallicity in the star_data of pulstar",
+                grids[0], grids[1])))}
+            Some(star) if grids[0] <= star[0] && star[1] <= grids[1] => {Ok(())}
+            Some(star) => {Err(ProfileError::MetallicityCoverage{star, grids})}
+        }
+    }
+}
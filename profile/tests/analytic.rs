@@ -0,0 +1,142 @@
+//! Tests for the analytic atmospheres, which don't need intensity grid files.
+use polars::prelude::*;
+use profile::analytic::{CoefficientTable, LimbDarkeningLaw, LineProfile, LineStrength, SpectralLine, planck};
+use profile::error::ProfileError;
+use profile::profile_mkr::{profile_main, profile_output};
+use temp_name_lib::interpolation::BoundsHandling;
+
+const LINE_CENTER: f64 = 413.95;
+
+/// A static star (no velocities) seen pole-on, with cells at different µ values and temperatures.
+fn static_star() -> DataFrame {
+    let coschi = vec![1.0, 0.9, 0.7, 0.5, 0.3, 0.1];
+    let ncells = coschi.len();
+    df!(
+        "time" => vec![0.0; ncells],
+        "velocity" => vec![0.0; ncells],
+        "area" => vec![0.2, 0.3, 0.4, 0.3, 0.2, 0.1],
+        "coschi" => coschi,
+        "temperature" => vec![22000.0, 21000.0, 20500.0, 20000.0, 19500.0, 19000.0],
+        "log gravity" => vec![4.0; ncells],
+    )
+    .unwrap()
+}
+
+fn profile_input(atmosphere: &str) -> String {
+    format!(
+        "path_to_grids = \"{}/\"\n\n[wavelength_range]\nstart = 413.85\nend = 414.05\nstep = 0.01\n\n[[intensity_grids]]\nAnalytic = {}\n",
+        std::env::temp_dir().display(),
+        atmosphere
+    )
+}
+
+fn column(df: &DataFrame, name: &str) -> Vec<f64> {
+    df.sort(["time", "wave length"], SortMultipleOptions::default())
+        .unwrap()
+        .column(name)
+        .unwrap()
+        .f64()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[test]
+fn limb_darkening_laws_are_normalized_at_the_centre_of_the_disk() {
+    let laws = [
+        (LimbDarkeningLaw::Linear, vec![0.6]),
+        (LimbDarkeningLaw::Quadratic, vec![0.3, 0.2]),
+        (LimbDarkeningLaw::SquareRoot, vec![0.1, 0.5]),
+        (LimbDarkeningLaw::Claret, vec![0.5, -0.2, 0.6, -0.2]),
+    ];
+    for (law, coefficients) in laws.iter() {
+        assert_eq!(coefficients.len(), law.ncoefficients());
+        assert!((law.evaluate(coefficients, 1.0) - 1.0).abs() < 1.0e-15, "{:?}", law);
+    }
+    assert!((LimbDarkeningLaw::Linear.evaluate(&[0.6], 0.0) - 0.4).abs() < 1.0e-15);
+    assert!((LimbDarkeningLaw::Quadratic.evaluate(&[0.3, 0.2], 0.5) - (1.0 - 0.15 - 0.05)).abs() < 1.0e-15);
+    assert!((LimbDarkeningLaw::SquareRoot.evaluate(&[0.1, 0.5], 0.25) - (1.0 - 0.075 - 0.25)).abs() < 1.0e-15);
+    assert!((LimbDarkeningLaw::Claret.evaluate(&[0.5, -0.2, 0.6, -0.2], 0.0) - 0.3).abs() < 1.0e-15);
+}
+
+#[test]
+fn equivalent_width_sets_the_depth_of_the_line() {
+    for profile in [LineProfile::Gaussian { sigma: 0.01 }, LineProfile::Voigt { sigma: 0.008, gamma: 0.004 }] {
+        // The profile is normalized to unit area.
+        let step = 1.0e-4;
+        let area: f64 = (-200_000..=200_000).map(|k| profile.evaluate(k as f64 * step) * step).sum();
+        assert!((area - 1.0).abs() < 2.0e-2, "{:?} has area {}", profile, area);
+
+        let line = SpectralLine { center: LINE_CENTER, profile, strength: LineStrength::EquivalentWidth(0.01) };
+        let equivalent_width: f64 = (-200_000..=200_000).map(|k| line.absorption(LINE_CENTER + k as f64 * step) * step).sum();
+        assert!((equivalent_width - 0.01).abs() < 2.0e-4, "{:?} has an equivalent width of {}", profile, equivalent_width);
+        assert!((line.absorption(LINE_CENTER) - line.depth()).abs() < 1.0e-15);
+    }
+}
+
+#[test]
+fn static_star_has_the_intrinsic_line_profile() {
+    let input = profile_input(
+        "{law = \"Quadratic\", coefficients = {Fixed = [0.3, 0.2]}, line = {center = 413.95, profile = {Gaussian = {sigma = 0.02}}, strength = {Depth = 0.6}}}",
+    );
+    let df = profile_main(&input, static_star()).unwrap();
+    let line = SpectralLine { center: LINE_CENTER, profile: LineProfile::Gaussian { sigma: 0.02 }, strength: LineStrength::Depth(0.6) };
+    for (wavelength, normalized) in column(&df, "wave length").iter().zip(column(&df, "normalized flux").iter()) {
+        assert!((normalized - (1.0 - line.absorption(*wavelength))).abs() < 1.0e-12, "{} nm: {}", wavelength, normalized);
+    }
+
+    // The continuum is the limb-darkened Planck function.
+    let star = static_star();
+    let expected: f64 = (0..star.height())
+        .map(|n| {
+            let value = |name: &str| star.column(name).unwrap().f64().unwrap().get(n).unwrap();
+            let mu = value("coschi");
+            value("area") * planck(413.85, value("temperature")) * LimbDarkeningLaw::Quadratic.evaluate(&[0.3, 0.2], mu)
+        })
+        .sum();
+    let continuum = column(&df, "continuum")[0];
+    assert!((continuum - expected).abs() < 1.0e-12 * expected);
+}
+
+#[test]
+fn coefficients_are_interpolated_from_a_table() {
+    let path = std::env::temp_dir().join(format!("profile_ld_table_{}.txt", std::process::id()));
+    std::fs::write(&path, "# T_eff log_g u\n19000 3.5 0.40\n19000 4.5 0.50\n23000 3.5 0.30\n23000 4.5 0.20\n").unwrap();
+    let table = CoefficientTable::from_file(path.to_str().unwrap(), 1).unwrap();
+    assert_eq!(table.coefficients(21000.0, 4.0, BoundsHandling::Error).unwrap(), vec![0.35]);
+    assert_eq!(table.coefficients(19000.0, 3.5, BoundsHandling::Error).unwrap(), vec![0.40]);
+    assert!(table.coefficients(24000.0, 4.0, BoundsHandling::Error).is_err());
+    assert_eq!(table.coefficients(24000.0, 4.5, BoundsHandling::Clamp).unwrap(), vec![0.20]);
+    assert!(CoefficientTable::from_file(path.to_str().unwrap(), 2).is_err());
+
+    // The run reads the table from the grid directory.
+    let atmosphere = format!(
+        "{{law = \"Linear\", coefficients = {{Table = \"{}\"}}, line = {{center = 413.95, profile = {{Voigt = {{sigma = 0.01, gamma = 0.005}}}}, strength = {{EquivalentWidth = 0.01}}}}}}",
+        path.file_name().unwrap().to_str().unwrap()
+    );
+    let output = profile_output(&profile_input(&atmosphere), static_star()).unwrap();
+    assert!(output.out_of_bounds_cells.column("out of bounds cells").unwrap().u32().unwrap().into_iter().flatten().all(|count| count == 0));
+    let normalized = column(&output.data_frame, "normalized flux");
+    assert!(normalized[10] < normalized[0] && normalized[0] < 1.0);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn invalid_atmospheres_are_rejected() {
+    let too_deep = profile_input(
+        "{law = \"Linear\", coefficients = {Fixed = [0.6]}, line = {center = 413.95, profile = {Gaussian = {sigma = 0.001}}, strength = {EquivalentWidth = 0.01}}}",
+    );
+    assert!(matches!(profile_main(&too_deep, static_star()), Err(ProfileError::Validation(_))));
+
+    let missing_coefficient = profile_input(
+        "{law = \"Claret\", coefficients = {Fixed = [0.5, 0.1, 0.1]}, line = {center = 413.95, profile = {Gaussian = {sigma = 0.01}}, strength = {Depth = 0.5}}}",
+    );
+    assert!(matches!(profile_main(&missing_coefficient, static_star()), Err(ProfileError::Validation(_))));
+
+    let combined = profile_input(
+        "{law = \"Linear\", coefficients = {Fixed = [0.6]}, line = {center = 413.95, profile = {Gaussian = {sigma = 0.01}}, strength = {Depth = 0.5}}}",
+    ) + "[[intensity_grids]]\nJoris = {temperature = 20000.0, log_gravity = 3.5, filename = \"t20000g35.txt\"}\n";
+    assert!(matches!(profile_main(&combined, static_star()), Err(ProfileError::Validation(_))));
+}
@@ -0,0 +1,61 @@
+//! Tests for the catalogue of the intensity grids built out of their file names, using the golden fixtures.
+mod common;
+
+use common::star;
+use polars::prelude::*;
+use profile::catalogue::{load_catalogue, scan_grid_directory, CATALOGUE_FILENAME};
+use profile::profile_mkr::profile_main;
+use profile::IntensityGrid;
+use std::path::Path;
+
+/// The golden profile input with the grids in `grids_dir`, without the list of intensity grids if `list_grids` is `false`.
+fn profile_input(grids_dir: &Path, list_grids: bool) -> String {
+    let input = common::profile_input(grids_dir);
+    match list_grids {
+        true => input,
+        false => input.split("[[intensity_grids]]").next().unwrap().to_string(),
+    }
+}
+
+/// Copies the golden grids into `dir`, together with a file that is not a grid.
+fn copy_golden_grids(dir: &Path) {
+    common::copy_golden_grids(dir);
+    std::fs::write(dir.join("README.txt"), "not an intensity grid\n").unwrap();
+}
+
+fn sorted(df: DataFrame) -> DataFrame {
+    df.sort(["time", "wave length"], SortMultipleOptions::default()).unwrap()
+}
+
+#[test]
+fn grids_are_recognized_by_their_file_names() {
+    assert_eq!(
+        IntensityGrid::from_filename("lp00000_21000_03500_0200..imu"),
+        Some(IntensityGrid::Nadya { temperature: 21000.0, log_gravity: 3.5, metalicity: 0.02, filename: "lp00000_21000_03500_0200..imu".to_string() })
+    );
+    assert_eq!(
+        IntensityGrid::from_filename("t21000g35.txt"),
+        Some(IntensityGrid::Joris { temperature: 21000.0, log_gravity: 3.5, filename: "t21000g35.txt".to_string() })
+    );
+    for filename in ["t21000g35.csv", "t21000.txt", "tabcg35.txt", "lp00000_21000_03500.imu", "README.md", CATALOGUE_FILENAME] {
+        assert_eq!(IntensityGrid::from_filename(filename), None, "{}", filename);
+    }
+}
+
+#[test]
+fn directory_only_configuration_matches_the_listed_grids() {
+    let dir = std::env::temp_dir().join(format!("profile_catalogue_{}", std::process::id()));
+    copy_golden_grids(&dir);
+
+    let listed = sorted(profile_main(&profile_input(&dir, true), star()).unwrap());
+    let catalogued = sorted(profile_main(&profile_input(&dir, false), star()).unwrap());
+    assert!(listed.equals(&catalogued));
+
+    // The run writes the catalogue, which is then read back.
+    assert!(dir.join(CATALOGUE_FILENAME).exists());
+    let scanned = scan_grid_directory(dir.to_str().unwrap()).unwrap();
+    assert_eq!(scanned.len(), 4);
+    assert_eq!(load_catalogue(dir.to_str().unwrap()).unwrap(), scanned);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
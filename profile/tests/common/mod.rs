@@ -0,0 +1,51 @@
+//! Fixtures shared by the integration tests of profile.
+//!
+//! Every test file declares `mod common;` and uses the part it needs.
+#![allow(dead_code)]
+use polars::prelude::*;
+use pulstar::pulstar_mkr::pulstar_main;
+use std::path::{Path, PathBuf};
+
+/// The directory of the test fixtures, `tests/fixtures/`.
+pub fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+}
+
+/// The directory of the golden fixture, with `profile_input.toml` and the intensity grids in `grids/`.
+pub fn fixture_dir() -> PathBuf {
+    fixtures_dir().join("golden")
+}
+
+/// The pulstar input of the golden star, `../pulstar/tests/fixtures/golden/pulstar_input.toml`.
+pub fn pulstar_input() -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("pulstar")
+        .join("tests")
+        .join("fixtures")
+        .join("golden")
+        .join("pulstar_input.toml");
+    std::fs::read_to_string(path).unwrap()
+}
+
+/// The rasterized golden star.
+pub fn star() -> DataFrame {
+    pulstar_main(&pulstar_input()).unwrap()
+}
+
+/// The golden profile input with the intensity grids in `grids_dir`.
+pub fn profile_input(grids_dir: &Path) -> String {
+    std::fs::read_to_string(fixture_dir().join("profile_input.toml"))
+        .unwrap()
+        .replace("{path_to_grids}", &format!("{}/", grids_dir.display()))
+}
+
+/// Copies the golden grids into `dir` and returns the golden profile input that uses them.
+pub fn copy_golden_grids(dir: &Path) -> String {
+    std::fs::create_dir_all(dir).unwrap();
+    for entry in std::fs::read_dir(fixture_dir().join("grids")).unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+    }
+    profile_input(dir)
+}
@@ -0,0 +1,132 @@
+//! Tests for the [Emulator] intensity provider, using the tiny model of the fixtures whose intensities have a closed form.
+mod common;
+
+use common::{fixtures_dir, star};
+use profile::emulator::Emulator;
+use profile::error::ProfileError;
+use profile::profile_mkr::profile_output;
+use profile::provider::{IntensityProvider, LocalAtmosphere};
+use profile::ProfileConfig;
+use temp_name_lib::interpolation::BoundsHandling;
+use std::path::PathBuf;
+
+fn emulator_dir() -> PathBuf {
+    fixtures_dir().join("emulator")
+}
+
+fn emulator() -> Emulator {
+    Emulator::from_file(emulator_dir().join("emulator.toml").to_str().unwrap()).unwrap()
+}
+
+/// The continuum and specific intensities of the fixture model, see the description at the top of its file.
+fn closed_form(atmosphere: &LocalAtmosphere, wavelength: f64) -> (f64, f64) {
+    let t = (atmosphere.temperature - 22000.0) / 2000.0;
+    let g = (atmosphere.log_gravity - 4.0) / 0.5;
+    let m = (atmosphere.mu - 0.5) / 0.5;
+    let (h1, h2) = ((0.5 * t + 0.8 * m).tanh(), (0.3 * g).tanh());
+    let (z1, z2) = (1.0 + 0.4 * h1 + 0.1 * h2, 0.5 + 0.2 * h1);
+    let line = (-0.5 * ((wavelength - 413.95) / 0.01).powi(2)).exp();
+    (z1 - 0.5 * z2 * line, z1)
+}
+
+fn evaluate(emulator: &Emulator, atmosphere: &LocalAtmosphere, wavelengths: &[f64], bounds_handling: BoundsHandling) -> Result<(Vec<f64>, Vec<f64>), ()> {
+    let mut scratch = emulator.new_scratch();
+    let (mut specific, mut continuum) = (vec![0.0; wavelengths.len()], vec![0.0; wavelengths.len()]);
+    emulator
+        .intensities(atmosphere, wavelengths, bounds_handling, &mut scratch, &mut specific, &mut continuum)
+        .map_err(|_| ())?;
+    Ok((specific, continuum))
+}
+
+#[test]
+fn bundled_model_reproduces_its_closed_form() {
+    let emulator = emulator();
+    assert_eq!(emulator.wavelengths().len(), 31);
+    let wavelengths: Vec<f64> = emulator.wavelengths().to_vec();
+    for (temperature, log_gravity, mu) in [(22000.0, 4.0, 0.5), (19500.0, 3.2, 0.05), (25000.0, 4.8, 1.0)] {
+        let atmosphere = LocalAtmosphere { temperature, log_gravity, metallicity: None, mu };
+        assert!(emulator.covers(&atmosphere));
+        let (specific, continuum) = evaluate(&emulator, &atmosphere, &wavelengths, BoundsHandling::Error).unwrap();
+        for (n, wavelength) in wavelengths.iter().enumerate() {
+            let (expected_specific, expected_continuum) = closed_form(&atmosphere, *wavelength);
+            assert!((specific[n] - expected_specific).abs() < 1.0e-9, "{}: {} is not {}", wavelength, specific[n], expected_specific);
+            assert!((continuum[n] - expected_continuum).abs() < 1.0e-12);
+        }
+
+        // Between the wavelengths of the emulator the intensities are interpolated linearly.
+        let (between, _) = evaluate(&emulator, &atmosphere, &[413.945], BoundsHandling::Error).unwrap();
+        assert!((between[0] - 0.5 * (specific[14] + specific[15])).abs() < 1.0e-12);
+    }
+
+    let atmosphere = LocalAtmosphere { temperature: 22000.0, log_gravity: 4.0, metallicity: None, mu: 0.5 };
+    assert!(evaluate(&emulator, &atmosphere, &[414.11], BoundsHandling::Extrapolate).is_err());
+}
+
+#[test]
+fn atmospheres_outside_of_the_training_domain() {
+    let emulator = emulator();
+    let wavelengths = [413.93, 413.95];
+    let hot = LocalAtmosphere { temperature: 28000.0, log_gravity: 4.0, metallicity: None, mu: 0.7 };
+    assert!(!emulator.covers(&hot));
+    assert!(evaluate(&emulator, &hot, &wavelengths, BoundsHandling::Error).is_err());
+
+    // Clamping evaluates the network at the edge of the domain, extrapolating evaluates it as is.
+    let edge = LocalAtmosphere { temperature: 26000.0, ..hot };
+    assert_eq!(evaluate(&emulator, &hot, &wavelengths, BoundsHandling::Clamp), evaluate(&emulator, &edge, &wavelengths, BoundsHandling::Error));
+    let (specific, continuum) = evaluate(&emulator, &hot, &wavelengths, BoundsHandling::Extrapolate).unwrap();
+    for (n, wavelength) in wavelengths.iter().enumerate() {
+        let (expected_specific, expected_continuum) = closed_form(&hot, *wavelength);
+        assert!((specific[n] - expected_specific).abs() < 1.0e-9);
+        assert!((continuum[n] - expected_continuum).abs() < 1.0e-12);
+    }
+}
+
+#[test]
+fn invalid_models_are_rejected() {
+    let dir = std::env::temp_dir().join(format!("profile_emulator_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let contents = std::fs::read_to_string(emulator_dir().join("emulator.toml")).unwrap();
+    let broken = [
+        contents.replace("offset = [22000.0, 4.0, 0.5]", "offset = [22000.0, 4.0]"),
+        contents.replace("scale = [2000.0, 0.5, 0.5]", "scale = [2000.0, 0.0, 0.5]"),
+        contents.replace("weights = [[0.4, 0.1], [0.2, 0.0]]", "weights = [[0.4, 0.1, 0.0], [0.2, 0.0, 0.0]]"),
+        contents.replace("explained_variance = [1.0, 0.25]", "explained_variance = [1.0]"),
+        contents.replace("activation = \"Tanh\"", "activation = \"Softplus\""),
+        contents.replace("mean = [0.0, ", "mean = ["),
+    ];
+    for (n, contents) in broken.iter().enumerate() {
+        let path = dir.join(format!("broken_{}.toml", n));
+        std::fs::write(&path, contents).unwrap();
+        assert!(matches!(Emulator::from_file(path.to_str().unwrap()), Err(ProfileError::GridFormat(_))), "model {}", n);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// The profile input with the emulator of the fixtures.
+fn emulator_input() -> String {
+    format!(
+        "path_to_grids = \"{}/\"\n\n[wavelength_range]\nstart = 413.92\nend = 413.98\nstep = 0.005\n\n[[intensity_grids]]\nEmulator = {{model = \"emulator.toml\"}}\n",
+        emulator_dir().display()
+    )
+}
+
+#[test]
+fn profile_runs_with_the_emulator() {
+    let config: ProfileConfig = toml::from_str(&emulator_input()).unwrap();
+    assert_eq!(config.emulator_model(), Some("emulator.toml"));
+    assert!(config.validate().is_valid());
+    assert!(config.grid_parameter_box().is_none());
+
+    let output = profile_output(&emulator_input(), star()).unwrap();
+    let out_of_bounds = output.out_of_bounds_cells.column("out of bounds cells").unwrap().u32().unwrap();
+    assert!(out_of_bounds.into_iter().flatten().all(|count| count == 0));
+    // The line is in the specific intensities only.
+    let normalized: Vec<f64> = output.data_frame.column("normalized flux").unwrap().f64().unwrap().into_iter().flatten().collect();
+    assert!(normalized.iter().all(|flux| *flux > 0.0 && *flux <= 1.0 + 1.0e-12));
+    assert!(normalized.iter().any(|flux| *flux < 0.9));
+
+    // An emulator must be the only intensity grid.
+    let mixed = emulator_input() + "[[intensity_grids]]\nJoris = {temperature = 20000.0, log_gravity = 3.5, filename = \"t20000g35.txt\"}\n";
+    let config: ProfileConfig = toml::from_str(&mixed).unwrap();
+    assert!(!config.validate().is_valid());
+}
@@ -0,0 +1,61 @@
+//! Golden snapshot test for the synthetic line profiles.
+//!
+//! The test rasterizes the star described by the pulstar golden fixture
+//! (`../pulstar/tests/fixtures/golden/pulstar_input.toml`) with [pulstar_main], feeds it to
+//! [profile_main] together with `tests/fixtures/golden/profile_input.toml` and the small grids in
+//! `tests/fixtures/golden/grids/`, and compares the output with `expected_profile.csv`
+//! column by column within the tolerances listed in [TOLERANCES].
+//!
+//! The expected table is a snapshot of the output of the Rust port itself, not of the original C
+//! program, so the test catches unintended changes of the numbers, not errors of the port.
+//! Running the test with `PULSTAR_BLESS=1` overwrites the expected values with the current output.
+//! Only do this in the commit that intentionally changes the numbers, and say so in its message
+//! (see `tests/fixtures/golden/README.md`).
+mod common;
+
+use common::{fixture_dir, profile_input, pulstar_input};
+use golden_support::{Tolerance, assert_matches_expected_table, write_expected_table};
+use polars::prelude::*;
+use profile::profile_mkr::profile_main;
+use pulstar::pulstar_mkr::pulstar_main;
+
+/// Tolerances per quantity of the profile output.
+///
+/// * `time` - copied from the rasterized star, must match to round-off.
+/// * `wave length` - observed wavelengths in nm, built from the input range, 1e-9 nm.
+/// * `flux`, `continuum` - area weighted intensities. They go through the grid interpolation, so
+///   1e-6 relative, which is below the 8 significant digits written in the grid files.
+/// * `normalized flux` - the quantity used for the line moments; 1e-6 absolute on a value of order unity.
+const TOLERANCES: [Tolerance; 5] = [
+    Tolerance { column: "time", absolute: 1.0e-12, relative: 0.0 },
+    Tolerance { column: "wave length", absolute: 1.0e-9, relative: 0.0 },
+    Tolerance { column: "flux", absolute: 0.0, relative: 1.0e-6 },
+    Tolerance { column: "continuum", absolute: 0.0, relative: 1.0e-6 },
+    Tolerance { column: "normalized flux", absolute: 1.0e-6, relative: 0.0 },
+];
+
+#[test]
+fn profile_matches_golden_output() {
+    let star_df = pulstar_main(&pulstar_input()).expect("pulstar produced no output");
+
+    let input = profile_input(&fixture_dir().join("grids"));
+
+    // The time points are collected with `unique`, so the order of the rows is not fixed.
+    let profile_df = profile_main(&input, star_df)
+        .expect("profile failed on the golden fixture")
+        .sort(["time", "wave length"], SortMultipleOptions::default())
+        .unwrap();
+    let expected_path = fixture_dir().join("expected_profile.csv");
+
+    if std::env::var("PULSTAR_BLESS").is_ok() {
+        write_expected_table(
+            &expected_path,
+            &profile_df,
+            &TOLERANCES,
+            "Expected profile for profile_input.toml, see README.md for its provenance.",
+        );
+        return;
+    }
+
+    assert_matches_expected_table(&profile_df, &expected_path, &TOLERANCES);
+}
@@ -0,0 +1,115 @@
+//! Tests for the binary cache of the intensity grids and the memory mapped backend, using copies of the golden fixtures.
+mod common;
+
+use common::{copy_golden_grids, star};
+use polars::prelude::*;
+use profile::error::ProfileError;
+use profile::profile_mkr::profile_main;
+use profile::utils::grid_cache::{MappedGrid, ParsedGrid};
+use profile::{IntensityGrid, NADYA_MU_VALUES, ProfileConfig};
+
+fn run(input: &str) -> DataFrame {
+    profile_main(input, star())
+        .unwrap()
+        .sort(["time", "wave length"], SortMultipleOptions::default())
+        .unwrap()
+}
+
+fn column(df: &DataFrame, name: &str) -> Vec<f64> {
+    df.column(name).unwrap().f64().unwrap().into_iter().flatten().collect()
+}
+
+#[test]
+fn cached_grids_give_the_same_fluxes() {
+    let dir = std::env::temp_dir().join(format!("profile_grid_cache_{}", std::process::id()));
+    let input = copy_golden_grids(&dir);
+    let from_csv = run(&input);
+
+    let config: ProfileConfig = toml::from_str(&input).unwrap();
+    let paths = config.write_grid_caches().unwrap();
+    assert_eq!(paths.len(), 4);
+    for (grid, path) in config.intensity_grids.iter().zip(paths.iter()) {
+        assert!(grid.has_cache(&config.path_to_grids));
+        assert_eq!(ParsedGrid::read_from(path).unwrap(), grid.parse_grid_file(&config.path_to_grids, &config.mu_values).unwrap());
+    }
+    assert!(from_csv.equals(&run(&input)));
+
+    // The runs read the caches: doubling all of the cached intensities doubles the flux and the continuum.
+    for path in paths.iter() {
+        let mut grid = ParsedGrid::read_from(path).unwrap();
+        grid.values *= 2.0;
+        grid.write_to(path).unwrap();
+    }
+    let doubled = run(&input);
+    for name in ["flux", "continuum"] {
+        for (cached, parsed) in column(&doubled, name).iter().zip(column(&from_csv, name).iter()) {
+            assert!((cached - 2.0 * parsed).abs() <= 1.0e-12 * parsed.abs(), "{}: {} is not 2 × {}", name, cached, parsed);
+        }
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn grids_are_sorted_by_wavelength() {
+    let dir = std::env::temp_dir().join(format!("profile_grid_cache_sorting_{}", std::process::id()));
+    let input = copy_golden_grids(&dir);
+    let from_csv = run(&input);
+
+    // Reversing the rows of the grid files doesn't change anything.
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let reversed: Vec<&str> = contents.lines().rev().collect();
+        std::fs::write(&path, reversed.join("\n") + "\n").unwrap();
+    }
+    let grid = IntensityGrid::Joris { temperature: 20000.0, log_gravity: 3.5, filename: "t20000g35.txt".to_string() };
+    let parsed = grid.parse_grid_file(&format!("{}/", dir.display()), &NADYA_MU_VALUES).unwrap();
+    assert!(parsed.wavelengths.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(from_csv.equals(&run(&input)));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn invalid_caches_are_rejected() {
+    let dir = std::env::temp_dir().join(format!("profile_grid_cache_invalid_{}", std::process::id()));
+    let input = copy_golden_grids(&dir);
+    let from_csv = run(&input);
+
+    let config: ProfileConfig = toml::from_str(&input).unwrap();
+    let paths = config.write_grid_caches().unwrap();
+    let bytes = std::fs::read(&paths[0]).unwrap();
+    std::fs::write(&paths[0], &bytes[..bytes.len() - 8]).unwrap();
+    assert!(matches!(ParsedGrid::read_from(&paths[0]), Err(ProfileError::GridFormat(_))));
+    std::fs::write(&paths[0], b"not a grid cache").unwrap();
+    assert!(matches!(ParsedGrid::read_from(&paths[0]), Err(ProfileError::GridFormat(_))));
+
+    // The run falls back to the csv file of the invalid cache.
+    assert!(from_csv.equals(&run(&input)));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn memory_mapped_grids_give_the_same_fluxes() {
+    let dir = std::env::temp_dir().join(format!("profile_grid_cache_mmap_{}", std::process::id()));
+    let input = copy_golden_grids(&dir);
+    let in_memory = run(&input);
+
+    // The caches are written by the first memory mapped run and reused by the following ones.
+    let memory_mapped_input = format!("grid_backend = \"MemoryMapped\"\n{}", input);
+    let config: ProfileConfig = toml::from_str(&memory_mapped_input).unwrap();
+    assert!(config.intensity_grids.iter().all(|grid| !grid.has_cache(&config.path_to_grids)));
+    assert!(in_memory.equals(&run(&memory_mapped_input)));
+    assert!(config.intensity_grids.iter().all(|grid| grid.has_cache(&config.path_to_grids)));
+    assert!(in_memory.equals(&run(&memory_mapped_input)));
+
+    let mapped = MappedGrid::open(&config.intensity_grids[0].cache_path(&config.path_to_grids)).unwrap();
+    let parsed = config.intensity_grids[0].parse_grid_file(&config.path_to_grids, &config.mu_values).unwrap();
+    assert_eq!(mapped.nrows(), parsed.wavelengths.len());
+    assert_eq!(mapped.rows_within(413.9, 414.0), parsed.rows_within(413.9, 414.0));
+    assert_eq!((mapped.wavelength(3), mapped.value(3, 5)), (parsed.wavelengths[3], parsed.values[[3, 5]]));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,239 @@
+//! Tests for [GridLattice] and for runs with more than four intensity grids, using the golden fixtures.
+mod common;
+
+use common::{fixture_dir, profile_input, pulstar_input};
+use polars::prelude::*;
+use profile::error::ProfileError;
+use profile::profile_mkr::{profile_main, profile_output};
+use profile::provider::{IntensityProvider, LocalAtmosphere};
+use profile::{GridLattice, IntensityGrid, ProfileConfig};
+use pulstar::pulstar_mkr::pulstar_main;
+use temp_name_lib::interpolation::BoundsHandling;
+use std::path::Path;
+
+fn joris(temperature: f64, log_gravity: f64) -> IntensityGrid {
+    IntensityGrid::Joris {
+        temperature,
+        log_gravity,
+        filename: format!("t{}g{}.txt", temperature, (log_gravity * 10.0).round()),
+    }
+}
+
+fn nadya(temperature: f64, log_gravity: f64, metalicity: f64) -> IntensityGrid {
+    IntensityGrid::Nadya { temperature, log_gravity, metalicity, filename: format!("t{}g{}z{}.imu", temperature, log_gravity, metalicity) }
+}
+
+/// The golden star with the given effective temperature.
+fn star(effective_temperature: f64) -> DataFrame {
+    let input = pulstar_input().replace(
+        "effective_temperature = 22642.0",
+        &format!("effective_temperature = {:.1}", effective_temperature),
+    );
+    pulstar_main(&input).unwrap()
+}
+
+/// Copies the golden grids into `dir` and adds grids at 32000 K, whose coefficients are the ones of 26000 K scaled with T⁴.
+/// Returns the profile input with the resulting 3×2 lattice.
+fn extended_grids(dir: &Path) -> String {
+    std::fs::create_dir_all(dir).unwrap();
+    let golden_grids = fixture_dir().join("grids");
+    for entry in std::fs::read_dir(&golden_grids).unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+    }
+    let scale = (32000.0_f64 / 26000.0).powi(4);
+    for log_g in ["35", "45"] {
+        let contents = std::fs::read_to_string(golden_grids.join(format!("t26000g{}.txt", log_g))).unwrap();
+        let scaled: Vec<String> = contents
+            .lines()
+            .map(|line| {
+                let values: Vec<f64> = line.split_whitespace().map(|v| v.parse().unwrap()).collect();
+                let mut columns = vec![format!("{:.8e}", values[0])];
+                columns.extend(values[1..].iter().map(|v| format!("{:.8e}", v * scale)));
+                columns.join(" ")
+            })
+            .collect();
+        std::fs::write(dir.join(format!("t32000g{}.txt", log_g)), scaled.join("\n") + "\n").unwrap();
+    }
+
+    profile_input(dir)
+        + "[[intensity_grids]]\nJoris = {temperature = 32000.0, log_gravity = 4.5, filename = \"t32000g45.txt\"}\n"
+        + "[[intensity_grids]]\nJoris = {temperature = 32000.0, log_gravity = 3.5, filename = \"t32000g35.txt\"}\n"
+}
+
+fn golden_input() -> String {
+    profile_input(&fixture_dir().join("grids"))
+}
+
+fn column(df: &DataFrame, name: &str) -> Vec<f64> {
+    df.column(name).unwrap().f64().unwrap().into_iter().flatten().collect()
+}
+
+#[test]
+fn lattice_does_not_depend_on_the_order_of_the_grids() {
+    let grids = vec![joris(26000.0, 4.5), joris(20000.0, 3.5), joris(23000.0, 4.5), joris(26000.0, 3.5),
+        joris(20000.0, 4.5), joris(23000.0, 3.5)];
+    let lattice = GridLattice::from_intensity_grids(&grids).unwrap();
+    assert_eq!(lattice.temperatures(), &[20000.0, 23000.0, 26000.0]);
+    assert_eq!(lattice.log_gravities(), &[3.5, 4.5]);
+    for i in 0..3 {
+        for j in 0..2 {
+            let grid = &grids[lattice.grid_index(i, j, 0)];
+            assert_eq!((grid.temperature(), grid.log_gravity()), (lattice.temperatures()[i], lattice.log_gravities()[j]));
+        }
+    }
+}
+
+#[test]
+fn cells_are_bracketed_by_the_closest_nodes() {
+    let grids: Vec<IntensityGrid> = [20000.0, 23000.0, 26000.0]
+        .iter()
+        .flat_map(|t| [joris(*t, 3.5), joris(*t, 4.0), joris(*t, 4.5)])
+        .collect();
+    let lattice = GridLattice::from_intensity_grids(&grids).unwrap();
+    assert_eq!(lattice.bracket(21000.0, 3.7, None), (0, 0, 0));
+    assert_eq!(lattice.bracket(23000.0, 4.2, None), (1, 1, 0));
+    assert_eq!(lattice.bracket(26000.0, 4.5, None), (1, 1, 0));
+    // Points outside of the lattice get the cells of the edges.
+    assert_eq!(lattice.bracket(19000.0, 5.0, None), (0, 1, 0));
+    assert_eq!(lattice.bracket(30000.0, 3.0, None), (1, 0, 0));
+}
+
+#[test]
+fn invalid_lattices_are_rejected() {
+    let repeated = vec![joris(20000.0, 3.5), joris(20000.0, 4.5), joris(26000.0, 3.5), joris(26000.0, 4.5), joris(26000.0, 4.5)];
+    assert!(GridLattice::from_intensity_grids(&repeated).is_err());
+
+    let single_temperature = vec![joris(20000.0, 3.5), joris(20000.0, 4.5)];
+    assert!(GridLattice::from_intensity_grids(&single_temperature).is_err());
+
+    // With several metallicities the lattice must be complete.
+    let mut missing: Vec<IntensityGrid> = [0.0, -0.5]
+        .iter()
+        .flat_map(|z| [nadya(20000.0, 3.5, *z), nadya(20000.0, 4.5, *z), nadya(26000.0, 3.5, *z), nadya(26000.0, 4.5, *z)])
+        .collect();
+    missing.pop();
+    match GridLattice::from_intensity_grids(&missing) {
+        Err(ProfileError::InvalidConfig(message)) => {
+            assert!(message.contains("T_eff = 26000.0 K, log g = 4.5000, [M/H] = -0.50"), "{}", message)
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // The nodes left on an incomplete lattice can't lie on a line.
+    let diagonal = vec![joris(20000.0, 3.5), joris(23000.0, 4.0), joris(26000.0, 4.5)];
+    assert!(GridLattice::from_intensity_grids(&diagonal).is_err());
+}
+
+#[test]
+fn incomplete_lattices_are_triangulated() {
+    let missing = vec![joris(20000.0, 3.5), joris(20000.0, 4.5), joris(26000.0, 3.5)];
+    let lattice = GridLattice::from_intensity_grids(&missing).unwrap();
+    assert!(!lattice.is_complete());
+    let complete = vec![joris(20000.0, 3.5), joris(20000.0, 4.5), joris(26000.0, 3.5), joris(26000.0, 4.5)];
+    assert!(GridLattice::from_intensity_grids(&complete).unwrap().is_complete());
+    assert!(lattice.contains(21000.0, 3.7));
+    assert!(!lattice.contains(25000.0, 4.4));
+
+    // Within the triangle the weights are barycentric, and they refer to the intensity grids.
+    let mut weights = Vec::new();
+    lattice.corner_weights(21500.0, 3.75, None, BoundsHandling::Error, &mut weights).unwrap();
+    weights.sort_by_key(|(grid, _)| *grid);
+    let expected = [(0, 0.5), (1, 0.25), (2, 0.25)];
+    for ((grid, weight), (expected_grid, expected_weight)) in weights.iter().zip(expected.iter()) {
+        assert_eq!(grid, expected_grid);
+        assert!((weight - expected_weight).abs() < 1.0e-12, "{:?}", weights);
+    }
+
+    // Beyond the diagonal the point is outside of the grids.
+    assert!(lattice.corner_weights(25000.0, 4.4, None, BoundsHandling::Error, &mut weights).is_err());
+    lattice.corner_weights(25000.0, 4.4, None, BoundsHandling::Clamp, &mut weights).unwrap();
+    assert!(weights.iter().all(|(_, weight)| *weight >= 0.0));
+}
+
+#[test]
+fn metallicity_is_a_third_axis_of_the_lattice() {
+    let grids: Vec<IntensityGrid> = [0.0, -1.0, -0.5]
+        .iter()
+        .flat_map(|z| [nadya(20000.0, 3.5, *z), nadya(20000.0, 4.5, *z), nadya(26000.0, 3.5, *z), nadya(26000.0, 4.5, *z)])
+        .collect();
+    let lattice = GridLattice::from_intensity_grids(&grids).unwrap();
+    assert!(lattice.interpolates_metallicity());
+    assert_eq!(lattice.metallicities(), &[-1.0, -0.5, 0.0]);
+    assert_eq!(lattice.bracket(21000.0, 3.7, Some(-0.2)), (0, 0, 1));
+    assert_eq!(grids[lattice.grid_index(1, 0, 1)].metallicity(), Some(-0.5));
+    assert!(lattice.contains_metallicity(Some(-0.7)));
+    assert!(!lattice.contains_metallicity(Some(0.2)));
+    assert!(!lattice.contains_metallicity(None));
+
+    // A single metallicity is not interpolated.
+    let solar = GridLattice::from_intensity_grids(&grids[0..4]).unwrap();
+    assert!(!solar.interpolates_metallicity());
+    assert_eq!(solar.bracket(21000.0, 3.7, Some(-0.2)), (0, 0, 0));
+
+    // Grids with and without metallicity can't be mixed.
+    let mixed = vec![nadya(20000.0, 3.5, 0.0), nadya(20000.0, 4.5, 0.0), joris(26000.0, 3.5), joris(26000.0, 4.5)];
+    assert!(GridLattice::from_intensity_grids(&mixed).is_err());
+}
+
+#[test]
+fn larger_lattices_extend_the_coverage() {
+    let dir = std::env::temp_dir().join(format!("profile_grid_lattice_{}", std::process::id()));
+    let extended_input = extended_grids(&dir);
+
+    // The golden star lies within the first cell of the lattice, so the extra grids don't change the fluxes.
+    let sort = |df: DataFrame| df.sort(["time", "wave length"], SortMultipleOptions::default()).unwrap();
+    let golden = sort(profile_main(&golden_input(), star(22642.0)).unwrap());
+    let extended = sort(profile_main(&extended_input, star(22642.0)).unwrap());
+    assert_eq!(column(&golden, "flux"), column(&extended, "flux"));
+    assert_eq!(column(&golden, "continuum"), column(&extended, "continuum"));
+
+    // A star hotter than 26000 K needs the grids at 32000 K.
+    assert!(profile_main(&golden_input(), star(25800.0)).is_err());
+    let hot = profile_output(&extended_input, star(25800.0)).unwrap();
+    let out_of_bounds = hot.out_of_bounds_cells.column("out of bounds cells").unwrap().u32().unwrap();
+    assert!(out_of_bounds.into_iter().flatten().all(|count| count == 0));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn grids_with_missing_nodes_are_interpolated_within_triangles() {
+    let dir = std::env::temp_dir().join(format!("profile_missing_nodes_{}", std::process::id()));
+    // The 3×2 lattice without the node at 32000 K and log g = 3.5.
+    let missing_input = extended_grids(&dir).replace(
+        "[[intensity_grids]]\nJoris = {temperature = 32000.0, log_gravity = 3.5, filename = \"t32000g35.txt\"}\n",
+        "",
+    );
+    let missing: ProfileConfig = toml::from_str(&missing_input).unwrap();
+    assert_eq!(missing.intensity_grids.len(), 5);
+    assert!(missing.validate().is_valid());
+    let missing_grid = missing.init_spectral_grid_from_csv(1.0, 1.0).unwrap();
+    let complete: ProfileConfig = toml::from_str(&golden_input()).unwrap();
+    let complete_grid = complete.init_spectral_grid_from_csv(1.0, 1.0).unwrap();
+
+    // Along the edges of the cells the triangles and the lattice cells interpolate linearly between the same nodes.
+    let wavelengths = [413.9, 413.95, 414.0];
+    let intensities = |grid: &profile::SpectralGrid, temperature: f64, log_gravity: f64| {
+        let mut scratch = grid.new_scratch();
+        let atmosphere = LocalAtmosphere { temperature, log_gravity, metallicity: None, mu: 0.7 };
+        let (mut specific, mut continuum) = ([0.0; 3], [0.0; 3]);
+        grid.intensities(&atmosphere, &wavelengths, BoundsHandling::Error, &mut scratch, &mut specific, &mut continuum).unwrap();
+        (specific, continuum)
+    };
+    for (temperature, log_gravity) in [(21000.0, 3.5), (24500.0, 4.5), (20000.0, 3.9), (26000.0, 4.2)] {
+        let (specific, continuum) = intensities(&missing_grid, temperature, log_gravity);
+        let (expected_specific, expected_continuum) = intensities(&complete_grid, temperature, log_gravity);
+        for n in 0..wavelengths.len() {
+            assert!((specific[n] - expected_specific[n]).abs() < 1.0e-12 * expected_specific[n]);
+            assert!((continuum[n] - expected_continuum[n]).abs() < 1.0e-12 * expected_continuum[n]);
+        }
+    }
+
+    // The golden star lies within the triangles.
+    let output = profile_output(&missing_input, star(22642.0)).unwrap();
+    let out_of_bounds = output.out_of_bounds_cells.column("out of bounds cells").unwrap().u32().unwrap();
+    assert!(out_of_bounds.into_iter().flatten().all(|count| count == 0));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,181 @@
+//! Tests for the interpolation schemes along the wavelength and µ axes of the intensity grids.
+//!
+//! The Joris-like grids are written by the tests, with a narrow absorption line sampled every 0.01 nm.
+use profile::provider::{IntensityProvider, LocalAtmosphere};
+use profile::{GridInterpolation, InterpolationScheme, ProfileConfig, SpectralGrid};
+use temp_name_lib::interpolation::BoundsHandling;
+use std::path::{Path, PathBuf};
+
+const LINE_CENTER: f64 = 414.0;
+const LINE_SIGMA: f64 = 0.01;
+
+/// Coefficients of Joris' limb-darkening law of the continuum, a quadratic law in µ.
+const CONTINUUM_LAW: [f64; 4] = [1.0, -0.45, 0.05, 0.0];
+
+/// The line depth relative to the continuum at a wavelength.
+fn line(wavelength: f64) -> f64 {
+    1.0 - 0.6 * (-0.5 * ((wavelength - LINE_CENTER) / LINE_SIGMA).powi(2)).exp()
+}
+
+fn law(mu: f64) -> f64 {
+    CONTINUUM_LAW[0] + CONTINUUM_LAW[1] * (1.0 - mu) + CONTINUUM_LAW[2] * (1.0 - mu.powi(2)) + CONTINUUM_LAW[3] * (1.0 - mu.powi(3))
+}
+
+/// The wavelengths of the grids, sampled every 0.01 nm.
+fn uniform_wavelengths() -> Vec<f64> {
+    (0..=100).map(|row| 413.5 + 0.01 * row as f64).collect()
+}
+
+/// Writes the four grids of a lattice, all with the same intensities, and returns the directory.
+fn write_grids() -> PathBuf {
+    write_grids_at("profile_interpolation", &uniform_wavelengths())
+}
+
+/// Writes the four grids of a lattice at some wavelengths into a directory named after `name`, and returns the directory.
+fn write_grids_at(name: &str, wavelengths: &[f64]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let lines: Vec<String> = wavelengths
+        .iter()
+        .map(|&wavelength| {
+            let specific = CONTINUUM_LAW.iter().map(|c| format!("{:.12e}", c * line(wavelength)));
+            let continuum = CONTINUUM_LAW.iter().map(|c| format!("{:.12e}", c));
+            std::iter::once(format!("{:.8e}", wavelength)).chain(specific).chain(continuum).collect::<Vec<String>>().join(" ")
+        })
+        .collect();
+    for name in ["t20000g35.txt", "t20000g45.txt", "t26000g35.txt", "t26000g45.txt"] {
+        std::fs::write(dir.join(name), lines.join("\n") + "\n").unwrap();
+    }
+    dir
+}
+
+fn spectral_grid(dir: &Path, interpolation: &str) -> SpectralGrid {
+    let mut input = format!(
+        "path_to_grids = \"{}/\"\ninterpolation = {}\n\n[wavelength_range]\nstart = 413.9\nend = 414.1\nstep = 0.01\n",
+        dir.display(),
+        interpolation
+    );
+    for (temperature, log_gravity) in [(20000, 35), (20000, 45), (26000, 35), (26000, 45)] {
+        input += &format!(
+            "[[intensity_grids]]\nJoris = {{temperature = {:.1}, log_gravity = {:.1}, filename = \"t{}g{}.txt\"}}\n",
+            temperature as f64,
+            log_gravity as f64 / 10.0,
+            temperature,
+            log_gravity
+        );
+    }
+    let config: ProfileConfig = toml::from_str(&input).unwrap();
+    config.init_spectral_grid_from_csv(1.0, 1.0).unwrap()
+}
+
+/// Returns the largest relative error of the specific intensities at some wavelengths and µ values.
+fn largest_error(grid: &SpectralGrid, wavelengths: &[f64], mu_values: &[f64]) -> f64 {
+    let mut scratch = grid.new_scratch();
+    let (mut specific, mut continuum) = (vec![0.0; wavelengths.len()], vec![0.0; wavelengths.len()]);
+    let mut largest = 0.0f64;
+    for mu in mu_values {
+        let atmosphere = LocalAtmosphere { temperature: 22000.0, log_gravity: 4.0, metallicity: None, mu: *mu };
+        grid.intensities(&atmosphere, wavelengths, BoundsHandling::Error, &mut scratch, &mut specific, &mut continuum).unwrap();
+        for (wavelength, intensity) in wavelengths.iter().zip(specific.iter()) {
+            let expected = law(*mu) * line(*wavelength);
+            largest = largest.max((intensity - expected).abs() / expected);
+        }
+    }
+    largest
+}
+
+#[test]
+fn cubic_schemes_follow_the_line_core_better() {
+    let dir = write_grids();
+    // Halfway between the wavelengths of the grids, at µ values of the grids.
+    let wavelengths: Vec<f64> = (0..20).map(|n| 413.905 + 0.01 * n as f64).collect();
+    let mu_values = [0.4629, 0.5976, 0.9636];
+    let linear = largest_error(&spectral_grid(&dir, "{}"), &wavelengths, &mu_values);
+    let catmull_rom = largest_error(&spectral_grid(&dir, "{wavelength = \"CatmullRom\"}"), &wavelengths, &mu_values);
+    let monotone = largest_error(&spectral_grid(&dir, "{wavelength = \"Monotone\"}"), &wavelengths, &mu_values);
+    assert!(catmull_rom < 0.5 * linear, "{} {}", catmull_rom, linear);
+    assert!(monotone < linear, "{} {}", monotone, linear);
+
+    // Between the µ values of the grids, at wavelengths of the grids. The end intervals are left out, the slopes at the ends are the
+    // ones of the end intervals so they're interpolated almost linearly.
+    let wavelengths = [413.95, 414.0, 414.05];
+    let mu_values = [0.5, 0.66, 0.75, 0.85];
+    let linear = largest_error(&spectral_grid(&dir, "{}"), &wavelengths, &mu_values);
+    let catmull_rom = largest_error(&spectral_grid(&dir, "{mu = \"CatmullRom\"}"), &wavelengths, &mu_values);
+    let monotone = largest_error(&spectral_grid(&dir, "{mu = \"Monotone\"}"), &wavelengths, &mu_values);
+    assert!(catmull_rom < 0.5 * linear, "{} {}", catmull_rom, linear);
+    assert!(monotone < linear, "{} {}", monotone, linear);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn interpolation_is_linear_by_default() {
+    let input = "path_to_grids = \"./\"\ninterpolation = {wavelength = \"Monotone\"}\n\n[wavelength_range]\nstart = 413.9\nend = 414.1\nstep = 0.01\n";
+    let config: ProfileConfig = toml::from_str(input).unwrap();
+    assert_eq!(config.interpolation, GridInterpolation { wavelength: InterpolationScheme::Monotone, mu: InterpolationScheme::Linear });
+    let input = "path_to_grids = \"./\"\n\n[wavelength_range]\nstart = 413.9\nend = 414.1\nstep = 0.01\n";
+    let config: ProfileConfig = toml::from_str(input).unwrap();
+    assert_eq!(config.interpolation, GridInterpolation::default());
+}
+
+#[test]
+fn uniform_and_non_uniform_wavelengths_are_indexed_alike() {
+    // Every other wavelength of the non-uniform grids is shifted by a quarter of the step.
+    let non_uniform: Vec<f64> = uniform_wavelengths()
+        .iter()
+        .enumerate()
+        .map(|(row, wavelength)| if row % 2 == 1 { wavelength + 0.0025 } else { *wavelength })
+        .collect();
+    for (name, wavelengths, uniform) in [
+        ("profile_uniform_wavelengths", uniform_wavelengths(), true),
+        ("profile_non_uniform_wavelengths", non_uniform, false),
+    ] {
+        let dir = write_grids_at(name, &wavelengths);
+        let grid = spectral_grid(&dir, "{}");
+        assert_eq!(grid.wavelength_step().is_some(), uniform, "{}", name);
+
+        // The wavelengths as read from the grids within [413.9, 414.1], these are always loaded.
+        let nodes: Vec<f64> = wavelengths
+            .iter()
+            .map(|wavelength| format!("{:.8e}", wavelength).parse::<f64>().unwrap())
+            .filter(|wavelength| (413.9..=414.1).contains(wavelength))
+            .collect();
+        let mu = 0.5976;
+        let intensity = |wavelength: f64| law(mu) * line(wavelength);
+        let mut queries: Vec<(f64, f64)> = nodes.iter().map(|node| (*node, intensity(*node))).collect();
+        for pair in nodes.windows(2) {
+            let wavelength = pair[0] + 0.3 * (pair[1] - pair[0]);
+            queries.push((wavelength, 0.7 * intensity(pair[0]) + 0.3 * intensity(pair[1])));
+        }
+
+        let atmosphere = LocalAtmosphere { temperature: 22000.0, log_gravity: 4.0, metallicity: None, mu };
+        let mut scratch = grid.new_scratch();
+        let (mut specific, mut continuum) = (vec![0.0; 1], vec![0.0; 1]);
+        for (wavelength, expected) in queries {
+            grid.intensities(&atmosphere, &[wavelength], BoundsHandling::Error, &mut scratch, &mut specific, &mut continuum).unwrap();
+            assert!((specific[0] - expected).abs() <= 1.0e-9 * expected, "{}: {} {} {}", name, wavelength, specific[0], expected);
+        }
+        for wavelength in [413.0, 415.0, f64::NAN] {
+            let result = grid.intensities(&atmosphere, &[wavelength], BoundsHandling::Error, &mut scratch, &mut specific, &mut continuum);
+            assert!(result.is_err(), "{}: {}", name, wavelength);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+#[test]
+fn grids_with_a_single_wavelength_are_out_of_bounds() {
+    let dir = write_grids_at("profile_single_wavelength", &[414.0]);
+    let grid = spectral_grid(&dir, "{}");
+    assert_eq!(grid.wavelength_step(), None);
+
+    let atmosphere = LocalAtmosphere { temperature: 22000.0, log_gravity: 4.0, metallicity: None, mu: 0.5976 };
+    let mut scratch = grid.new_scratch();
+    let (mut specific, mut continuum) = (vec![0.0; 1], vec![0.0; 1]);
+    for wavelength in [413.95, 414.0, 414.05] {
+        let result = grid.intensities(&atmosphere, &[wavelength], BoundsHandling::Error, &mut scratch, &mut specific, &mut continuum);
+        assert!(result.is_err(), "{}", wavelength);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,125 @@
+//! Tests for the interpolation of the intensity grids in metallicity.
+//!
+//! The Nadya-like grids are written by the tests out of the golden Joris-like grids, evaluating their limb-darkening law
+//! on the µ values of Nadya's grids. The grids of some metallicities are scaled by a constant factor.
+mod common;
+
+use common::{fixture_dir, pulstar_input};
+use polars::prelude::*;
+use profile::error::ProfileError;
+use profile::profile_mkr::{profile_main, profile_output};
+use profile::NADYA_MU_VALUES;
+use pulstar::pulstar_mkr::pulstar_main;
+use std::path::Path;
+
+/// Intensity factor of the metal poor grids with respect to the solar ones.
+const METAL_POOR_FACTOR: f64 = 0.8;
+
+/// The golden star, with a metallicity if it's given.
+fn star(metallicity: Option<f64>) -> DataFrame {
+    let mut input = pulstar_input();
+    if let Some(metallicity) = metallicity {
+        input = input.replace("inclination_angle = 45.0", &format!("inclination_angle = 45.0\nmetallicity = {:.2}", metallicity));
+    }
+    pulstar_main(&input).unwrap()
+}
+
+/// Writes a Nadya-like grid out of a golden Joris-like grid, with all of the intensities scaled by `factor`.
+fn write_nadya_grid(joris_file: &Path, nadya_file: &Path, factor: f64) {
+    let contents = std::fs::read_to_string(joris_file).unwrap();
+    let law = |coefficients: &[f64], mu: f64| {
+        coefficients[0] + coefficients[1] * (1.0 - mu) + coefficients[2] * (1.0 - mu.powi(2)) + coefficients[3] * (1.0 - mu.powi(3))
+    };
+    let lines: Vec<String> = contents
+        .lines()
+        .map(|line| {
+            let values: Vec<f64> = line.split_whitespace().map(|v| v.parse().unwrap()).collect();
+            let mut columns = vec![format!("{:.8e}", values[0])];
+            columns.extend(NADYA_MU_VALUES.iter().map(|mu| format!("{:.8e}", factor * law(&values[1..5], *mu))));
+            columns.extend(NADYA_MU_VALUES.iter().map(|mu| format!("{:.8e}", factor * law(&values[5..9], *mu))));
+            columns.join(" ")
+        })
+        .collect();
+    std::fs::write(nadya_file, lines.join("\n") + "\n").unwrap();
+}
+
+/// Writes the Nadya-like grids for the given metallicities into `dir`, scaled by the given factors,
+/// and returns the profile input that uses them.
+fn nadya_grids(dir: &Path, metallicities: &[(f64, f64)]) -> String {
+    std::fs::create_dir_all(dir).unwrap();
+    let mut input = format!(
+        "path_to_grids = \"{}/\"\n\n[wavelength_range]\nstart = 413.85\nend = 414.05\nstep = 0.01\n\n",
+        dir.display()
+    );
+    for (metallicity, factor) in metallicities {
+        for (temperature, log_gravity) in [(20000, 35), (20000, 45), (26000, 35), (26000, 45)] {
+            let filename = format!("lp00000_{:05}_0{}00_{:+.2}.imu", temperature, log_gravity, metallicity);
+            write_nadya_grid(
+                &fixture_dir().join("grids").join(format!("t{}g{}.txt", temperature, log_gravity)),
+                &dir.join(&filename),
+                *factor,
+            );
+            input += &format!(
+                "[[intensity_grids]]\nNadya = {{temperature = {:.1}, log_gravity = {:.1}, metalicity = {:.2}, filename = \"{}\"}}\n",
+                temperature as f64,
+                log_gravity as f64 / 10.0,
+                metallicity,
+                filename
+            );
+        }
+    }
+    input
+}
+
+fn column(df: &DataFrame, name: &str) -> Vec<f64> {
+    df.sort(["time", "wave length"], SortMultipleOptions::default())
+        .unwrap()
+        .column(name)
+        .unwrap()
+        .f64()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[test]
+fn pulstar_writes_the_metallicity_of_the_star() {
+    assert!(star(None).column("metallicity").is_err());
+    let metallicity = star(Some(-0.25)).column("metallicity").unwrap().f64().unwrap().clone();
+    assert!(metallicity.into_iter().all(|value| value == Some(-0.25)));
+}
+
+#[test]
+fn fluxes_are_interpolated_in_metallicity() {
+    let dir = std::env::temp_dir().join(format!("profile_metallicity_{}", std::process::id()));
+    // The reference fluxes come from grids that don't change with metallicity.
+    let reference = profile_main(&nadya_grids(&dir.join("reference"), &[(-1.0, 1.0), (-0.5, 1.0), (0.0, 1.0)]), star(Some(-0.25))).unwrap();
+
+    // Both metallicities around the star are scaled by the same factor, so the fluxes are scaled by it too.
+    let metal_poor_grids = nadya_grids(&dir.join("metal_poor"), &[(-1.0, 1.0), (-0.5, METAL_POOR_FACTOR), (0.0, METAL_POOR_FACTOR)]);
+    let metal_poor = profile_main(&metal_poor_grids, star(Some(-0.25))).unwrap();
+    for name in ["flux", "continuum"] {
+        for (interpolated, reference) in column(&metal_poor, name).iter().zip(column(&reference, name).iter()) {
+            assert!((interpolated - METAL_POOR_FACTOR * reference).abs() <= 1.0e-9 * reference.abs(),
+                "{}: {} is not {} × {}", name, interpolated, METAL_POOR_FACTOR, reference);
+        }
+    }
+
+    // A star between metal poor and solar grids lies in between them.
+    let input = nadya_grids(&dir.join("lattice"), &[(-1.0, METAL_POOR_FACTOR), (-0.5, METAL_POOR_FACTOR), (0.0, 1.0)]);
+    let halfway = profile_main(&input, star(Some(-0.25))).unwrap();
+    for (interpolated, reference) in column(&halfway, "flux").iter().zip(column(&reference, "flux").iter()) {
+        assert!(METAL_POOR_FACTOR * reference < *interpolated && interpolated < reference,
+            "{} is not between the metal poor and the solar flux {}", interpolated, reference);
+    }
+
+    // The star must have a metallicity within the grids.
+    assert!(matches!(profile_main(&input, star(None)), Err(ProfileError::InvalidConfig(_))));
+    assert!(matches!(profile_main(&input, star(Some(0.3))), Err(ProfileError::MetallicityCoverage { .. })));
+    let clamped = profile_output(&format!("out_of_bounds = \"Clamp\"\n{}", input), star(Some(0.3))).unwrap();
+    let out_of_bounds = clamped.out_of_bounds_cells.column("out of bounds cells").unwrap().u32().unwrap();
+    assert!(out_of_bounds.into_iter().flatten().all(|count| count > 0));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,182 @@
+//! Tests for the µ values of the intensity grids, given by the configuration or by the grid files, using the golden fixtures.
+//!
+//! The Nadya-like grids are written by the tests out of the golden Joris-like grids, evaluating their limb-darkening law
+//! on the µ values given in the header of the files.
+mod common;
+
+use common::{fixture_dir, profile_input, star};
+use polars::prelude::*;
+use profile::profile_mkr::profile_main;
+use profile::provider::{IntensityProvider, LocalAtmosphere};
+use profile::{FluxOfSpectra, ProfileConfig, NADYA_MU_VALUES};
+use temp_name_lib::interpolation::BoundsHandling;
+use std::path::Path;
+
+/// Ten µ values, in the order of the columns of the grid files.
+const MU_VALUES: [f64; 10] = [0.95, 0.2, 0.9, 0.3, 0.8, 0.4, 0.7, 0.5, 0.6, 1.0];
+
+const GOLDEN_GRIDS: [(u32, u32); 4] = [(20000, 35), (20000, 45), (26000, 35), (26000, 45)];
+
+/// The golden profile input, with the given µ values.
+fn joris_input(mu_values: &[f64]) -> String {
+    let input = profile_input(&fixture_dir().join("grids"));
+    format!("mu_values = {:?}\n{}", mu_values, input)
+}
+
+/// Writes a Nadya-like grid out of a golden Joris-like grid, with the µ values of its columns in the first line.
+fn write_nadya_grid(joris_file: &Path, nadya_file: &Path, mu_values: &[f64]) {
+    let contents = std::fs::read_to_string(joris_file).unwrap();
+    let law = |coefficients: &[f64], mu: f64| {
+        coefficients[0] + coefficients[1] * (1.0 - mu) + coefficients[2] * (1.0 - mu.powi(2)) + coefficients[3] * (1.0 - mu.powi(3))
+    };
+    let header = format!("# mu {}", mu_values.iter().map(|mu| mu.to_string()).collect::<Vec<String>>().join(" "));
+    let lines: Vec<String> = std::iter::once(header)
+        .chain(contents.lines().map(|line| {
+            let values: Vec<f64> = line.split_whitespace().map(|v| v.parse().unwrap()).collect();
+            let mut columns = vec![format!("{:.12e}", values[0])];
+            columns.extend(mu_values.iter().map(|mu| format!("{:.12e}", law(&values[1..5], *mu))));
+            columns.extend(mu_values.iter().map(|mu| format!("{:.12e}", law(&values[5..9], *mu))));
+            columns.join(" ")
+        }))
+        .collect();
+    std::fs::write(nadya_file, lines.join("\n") + "\n").unwrap();
+}
+
+fn column(df: &DataFrame, name: &str) -> Vec<f64> {
+    df.sort(["time", "wave length"], SortMultipleOptions::default())
+        .unwrap()
+        .column(name)
+        .unwrap()
+        .f64()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[test]
+fn grid_files_give_their_own_mu_values() {
+    let dir = std::env::temp_dir().join(format!("profile_mu_sampling_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // The configuration has the default µ values, the ones of the files take precedence.
+    let mut input = format!("path_to_grids = \"{}/\"\n\n[wavelength_range]\nstart = 413.85\nend = 414.05\nstep = 0.01\n\n", dir.display());
+    for (temperature, log_gravity) in GOLDEN_GRIDS {
+        let filename = format!("lp00000_{:05}_0{}00_0200..imu", temperature, log_gravity);
+        write_nadya_grid(&fixture_dir().join("grids").join(format!("t{}g{}.txt", temperature, log_gravity)), &dir.join(&filename), &MU_VALUES);
+        input += &format!(
+            "[[intensity_grids]]\nNadya = {{temperature = {:.1}, log_gravity = {:.1}, metalicity = 0.02, filename = \"{}\"}}\n",
+            temperature as f64,
+            log_gravity as f64 / 10.0,
+            filename
+        );
+    }
+
+    let config: ProfileConfig = toml::from_str(&input).unwrap();
+    let parsed = config.intensity_grids[0].parse_grid_file(&config.path_to_grids, &config.mu_values).unwrap();
+    let mut sorted = MU_VALUES.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    assert_eq!(parsed.mu_values, sorted);
+    assert_eq!(parsed.values.ncols(), 2 * MU_VALUES.len());
+
+    // The Joris grids evaluated on the same µ values give the same fluxes.
+    let nadya = profile_main(&input, star()).unwrap();
+    let joris = profile_main(&joris_input(&MU_VALUES), star()).unwrap();
+    for name in ["flux", "continuum"] {
+        for (nadya, joris) in column(&nadya, name).iter().zip(column(&joris, name).iter()) {
+            assert!((nadya - joris).abs() <= 1.0e-9 * joris.abs(), "{}: {} is not {}", name, nadya, joris);
+        }
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Returns the coefficients of the limb-darkening law of the specific and continuum intensities of a golden grid at a wavelength.
+fn joris_coefficients(temperature: u32, log_gravity: u32, wavelength: f64) -> (Vec<f64>, Vec<f64>) {
+    let contents = std::fs::read_to_string(fixture_dir().join("grids").join(format!("t{}g{}.txt", temperature, log_gravity))).unwrap();
+    let values: Vec<f64> = contents
+        .lines()
+        .map(|line| line.split_whitespace().map(|v| v.parse().unwrap()).collect::<Vec<f64>>())
+        .find(|values| (values[0] - wavelength).abs() < 1.0e-9)
+        .unwrap();
+    (values[1..5].to_vec(), values[5..9].to_vec())
+}
+
+/// Evaluates Joris' limb-darkening law.
+fn joris_law(coefficients: &[f64], mu: f64) -> f64 {
+    coefficients[0] + coefficients[1] * (1.0 - mu) + coefficients[2] * (1.0 - mu.powi(2)) + coefficients[3] * (1.0 - mu.powi(3))
+}
+
+#[test]
+fn intensities_are_extrapolated_outside_of_the_mu_range() {
+    let config: ProfileConfig = toml::from_str(&joris_input(&[0.5, 0.6, 0.7, 0.8, 0.9])).unwrap();
+    let grid = config.init_spectral_grid_from_csv(1.0, 1.0).unwrap();
+    let mut scratch = grid.new_scratch();
+    let wavelengths = [413.9, 413.95, 414.0];
+    let mut intensities = |mu: f64| {
+        let atmosphere = LocalAtmosphere { temperature: 20000.0, log_gravity: 3.5, metallicity: None, mu };
+        let (mut specific, mut continuum) = ([0.0; 3], [0.0; 3]);
+        grid.intensities(&atmosphere, &wavelengths, BoundsHandling::Error, &mut scratch, &mut specific, &mut continuum).unwrap();
+        (specific[0], continuum[0])
+    };
+
+    // At the nodes of the grids the intensities are the ones of the grid files, the grids are looked up at µ = cos(χ).
+    let parsed = config.intensity_grids[0].parse_grid_file(&config.path_to_grids, &config.mu_values).unwrap();
+    let row = parsed.wavelengths.iter().position(|wavelength| (wavelength - 413.9).abs() < 1.0e-9).unwrap();
+    let (specific, continuum) = intensities(0.6);
+    assert!((specific - parsed.values[[row, 1]]).abs() < 1.0e-15);
+    assert!((continuum - parsed.values[[row, 6]]).abs() < 1.0e-15);
+
+    // The golden grids follow a quadratic law, so the law fitted to the µ values extrapolates them exactly.
+    let (specific_law, continuum_law) = joris_coefficients(20000, 35, 413.9);
+    for mu in [0.01, 0.2, 0.45, 0.95, 1.0] {
+        let (specific, continuum) = intensities(mu);
+        assert!((specific - joris_law(&specific_law, mu)).abs() < 1.0e-12 * specific, "µ = {}", mu);
+        assert!((continuum - joris_law(&continuum_law, mu)).abs() < 1.0e-12 * continuum, "µ = {}", mu);
+    }
+}
+
+/// A static star of uniform atmosphere seen pole-on, made of rings of equal width in µ down to the limb.
+/// The areas are projected towards the observer and add up to one.
+fn uniform_disk(temperature: f64, log_gravity: f64, rings: usize) -> LazyFrame {
+    let coschi: Vec<f64> = (0..rings).map(|ring| (ring as f64 + 0.5) / rings as f64).collect();
+    let area: Vec<f64> = coschi.iter().map(|mu| 2.0 * mu / rings as f64).collect();
+    df!(
+        "time" => vec![0.0; rings],
+        "velocity" => vec![0.0; rings],
+        "area" => area,
+        "coschi" => coschi,
+        "temperature" => vec![temperature; rings],
+        "log gravity" => vec![log_gravity; rings],
+    )
+    .unwrap()
+    .lazy()
+}
+
+#[test]
+fn the_disk_integrated_flux_of_the_grids_is_conserved() {
+    let input = joris_input(&NADYA_MU_VALUES);
+    let config: ProfileConfig = toml::from_str(&input).unwrap();
+    let grid = config.init_spectral_grid_from_csv(1.0, 1.0).unwrap();
+    let mut fluxes = FluxOfSpectra::new(&config).unwrap();
+    fluxes.integrate(uniform_disk(20000.0, 3.5, 2000), 0.0, &grid).unwrap();
+
+    // The flux of the grid's own law, 2∫I(µ)µdµ, including the limb and the disk centre. The difference comes from the linear
+    // interpolation between the µ values of the grids, keeping the intensities of the closest ones outside of them would be 25 times larger.
+    let disk_flux = |coefficients: &[f64]| coefficients[0] + coefficients[1] / 3.0 + coefficients[2] / 2.0 + 0.6 * coefficients[3];
+    for (n, wavelength) in fluxes.wavelengths.iter().enumerate() {
+        let (specific_law, continuum_law) = joris_coefficients(20000, 35, (wavelength * 1.0e3).round() / 1.0e3);
+        let (flux, continuum) = (disk_flux(&specific_law), disk_flux(&continuum_law));
+        assert!((fluxes.flux[n] - flux).abs() < 5.0e-4 * flux, "{}: {} is not {}", wavelength, fluxes.flux[n], flux);
+        assert!((fluxes.continuum[n] - continuum).abs() < 5.0e-4 * continuum, "{}: {} is not {}", wavelength, fluxes.continuum[n], continuum);
+    }
+}
+
+#[test]
+fn invalid_mu_values_are_rejected() {
+    for mu_values in [vec![0.5], vec![0.5, 0.7, 0.5], vec![0.0, 0.5], vec![0.5, 1.2]] {
+        let config: ProfileConfig = toml::from_str(&joris_input(&mu_values)).unwrap();
+        assert!(!config.validate().is_valid(), "{:?}", mu_values);
+    }
+    let config: ProfileConfig = toml::from_str(&joris_input(&MU_VALUES)).unwrap();
+    assert!(config.validate().is_valid());
+}
@@ -0,0 +1,123 @@
+//! Tests for the `out_of_bounds` policy of the profile configuration, using the golden fixtures
+//! with a star hot enough to leave the intensity grids on some of its cells.
+mod common;
+
+use common::{fixture_dir, pulstar_input};
+use polars::prelude::*;
+use profile::profile_mkr::{profile_main, profile_output};
+use profile::utils::{output_path, IntensityFlux, FLUX_OUTPUT, OUT_OF_BOUNDS_OUTPUT};
+use profile::{FluxOfSpectra, ProfileConfig};
+use pulstar::pulstar_mkr::pulstar_main;
+
+/// The golden profile input with the given `out_of_bounds` policy.
+fn profile_input(policy: &str) -> String {
+    let input = common::profile_input(&fixture_dir().join("grids"));
+    format!("out_of_bounds = \"{}\"\n{}", policy, input)
+}
+
+/// The golden star with the given effective temperature.
+fn star(effective_temperature: f64) -> DataFrame {
+    let input = pulstar_input().replace(
+        "effective_temperature = 22642.0",
+        &format!("effective_temperature = {:.1}", effective_temperature),
+    );
+    pulstar_main(&input).unwrap()
+}
+
+/// Runs profile and returns the fluxes sorted by time and wavelength, and the out of bounds cells sorted by time.
+fn run(policy: &str, star_df: DataFrame) -> IntensityFlux {
+    let output = profile_output(&profile_input(policy), star_df)
+        .unwrap_or_else(|e| panic!("profile failed with the {} policy: {}", policy, e));
+    IntensityFlux {
+        data_frame: output.data_frame.sort(["time", "wave length"], SortMultipleOptions::default()).unwrap(),
+        out_of_bounds_cells: output.out_of_bounds_cells.sort(["time"], SortMultipleOptions::default()).unwrap(),
+    }
+}
+
+fn out_of_bounds_cells(output: &IntensityFlux) -> Vec<u32> {
+    output.out_of_bounds_cells.column("out of bounds cells").unwrap().u32().unwrap().into_iter().flatten().collect()
+}
+
+fn times(df: &DataFrame) -> Vec<f64> {
+    df.column("time").unwrap().f64().unwrap().into_iter().flatten().collect()
+}
+
+fn flux(df: &DataFrame) -> Vec<f64> {
+    df.column("flux").unwrap().f64().unwrap().into_iter().flatten().collect()
+}
+
+#[test]
+fn star_within_the_grids_has_no_out_of_bounds_cells() {
+    let output = run("Error", star(22642.0));
+    assert!(out_of_bounds_cells(&output).iter().all(|count| *count == 0));
+    // There is a count for every time point of the fluxes.
+    let mut flux_times = times(&output.data_frame);
+    flux_times.dedup();
+    assert_eq!(flux_times, times(&output.out_of_bounds_cells));
+}
+
+#[test]
+fn policies_count_the_same_cells() {
+    let hot_star = star(25800.0);
+    let clamped = run("Clamp", hot_star.clone());
+    let extrapolated = run("Extrapolate", hot_star.clone());
+    let skipped = run("Skip", hot_star);
+
+    let counts = out_of_bounds_cells(&clamped);
+    assert!(counts.iter().any(|count| *count > 0), "the hot star should leave the grids");
+    assert_eq!(counts, out_of_bounds_cells(&extrapolated));
+    assert_eq!(counts, out_of_bounds_cells(&skipped));
+
+    // Skipping cells removes their (positive) contribution to the flux.
+    let out_of_bounds_times = times(&clamped.out_of_bounds_cells);
+    let count_at = |time: f64| counts[out_of_bounds_times.iter().position(|t| *t == time).unwrap()];
+    let flux_times = times(&clamped.data_frame);
+    for ((skip, clamp), time) in flux(&skipped.data_frame).iter().zip(flux(&clamped.data_frame).iter()).zip(flux_times.iter()) {
+        if count_at(*time) > 0 {
+            assert!(skip < clamp, "skipped flux {} is not smaller than clamped flux {}", skip, clamp);
+        } else {
+            assert_eq!(skip, clamp);
+        }
+    }
+}
+
+#[test]
+fn error_policy_stops_the_run() {
+    assert!(profile_main(&profile_input("Error"), star(25800.0)).is_err());
+}
+
+fn read_parquet(path: &std::path::Path) -> DataFrame {
+    LazyFrame::scan_parquet(path, ScanArgsParquet::default()).unwrap().collect().unwrap()
+}
+
+#[test]
+fn out_of_bounds_cells_are_written_next_to_the_fluxes() {
+    let output = run("Clamp", star(25800.0));
+    let expected = output.out_of_bounds_cells.clone();
+    let config: ProfileConfig = toml::from_str(&profile_input("Clamp")).unwrap();
+    let mut fluxes = FluxOfSpectra::new(&config).unwrap();
+
+    // The files are written into the working directory.
+    let dir = std::env::temp_dir().join(format!("profile_out_of_bounds_output_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::env::set_current_dir(&dir).unwrap();
+
+    output.write_output(3).unwrap();
+    assert!(read_parquet(&output_path(OUT_OF_BOUNDS_OUTPUT, 3)).equals(&expected));
+    assert!(output_path(FLUX_OUTPUT, 3).exists());
+
+    // The time points written one by one are collected into the files of the last one.
+    for (time_point, (time, count)) in [(0.1, 4), (0.2, 0)].into_iter().enumerate() {
+        fluxes.time = vec![time; fluxes.wavelengths.len()];
+        fluxes.flux = vec![1.0; fluxes.wavelengths.len()];
+        fluxes.continuum = vec![1.0; fluxes.wavelengths.len()];
+        fluxes.out_of_bounds_cells = count;
+        fluxes.write_output(time_point as u16).unwrap();
+    }
+    assert!(!output_path(OUT_OF_BOUNDS_OUTPUT, 1).exists());
+    let written = read_parquet(&output_path(OUT_OF_BOUNDS_OUTPUT, 2));
+    assert_eq!(times(&written), vec![0.1, 0.2]);
+    assert_eq!(written.column("out of bounds cells").unwrap().u32().unwrap().into_iter().flatten().collect::<Vec<u32>>(), vec![4, 0]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,131 @@
+//! Tests for the compression of the intensity grids into principal components and the compressed backend.
+mod common;
+
+use common::{copy_golden_grids, star};
+use ndarray::Array2;
+use polars::prelude::*;
+use profile::error::ProfileError;
+use profile::profile_mkr::profile_main;
+use profile::utils::grid_cache::ParsedGrid;
+use profile::utils::pca_grid::{COMPRESSED_GRIDS_FILE, CompressedGrids};
+use profile::ProfileConfig;
+
+fn run(input: &str) -> DataFrame {
+    profile_main(input, star())
+        .unwrap()
+        .sort(["time", "wave length"], SortMultipleOptions::default())
+        .unwrap()
+}
+
+fn column(df: &DataFrame, name: &str) -> Vec<f64> {
+    df.column(name).unwrap().f64().unwrap().into_iter().flatten().collect()
+}
+
+/// Grids whose spectra are a continuum times an absorption line, both varying from grid to grid.
+/// The spectra minus their mean lie in the plane of a flat spectrum and the line, so two components reproduce them.
+fn line_family(ngrids: usize) -> Vec<ParsedGrid> {
+    let wavelengths: Vec<f64> = (0..61).map(|n| 413.7 + 0.005 * n as f64).collect();
+    let mu_values = vec![0.3, 0.6, 1.0];
+    (0..ngrids)
+        .map(|grid| {
+            let continuum = 1.0 + 0.1 * grid as f64;
+            let depth = 0.2 + 0.03 * (grid as f64).sqrt();
+            let values = Array2::from_shape_fn((wavelengths.len(), 2 * mu_values.len()), |(row, column)| {
+                let law = 0.4 + 0.6 * mu_values[column % mu_values.len()];
+                let line = (-0.5 * ((wavelengths[row] - 413.85) / 0.02).powi(2)).exp();
+                if column < mu_values.len() { law * continuum * (1.0 - depth * line) } else { law * continuum }
+            });
+            ParsedGrid { mu_values: mu_values.clone(), wavelengths: wavelengths.clone(), values }
+        })
+        .collect()
+}
+
+fn filenames(ngrids: usize) -> Vec<String> {
+    (0..ngrids).map(|grid| format!("grid{}.txt", grid)).collect()
+}
+
+#[test]
+fn principal_components_reproduce_the_grids() {
+    let grids = line_family(12);
+    let compressed = CompressedGrids::compress(filenames(12), &grids, 2).unwrap();
+    assert_eq!(compressed.ncomponents(), 2);
+    let error = compressed.largest_relative_error(&grids);
+    assert!(error < 1.0e-12, "{}", error);
+    for (n, grid) in grids.iter().enumerate() {
+        let reconstructed = compressed.reconstruct(n);
+        assert!(reconstructed.iter().zip(grid.values.iter()).all(|(a, b)| (a - b).abs() < 1.0e-12));
+    }
+    let one_component = CompressedGrids::compress(filenames(12), &grids, 1).unwrap();
+    assert!(one_component.largest_relative_error(&grids) > 1.0e-4);
+    // Not more components than grids are kept.
+    assert_eq!(CompressedGrids::compress(filenames(3), &grids[..3], 8).unwrap().ncomponents(), 3);
+
+    // The file holds the same grids.
+    let path = std::env::temp_dir().join(format!("profile_pca_{}.pca", std::process::id()));
+    compressed.write_to(&path).unwrap();
+    assert_eq!(CompressedGrids::read_from(&path).unwrap(), compressed);
+    std::fs::remove_file(path).unwrap();
+
+    // The selected grids and rows are numbered as the ones of an in-memory grid.
+    let rows = compressed.rows_within(413.8, 413.9);
+    assert_eq!(rows, 19..42);
+    let selected = compressed.select(&["grid7.txt", "grid2.txt"], rows.clone()).unwrap();
+    assert_eq!(selected.wavelengths, grids[0].wavelengths[rows.clone()].to_vec());
+    for (n, grid) in [7, 2].into_iter().enumerate() {
+        for row in 0..rows.len() {
+            assert_eq!(selected.value(n, row, 4), compressed.value(grid, rows.start + row, 4));
+        }
+    }
+}
+
+#[test]
+fn invalid_compressions_are_rejected() {
+    let mut grids = line_family(4);
+    assert!(matches!(CompressedGrids::compress(filenames(4), &grids, 0), Err(ProfileError::GridFormat(_))));
+    assert!(matches!(CompressedGrids::compress(Vec::new(), &[], 2), Err(ProfileError::GridFormat(_))));
+    let compressed = CompressedGrids::compress(filenames(4), &grids, 2).unwrap();
+    assert!(matches!(compressed.select(&["grid9.txt"], 0..2), Err(ProfileError::GridFormat(_))));
+    grids[2].wavelengths[10] += 1.0e-3;
+    assert!(matches!(CompressedGrids::compress(filenames(4), &grids, 2), Err(ProfileError::GridFormat(_))));
+
+    let path = std::env::temp_dir().join(format!("profile_pca_truncated_{}.pca", std::process::id()));
+    compressed.write_to(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    for broken in [&bytes[..bytes.len() - 8], &bytes[..30], &[b"PRFGRID2", &bytes[8..]].concat()[..]] {
+        std::fs::write(&path, broken).unwrap();
+        assert!(matches!(CompressedGrids::read_from(&path), Err(ProfileError::GridFormat(_))));
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn compressed_grids_give_the_same_fluxes() {
+    let dir = std::env::temp_dir().join(format!("profile_pca_grids_{}", std::process::id()));
+    let input = copy_golden_grids(&dir);
+    let from_csv = run(&input);
+
+    // Four grids are reproduced exactly by three components.
+    let config: ProfileConfig = toml::from_str(&input).unwrap();
+    let (path, error) = config.write_compressed_grids(3).unwrap();
+    assert_eq!(path, dir.join(COMPRESSED_GRIDS_FILE));
+    assert!(error < 1.0e-12, "{}", error);
+
+    // The grid files aren't needed anymore.
+    for grid in config.intensity_grids.iter() {
+        std::fs::remove_file(dir.join(grid.filename())).unwrap();
+    }
+    let compressed_input = String::from("grid_backend = \"Compressed\"\n") + &input;
+    let compressed_config: ProfileConfig = toml::from_str(&compressed_input).unwrap();
+    assert!(compressed_config.validate().is_valid());
+    assert!(!config.validate().is_valid());
+    let from_compressed = run(&compressed_input);
+    for name in ["flux", "continuum", "normalized flux"] {
+        for (a, b) in column(&from_csv, name).iter().zip(column(&from_compressed, name).iter()) {
+            assert!((a - b).abs() <= 1.0e-10 * a.abs().max(1.0e-300), "{}: {} {}", name, a, b);
+        }
+    }
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(!compressed_config.validate().is_valid());
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,150 @@
+//! Tests for the integration of the fluxes against the [IntensityProvider] trait, using a provider defined here.
+use polars::prelude::*;
+use profile::error::ProfileError;
+use profile::provider::{IntensityProvider, LocalAtmosphere};
+use profile::{FluxOfSpectra, ProfileConfig};
+use temp_name_lib::interpolation::BoundsHandling;
+use temp_name_lib::utils::MathErrors;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A provider with a continuum `I_c = µ` and a specific intensity `I = depth I_c`, defined up to a maximum temperature.
+/// It counts the evaluations, and its scratch the evaluations of each thread.
+struct LinearLimbDarkening {
+    depth: f64,
+    max_temperature: f64,
+    evaluations: AtomicUsize,
+}
+
+impl LinearLimbDarkening {
+    fn new(depth: f64, max_temperature: f64) -> Self {
+        Self { depth, max_temperature, evaluations: AtomicUsize::new(0) }
+    }
+}
+
+impl IntensityProvider for LinearLimbDarkening {
+    type Scratch = usize;
+
+    fn new_scratch(&self) -> usize {
+        0
+    }
+
+    fn covers(&self, atmosphere: &LocalAtmosphere) -> bool {
+        atmosphere.temperature <= self.max_temperature
+    }
+
+    fn intensities(
+        &self,
+        atmosphere: &LocalAtmosphere,
+        wavelengths: &[f64],
+        bounds_handling: BoundsHandling,
+        scratch: &mut usize,
+        specific: &mut [f64],
+        continuum: &mut [f64],
+    ) -> Result<(), MathErrors> {
+        if !self.covers(atmosphere) && bounds_handling == BoundsHandling::Error {
+            return Err(MathErrors::OutOfBounds);
+        }
+        *scratch += 1;
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        for n in 0..wavelengths.len() {
+            continuum[n] = atmosphere.mu;
+            specific[n] = self.depth * atmosphere.mu;
+        }
+        Ok(())
+    }
+}
+
+/// A static star (no velocities) seen pole-on, with cells at different µ values and temperatures.
+fn static_star() -> LazyFrame {
+    let coschi = vec![1.0, 0.9, 0.7, 0.5, 0.3, 0.1];
+    let ncells = coschi.len();
+    df!(
+        "time" => vec![0.0; ncells],
+        "velocity" => vec![0.0; ncells],
+        "area" => vec![0.2, 0.3, 0.4, 0.3, 0.2, 0.1],
+        "coschi" => coschi,
+        "temperature" => vec![22000.0, 21000.0, 20500.0, 20000.0, 19500.0, 19000.0],
+        "log gravity" => vec![4.0; ncells],
+    )
+    .unwrap()
+    .lazy()
+}
+
+fn fluxes(out_of_bounds: &str) -> FluxOfSpectra {
+    let input = format!(
+        "path_to_grids = \"./\"\nout_of_bounds = \"{}\"\n\n[wavelength_range]\nstart = 413.85\nend = 414.05\nstep = 0.01\n",
+        out_of_bounds
+    );
+    let config: ProfileConfig = toml::from_str(&input).unwrap();
+    FluxOfSpectra::new(&config).unwrap()
+}
+
+#[test]
+fn fluxes_are_integrated_with_any_provider() {
+    let provider = LinearLimbDarkening::new(0.5, 21500.0);
+    let mut fluxes = fluxes("Skip");
+    fluxes.integrate(static_star(), 0.0, &provider).unwrap();
+
+    // The hottest cell is skipped, the others add their area times µ.
+    let expected = 0.3 * 0.9 + 0.4 * 0.7 + 0.3 * 0.5 + 0.2 * 0.3 + 0.1 * 0.1;
+    assert_eq!((fluxes.out_of_bounds_cells, provider.evaluations.load(Ordering::Relaxed)), (1, 5));
+    for (flux, continuum) in fluxes.flux.iter().zip(fluxes.continuum.iter()) {
+        assert!((continuum - expected).abs() < 1.0e-12, "{}", continuum);
+        assert!((flux - 0.5 * expected).abs() < 1.0e-12, "{}", flux);
+    }
+
+    // The fluxes are restarted by the following time points.
+    fluxes.integrate(static_star(), 0.0, &provider).unwrap();
+    assert_eq!(provider.evaluations.load(Ordering::Relaxed), 10);
+    assert!(fluxes.continuum.iter().all(|continuum| (continuum - expected).abs() < 1.0e-12));
+}
+
+#[test]
+fn cells_outside_of_the_provider_are_an_error_by_default() {
+    let provider = LinearLimbDarkening::new(0.5, 21500.0);
+    assert!(matches!(
+        fluxes("Error").integrate(static_star(), 0.0, &provider),
+        Err(ProfileError::Math(MathErrors::OutOfBounds))
+    ));
+}
+
+/// A static star with many cells of different areas, µ values and temperatures.
+fn large_static_star(ncells: usize) -> LazyFrame {
+    let coschi: Vec<f64> = (0..ncells).map(|n| 1.0 - n as f64 / ncells as f64).collect();
+    df!(
+        "time" => vec![0.0; ncells],
+        "velocity" => vec![0.0; ncells],
+        "area" => (0..ncells).map(|n| 1.0e-3 * (1.0 + (n as f64).sin().powi(2))).collect::<Vec<f64>>(),
+        "coschi" => coschi,
+        "temperature" => (0..ncells).map(|n| 19000.0 + (n % 7) as f64 * 500.0).collect::<Vec<f64>>(),
+        "log gravity" => vec![4.0; ncells],
+    )
+    .unwrap()
+    .lazy()
+}
+
+#[test]
+fn fluxes_do_not_depend_on_the_number_of_threads() {
+    let provider = LinearLimbDarkening::new(0.5, 21500.0);
+    let integrate = |threads: usize| {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let mut fluxes = fluxes("Skip");
+        pool.install(|| fluxes.integrate(large_static_star(20000), 0.0, &provider)).unwrap();
+        fluxes
+    };
+    let single = integrate(1);
+    for threads in [2, 5] {
+        let parallel = integrate(threads);
+        assert_eq!(parallel.flux, single.flux);
+        assert_eq!(parallel.continuum, single.continuum);
+        assert_eq!(parallel.out_of_bounds_cells, single.out_of_bounds_cells);
+    }
+
+    // All of the cells are visible, the ones hotter than the provider are skipped and the others are integrated once.
+    let expected: f64 = (0..20000)
+        .filter(|n| n % 7 != 6)
+        .map(|n| 1.0e-3 * (1.0 + (n as f64).sin().powi(2)) * (1.0 - n as f64 / 20000.0))
+        .sum();
+    assert_eq!(single.out_of_bounds_cells as usize, (0..20000).filter(|n| n % 7 == 6).count());
+    assert!((single.continuum[0] - expected).abs() < 1.0e-9 * expected, "{} {}", single.continuum[0], expected);
+}
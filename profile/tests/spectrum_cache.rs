@@ -0,0 +1,215 @@
+//! Tests for the [SpectrumCache], which evaluates a provider once for the surface cells with the same atmosphere up to a tolerance.
+use polars::prelude::*;
+use profile::error::ProfileError;
+use profile::profile_mkr::profile_main;
+use profile::provider::{IntensityProvider, LocalAtmosphere};
+use profile::spectrum_cache::{DEFAULT_CAPACITY, DEFAULT_MU_TOLERANCE, SpectrumCache, SpectrumCacheConfig};
+use profile::{FluxOfSpectra, ProfileConfig};
+use pulstar::validation::Severity;
+use temp_name_lib::interpolation::BoundsHandling;
+use temp_name_lib::utils::MathErrors;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A provider with a continuum `I_c = µ T λ / 10^7` and a specific intensity `I = depth I_c`, defined up to a maximum temperature.
+/// The intensities are linear in the wavelength, so they're resampled exactly. It counts the evaluations.
+struct LinearContinuum {
+    depth: f64,
+    max_temperature: f64,
+    evaluations: AtomicUsize,
+}
+
+impl LinearContinuum {
+    fn new(depth: f64, max_temperature: f64) -> Self {
+        Self { depth, max_temperature, evaluations: AtomicUsize::new(0) }
+    }
+}
+
+impl IntensityProvider for LinearContinuum {
+    type Scratch = ();
+
+    fn new_scratch(&self) {}
+
+    fn covers(&self, atmosphere: &LocalAtmosphere) -> bool {
+        atmosphere.temperature <= self.max_temperature
+    }
+
+    fn intensities(
+        &self,
+        atmosphere: &LocalAtmosphere,
+        wavelengths: &[f64],
+        bounds_handling: BoundsHandling,
+        _scratch: &mut (),
+        specific: &mut [f64],
+        continuum: &mut [f64],
+    ) -> Result<(), MathErrors> {
+        if !self.covers(atmosphere) && bounds_handling == BoundsHandling::Error {
+            return Err(MathErrors::OutOfBounds);
+        }
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        for (n, wavelength) in wavelengths.iter().enumerate() {
+            continuum[n] = atmosphere.mu * atmosphere.temperature * wavelength * 1.0e-7;
+            specific[n] = self.depth * continuum[n];
+        }
+        Ok(())
+    }
+}
+
+fn observed_wavelengths() -> Vec<f64> {
+    (0..=20).map(|n| 413.85 + 0.01 * n as f64).collect()
+}
+
+fn atmosphere(temperature: f64, mu: f64) -> LocalAtmosphere {
+    LocalAtmosphere { temperature, log_gravity: 4.0, metallicity: None, mu }
+}
+
+#[test]
+fn atmospheres_within_the_tolerances_share_their_spectrum() {
+    let config: SpectrumCacheConfig = toml::from_str("temperature = 10.0").unwrap();
+    assert_eq!((config.mu, config.wavelength_step, config.capacity), (DEFAULT_MU_TOLERANCE, None, DEFAULT_CAPACITY));
+    assert!(config.check().is_empty());
+
+    let provider = LinearContinuum::new(0.5, 21500.0);
+    let cache = SpectrumCache::new(&provider, config, &observed_wavelengths(), (0.9999, 1.0001)).unwrap();
+    assert!(cache.is_empty());
+    let shifted: Vec<f64> = observed_wavelengths().iter().map(|wavelength| wavelength * 1.00004).collect();
+    let mut scratch = cache.new_scratch();
+    let evaluate = |atmosphere: LocalAtmosphere, scratch: &mut _| {
+        let (mut specific, mut continuum) = (vec![0.0; shifted.len()], vec![0.0; shifted.len()]);
+        cache.intensities(&atmosphere, &shifted, BoundsHandling::Error, scratch, &mut specific, &mut continuum).map(|_| (specific, continuum))
+    };
+
+    // Both atmospheres round to T_eff = 20000 K and µ = 0.5, which is the one evaluated.
+    let first = evaluate(atmosphere(20003.0, 0.5004), &mut scratch).unwrap();
+    let second = evaluate(atmosphere(19996.0, 0.4996), &mut scratch).unwrap();
+    assert_eq!(first, second);
+    assert_eq!((provider.evaluations.load(Ordering::Relaxed), cache.len()), (1, 1));
+    assert_eq!(config.quantised(&atmosphere(20003.0, 0.5004)), atmosphere(20000.0, 0.5));
+    for (n, wavelength) in shifted.iter().enumerate() {
+        let expected = 0.5 * 20000.0 * wavelength * 1.0e-7;
+        assert!((first.1[n] - expected).abs() < 1.0e-12 * expected, "{} nm: {} {}", wavelength, first.1[n], expected);
+        assert!((first.0[n] - 0.5 * expected).abs() < 1.0e-12 * expected);
+    }
+
+    // Another temperature is evaluated on its own, and the wavelengths must lie within the Doppler shifts of the star.
+    evaluate(atmosphere(20010.0, 0.5), &mut scratch).unwrap();
+    assert_eq!((provider.evaluations.load(Ordering::Relaxed), cache.len()), (2, 2));
+    let far: Vec<f64> = observed_wavelengths().iter().map(|wavelength| wavelength * 1.001).collect();
+    let (mut specific, mut continuum) = (vec![0.0; far.len()], vec![0.0; far.len()]);
+    assert!(matches!(
+        cache.intensities(&atmosphere(20000.0, 0.5), &far, BoundsHandling::Error, &mut scratch, &mut specific, &mut continuum),
+        Err(MathErrors::OutOfBounds)
+    ));
+
+    // The cells are covered if their rounded atmospheres are.
+    assert!(cache.covers(&atmosphere(21504.0, 0.5)));
+    assert!(!cache.covers(&atmosphere(21506.0, 0.5)));
+}
+
+#[test]
+fn spectra_beyond_the_capacity_are_not_kept() {
+    let config = SpectrumCacheConfig { capacity: 2, ..Default::default() };
+    let provider = LinearContinuum::new(0.5, 30000.0);
+    let cache = SpectrumCache::new(&provider, config, &observed_wavelengths(), (1.0, 1.0)).unwrap();
+    let wavelengths = observed_wavelengths();
+    let mut scratch = cache.new_scratch();
+    let (mut specific, mut continuum) = (vec![0.0; wavelengths.len()], vec![0.0; wavelengths.len()]);
+    for _ in 0..2 {
+        for temperature in [19000.0, 20000.0, 21000.0] {
+            cache.intensities(&atmosphere(temperature, 0.5), &wavelengths, BoundsHandling::Error, &mut scratch, &mut specific, &mut continuum).unwrap();
+        }
+    }
+    assert_eq!((provider.evaluations.load(Ordering::Relaxed), cache.len()), (4, 2));
+}
+
+/// A star with many cells of different areas, µ values, temperatures and velocities.
+fn moving_star(ncells: usize) -> DataFrame {
+    df!(
+        "time" => vec![0.0; ncells],
+        "velocity" => (0..ncells).map(|n| 30.0 * (n as f64 * 0.37).sin()).collect::<Vec<f64>>(),
+        "area" => (0..ncells).map(|n| 1.0e-3 * (1.0 + (n as f64).sin().powi(2))).collect::<Vec<f64>>(),
+        "coschi" => (0..ncells).map(|n| 1.0 - n as f64 / ncells as f64).collect::<Vec<f64>>(),
+        "temperature" => (0..ncells).map(|n| 19000.0 + (n % 7) as f64 * 500.0).collect::<Vec<f64>>(),
+        "log gravity" => vec![4.0; ncells],
+    )
+    .unwrap()
+}
+
+fn fluxes(spectrum_cache: &str) -> FluxOfSpectra {
+    let input = format!(
+        "path_to_grids = \"./\"\nout_of_bounds = \"Skip\"\n{}\n\n[wavelength_range]\nstart = 413.85\nend = 414.05\nstep = 0.01\n",
+        spectrum_cache
+    );
+    let config: ProfileConfig = toml::from_str(&input).unwrap();
+    FluxOfSpectra::new(&config).unwrap()
+}
+
+#[test]
+fn cached_fluxes_do_not_depend_on_the_number_of_threads() {
+    let provider = LinearContinuum::new(0.5, 21500.0);
+    let star = moving_star(20000).lazy();
+    let integrate = |threads: usize| {
+        let cache = SpectrumCache::new(&provider, SpectrumCacheConfig::default(), &observed_wavelengths(), (0.9998, 1.0002)).unwrap();
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let mut fluxes = fluxes("");
+        pool.install(|| fluxes.integrate(star.clone(), 0.0, &cache)).unwrap();
+        (fluxes, cache.len())
+    };
+    let (single, cached) = integrate(1);
+    // A single thread evaluates every rounded atmosphere once.
+    assert_eq!(provider.evaluations.load(Ordering::Relaxed), cached);
+    for threads in [2, 5] {
+        let (parallel, _) = integrate(threads);
+        assert_eq!(parallel.flux, single.flux);
+        assert_eq!(parallel.continuum, single.continuum);
+    }
+
+    // The cells share 6 temperatures within the provider and at most 1001 µ values.
+    assert!(cached <= 6 * 1001, "{} spectra cached", cached);
+    let mut direct = fluxes("");
+    direct.integrate(star, 0.0, &provider).unwrap();
+    assert_eq!(direct.out_of_bounds_cells, single.out_of_bounds_cells);
+    // The continuum is proportional to µ, which is rounded by at most half of its tolerance.
+    for (cached, direct) in single.continuum.iter().zip(direct.continuum.iter()) {
+        assert!((cached - direct).abs() < 1.0e-4 * direct, "{} {}", cached, direct);
+    }
+}
+
+fn profile_input(spectrum_cache: &str) -> String {
+    format!(
+        "path_to_grids = \"{}/\"\n{}\n\n[wavelength_range]\nstart = 413.85\nend = 414.05\nstep = 0.01\n\n[[intensity_grids]]\nAnalytic = {}\n",
+        std::env::temp_dir().display(),
+        spectrum_cache,
+        "{law = \"Quadratic\", coefficients = {Fixed = [0.3, 0.2]}, line = {center = 413.95, profile = {Gaussian = {sigma = 0.02}}, strength = {Depth = 0.6}}}"
+    )
+}
+
+fn normalized_flux(df: &DataFrame) -> Vec<f64> {
+    df.sort(["time", "wave length"], SortMultipleOptions::default())
+        .unwrap()
+        .column("normalized flux")
+        .unwrap()
+        .f64()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[test]
+fn cached_profile_agrees_with_the_direct_evaluation() {
+    let direct = normalized_flux(&profile_main(&profile_input(""), moving_star(5000)).unwrap());
+    let cached = normalized_flux(&profile_main(&profile_input("spectrum_cache = {wavelength_step = 0.0005}"), moving_star(5000)).unwrap());
+    assert_eq!(direct.len(), cached.len());
+    for (direct, cached) in direct.iter().zip(cached.iter()) {
+        assert!((direct - cached).abs() < 1.0e-4, "{} {}", direct, cached);
+    }
+}
+
+#[test]
+fn invalid_tolerances_are_rejected() {
+    let input = profile_input("spectrum_cache = {mu = 0.0, wavelength_step = -0.01}");
+    let config: ProfileConfig = toml::from_str(&input).unwrap();
+    let report = config.validate();
+    assert_eq!(report.count(Severity::Error), 2, "{}", report);
+    assert!(matches!(profile_main(&input, moving_star(10)), Err(ProfileError::Validation(_))));
+}
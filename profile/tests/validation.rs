@@ -0,0 +1,81 @@
+//! Tests for [ProfileConfig::validate], [validate_run] and the preflight check of the star
+//! against the intensity grids, using the golden fixtures.
+mod common;
+
+use common::{fixture_dir, pulstar_input};
+use profile::ProfileConfig;
+use profile::error::ProfileError;
+use profile::profile_mkr::profile_main;
+use profile::validation::{suggest_grid_box, validate_run};
+use pulstar::pulstar_mkr::pulstar_main;
+use pulstar::validation::{ParameterBox, Severity};
+use pulstar::{ParsingFromToml, PulstarConfig};
+
+fn profile_input() -> String {
+    common::profile_input(&fixture_dir().join("grids"))
+}
+
+fn pulstar_config(input: &str) -> PulstarConfig {
+    PulstarConfig::read_from_toml(input).unwrap()
+}
+
+#[test]
+fn golden_run_is_valid() {
+    let profile_config: ProfileConfig = toml::from_str(&profile_input()).unwrap();
+    let report = validate_run(&profile_config, &pulstar_config(&pulstar_input()));
+    assert!(report.is_valid(), "{}", report);
+}
+
+#[test]
+fn profile_problems_are_reported_at_once() {
+    let input = profile_input()
+        .replace("end = 414.05", "end = 413.0")
+        .replace("t26000g45.txt", "t26000g50.txt");
+    let profile_config: ProfileConfig = toml::from_str(&input).unwrap();
+    let report = profile_config.validate();
+    assert_eq!(report.count(Severity::Error), 2, "{}", report);
+    assert!(report.to_string().contains("t26000g50.txt"), "{}", report);
+}
+
+#[test]
+fn star_outside_of_the_grids_is_reported() {
+    let profile_config: ProfileConfig = toml::from_str(&profile_input()).unwrap();
+    let hot_star = pulstar_input().replace("effective_temperature = 22642.0", "effective_temperature = 25800.0");
+    let report = validate_run(&profile_config, &pulstar_config(&hot_star));
+    assert!(!report.is_valid());
+    assert!(report.to_string().contains("the intensity grids cover"), "{}", report);
+}
+
+#[test]
+fn profile_refuses_a_star_outside_of_the_grids() {
+    let hot_star = pulstar_input().replace("effective_temperature = 22642.0", "effective_temperature = 25800.0");
+    let star_df = pulstar_main(&hot_star).unwrap();
+    match profile_main(&profile_input(), star_df) {
+        Err(ProfileError::GridCoverage { star, grids }) => {
+            assert!(star.temperature[1] > 26000.0, "{}", star);
+            assert_eq!(grids.temperature, [20000.0, 26000.0]);
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("profile integrated a star outside of the grids"),
+    }
+}
+
+#[test]
+fn suggested_grids_lie_on_the_same_lattice() {
+    let grids = ParameterBox { temperature: [20000.0, 26000.0], log_gravity: [3.5, 4.5] };
+    let star = ParameterBox { temperature: [19000.0, 26500.0], log_gravity: [3.6, 4.4] };
+    let suggestion = suggest_grid_box(&star, &grids);
+    assert_eq!(suggestion.temperature, [14000.0, 32000.0]);
+    assert_eq!(suggestion.log_gravity, [3.5, 4.5]);
+    assert!(suggestion.contains(&star));
+}
+
+#[test]
+fn metallicity_of_the_star_is_ignored_by_grids_without_one() {
+    let profile_config: ProfileConfig = toml::from_str(&profile_input()).unwrap();
+    let star = pulstar_input().replace("inclination_angle = 45.0", "inclination_angle = 45.0\nmetallicity = -0.3");
+    let report = validate_run(&profile_config, &pulstar_config(&star));
+    assert!(report.is_valid(), "{}", report);
+    assert_eq!(report.count(Severity::Warning), 1, "{}", report);
+    assert!(report.to_string().contains("metallicity"), "{}", report);
+}
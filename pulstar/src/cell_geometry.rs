@@ -0,0 +1,105 @@
+use crate::{PulstarConfig, PulsationMode, na};
+use crate::reference_frames::Coordinates;
+use temp_name_lib::math_module::spherical_harmonics::plmcos::plmcos;
+use temp_name_lib::math_module::spherical_harmonics::d_plmcos_dtheta::{deriv1_plmcos_dtheta, deriv2_plmcos_dtheta};
+use temp_name_lib::math_module::spherical_harmonics::norm_factor::ylmnorm;
+
+/// The values of a [PulsationMode] over a surface cell that don't depend on time: the associated Legendre function
+/// P_l^|m|(cos θ), its derivatives, the normalization factor `Y_l^m` and the azimuthal term m·φ.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ModeGeometry{
+    /// The degree of the mode.
+    pub l: u16,
+    /// The azimuthal order of the mode.
+    pub m: i16,
+    /// P_l^|m|(cos θ).
+    pub plm: f64,
+    /// The derivative of P_l^|m|(cos θ) with respect to θ used by the Lagrangian displacement, see [crate::reference_frames::displacement].
+    pub dplm_displacement: f64,
+    /// The first derivative of P_l^|m|(cos θ) with respect to θ.
+    pub dplm_dtheta: f64,
+    /// The second derivative of P_l^|m|(cos θ) with respect to θ.
+    pub d2plm_dtheta: f64,
+    /// The normalization factor `Y_l^m`.
+    pub ylmnorm: f64,
+    /// The azimuthal term m·φ of the phase.
+    pub m_phi: f64,
+}
+
+/// The values of a surface cell that don't depend on time. They're computed once when the star is rasterized,
+/// so that computing a frame only requires the sine and cosine of the phase of each mode (see [ModeGeometry::phase_terms]).
+#[derive(Debug,Clone,PartialEq)]
+pub struct CellGeometry{
+    /// The sine of the colatitude angle θ.
+    pub sintheta: f64,
+    /// The cosine of the colatitude angle θ.
+    pub costheta: f64,
+    /// The azimuthal angle φ in radians.
+    pub phi_rad: f64,
+    /// The transformation matrix from spherical to cartesian coordinates at (θ,φ), see [Coordinates::transform_with].
+    pub t_matrix: na::Matrix3<f64>,
+    /// The [ModeGeometry] of each [PulsationMode], in the order of the modes of the configuration.
+    pub modes: Vec<ModeGeometry>,
+}
+
+impl ModeGeometry{
+    /// Computes the time-independent values of a pulsation mode over a surface cell.
+    ///
+    /// ### Arguments:
+    /// * `mode` - the [PulsationMode], only its degree and azimuthal order are used.
+    /// * `sintheta` - sine of the colatitude angle (theta in rads)
+    /// * `costheta` - cosine of the colatitude angle (theta in rads)
+    /// * `phi_rad` - azimuthal coordinate in rads
+    pub fn new(mode:&PulsationMode, sintheta:f64, costheta:f64, phi_rad:f64)->Self{
+        let l = mode.l;
+        let m = mode.m;
+        let plm = plmcos(l, m.unsigned_abs(), sintheta, costheta);
+        let dplm_displacement = (- f64::from(l+1) * costheta * plm
+                                + f64::from((l as i16) - m + 1)
+                                * plmcos(l+1, m.unsigned_abs(), sintheta, costheta))
+                                / sintheta;
+        ModeGeometry{
+            l,
+            m,
+            plm,
+            dplm_displacement,
+            dplm_dtheta: deriv1_plmcos_dtheta(l, m.unsigned_abs(), sintheta, costheta),
+            d2plm_dtheta: deriv2_plmcos_dtheta(l, m.unsigned_abs(), sintheta, costheta),
+            ylmnorm: ylmnorm(l, m),
+            m_phi: f64::from(m) * phi_rad,
+        }
+    }
+
+    /// Returns the sine and cosine of the phase `phase + m·φ` of the mode over the cell.
+    pub fn phase_terms(&self, phase:f64)->(f64,f64){
+        (phase + self.m_phi).sin_cos()
+    }
+}
+
+impl CellGeometry{
+    /// Computes the time-independent values of a surface cell for the [PulsationMode]s of a configuration.
+    ///
+    /// ### Arguments:
+    /// * `parameters` - The data contained in [PulstarConfig], here you find the parameters that describe the pulsation modes and the star.
+    /// * `theta_rad` - The colatitude angle in rads
+    /// * `phi_rad` - The azimuthal angle in rads
+    pub fn new(parameters:&PulstarConfig, theta_rad:f64, phi_rad:f64)->Self{
+        let sintheta = theta_rad.sin();
+        let costheta = theta_rad.cos();
+        CellGeometry{
+            sintheta,
+            costheta,
+            phi_rad,
+            t_matrix: Coordinates::transformation_matrix(theta_rad, phi_rad),
+            modes: parameters.mode_data.iter()
+                .map(|mode| ModeGeometry::new(mode, sintheta, costheta, phi_rad))
+                .collect(),
+        }
+    }
+
+    /// Returns `true` if the geometry was computed for [PulsationMode]s with the same degrees and azimuthal orders as the ones of `parameters`.
+    pub fn matches(&self, parameters:&PulstarConfig)->bool{
+        self.modes.len() == parameters.mode_data.len()
+            && self.modes.iter().zip(parameters.mode_data.iter()).all(|(geometry,mode)| geometry.l == mode.l && geometry.m == mode.m)
+    }
+}
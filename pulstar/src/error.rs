@@ -0,0 +1,64 @@
+use polars::prelude::PolarsError;
+use temp_name_lib::utils::MathErrors;
+use crate::validation::ValidationReport;
+
+/// Errors that can be returned by the pulstar library.
+///
+/// The variants wrap the errors of the crates used by pulstar so that the calling program
+/// (e.g. the `pulstar` binary or `pulstar_py`) decides what to do with them instead of panicking.
+#[derive(Debug)]
+pub enum PulstarError{
+    /// A mathematical error, e.g. a surface cell too close to the poles.
+    Math(MathErrors),
+    /// An error produced while building or writing the rasterized star [polars::prelude::DataFrame].
+    Polars(PolarsError),
+    /// An error reading or writing a file.
+    Io(std::io::Error),
+    /// The toml configuration could not be parsed.
+    Toml(toml::de::Error),
+    /// The configuration was parsed but it does not describe a valid run.
+    InvalidConfig(String),
+    /// The configuration didn't pass [crate::PulstarConfig::validate], the report holds all of the problems found.
+    Validation(ValidationReport),
+}
+
+impl std::fmt::Display for PulstarError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            PulstarError::Math(e) => write!(f, "math error: {}", e),
+            PulstarError::Polars(e) => write!(f, "polars error: {}", e),
+            PulstarError::Io(e) => write!(f, "io error: {}", e),
+            PulstarError::Toml(e) => write!(f, "unable to parse the pulstar configuration: {}", e),
+            PulstarError::InvalidConfig(message) => write!(f, "invalid pulstar configuration: {}", message),
+            PulstarError::Validation(report) => write!(f, "invalid pulstar configuration, {}", report),
+        }
+    }
+}
+
+impl std::error::Error for PulstarError{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self{
+            PulstarError::Math(e) => Some(e),
+            PulstarError::Polars(e) => Some(e),
+            PulstarError::Io(e) => Some(e),
+            PulstarError::Toml(e) => Some(e),
+            PulstarError::InvalidConfig(_) | PulstarError::Validation(_) => None,
+        }
+    }
+}
+
+impl From<MathErrors> for PulstarError{
+    fn from(e: MathErrors) -> Self { PulstarError::Math(e) }
+}
+
+impl From<PolarsError> for PulstarError{
+    fn from(e: PolarsError) -> Self { PulstarError::Polars(e) }
+}
+
+impl From<std::io::Error> for PulstarError{
+    fn from(e: std::io::Error) -> Self { PulstarError::Io(e) }
+}
+
+impl From<toml::de::Error> for PulstarError{
+    fn from(e: toml::de::Error) -> Self { PulstarError::Toml(e) }
+}
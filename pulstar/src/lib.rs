@@ -2,17 +2,26 @@
 //! the (linear) variations on surface temperature, log g, and also the pulsation velocity components.
 //! for each of the surface cells. 
 use serde::Deserialize;
-use temp_name_lib::math_module::spherical_harmonics;
 use temp_name_lib::utils::{MathErrors,MACHINE_PRECISION};
 use temp_name_lib::type_def::PI;
 use nalgebra as na;
+use std::sync::Arc;
 
 use crate::local_pulsation_velocity::observed_pulsation_velocity;
 use crate::local_temperature_and_gravity::local_surface_temperature_logg;
 use crate::reference_frames::{surface_normal, Coordinates};
+use crate::cell_geometry::CellGeometry;
 
 pub mod pulstar_mkr;
 
+/// This module contains the [error::PulstarError] type returned by the public entry points of the library.
+pub mod error;
+use crate::error::PulstarError;
+
+/// This module contains the checks performed on a [PulstarConfig] before rasterizing the star,
+/// and the [validation::ValidationReport] used to collect all of the problems found.
+pub mod validation;
+
 /// This structure is necessary for starting the program. 
 /// It contains `mode_data` which is a [Vec] collection of the pulsation modes to be implemented, the `star_data` that characterizes the star, and the `time points` to be simulated. 
 /// 
@@ -39,7 +48,7 @@ pub mod pulstar_mkr;
 /// * a vector of all of the oscillation phases to be created. 
 /// It could be provided as an `Explicit` collection where the individual terms are posted explicitly 
 /// or as a `Uniform` collection where the array is characterized by a beggining, an end, and the number of time points. 
-#[derive(Deserialize,Debug,PartialEq)]
+#[derive(Deserialize,Debug,PartialEq,Clone)]
 pub struct PulstarConfig{
     /// A vector collection of all of the modes that will be analyzed
     pub mode_data:Vec<PulsationMode>,
@@ -55,7 +64,7 @@ pub struct PulstarConfig{
 }
 
 /// This structure parameterizes a pulsation mode
-#[derive(Deserialize,Debug,PartialEq)]
+#[derive(Deserialize,Debug,PartialEq,Clone)]
 pub struct PulsationMode{
     /// The degree of the mode
     pub l: u16, 
@@ -98,7 +107,7 @@ pub struct PulsationMode{
 }   
 
 /// This structure parameterizes the star
-#[derive(Deserialize,Debug,PartialEq)]
+#[derive(Deserialize,Debug,PartialEq,Clone)]
 pub struct StarData{
     /// The mass of the star in solar units
     pub mass: f64,
@@ -114,6 +123,11 @@ pub struct StarData{
 
     /// The inclination angle in degrees
     pub inclination_angle: f64,
+
+    /// The metallicity [M/H] in dex. It's optional, when given it's written for every surface cell
+    /// so that profile can interpolate the intensity grids in metallicity.
+    #[serde(default)]
+    pub metallicity: Option<f64>,
 }
 
 #[derive(Deserialize,Debug,PartialEq,Clone)]
@@ -122,7 +136,7 @@ pub enum  TimeType{
     Uniform{ start:f64, end:f64, step:f64}
 }
 
-#[derive(Deserialize,Debug,PartialEq)]
+#[derive(Deserialize,Debug,PartialEq,Clone)]
 pub enum MeshConfig{
     Sphere{theta_step:f64,
            phi_step:f64},
@@ -130,6 +144,7 @@ pub enum MeshConfig{
 }
 
 /// This structure holds the local quantities over a surface element of the star. 
+#[derive(Clone)]
 pub struct SurfaceCell{
     /// Effective temperature.
     t_eff: f64,
@@ -150,15 +165,20 @@ pub struct SurfaceCell{
 }
 
 /// Discretized version of the star. 
+#[derive(Clone)]
 pub struct RasterizedStar{
     /// A [Vec] collection  that contains the [SurfaceCell]s of the star excluding the poles. 
     cells: Vec<SurfaceCell>,
+    /// The [CellGeometry] of each of the `cells`, shared by all of the frames of the star.
+    geometry: Arc<Vec<CellGeometry>>,
     /// A [f64] value that holds the current phase of pulsation to be analized. 
     time_stamp: f64,
     /// The effective temperature of the star. 
     pub t_eff: f64,
     /// The surface gravity of the star. 
     pub g_0: f64,
+    /// The metallicity [M/H] of the star, if it was given.
+    pub metallicity: Option<f64>,
 }
 
 //----------------------------------------
@@ -197,6 +217,7 @@ impl PulstarConfig {
         let mut rasterized_star = RasterizedStar::new();
 
         // Set a mesh on the star depending on the selected geometry
+        let mut geometry = Vec::new();
         match self.mesh {
             // On the spherical case we will be using equally spaced cells on (θ,φ)
             MeshConfig::Sphere { theta_step, phi_step } =>{
@@ -205,6 +226,7 @@ impl PulstarConfig {
                 while theta < 180.0{
                     while phi < 360.0{                        
                         rasterized_star.cells.push(SurfaceCell::new(theta.to_radians(), phi.to_radians()));
+                        geometry.push(CellGeometry::new(self, theta.to_radians(), phi.to_radians()));
                         phi += phi_step;
                     }
                     phi = 1.0;
@@ -212,35 +234,70 @@ impl PulstarConfig {
                 }
             }   
         }
+        rasterized_star.geometry = Arc::new(geometry);
 
         //--Equilibrium log(g_0) (gravity g_0 is in cgs units)
-        //--Mass & radius are in solar units
-        let log_g0 = 4.438 + self.star_data.mass.log10()
-            - 2.0 * self.star_data.radius.log10();
+        let log_g0 = self.equilibrium_log_g();
         
         rasterized_star.g_0 = 10.0_f64.powf(log_g0);
         rasterized_star.t_eff = self.star_data.effective_temperature;
+        rasterized_star.metallicity = self.star_data.metallicity;
 
         rasterized_star
     }
+
+    /// Returns a copy of the configuration with the phases of the [PulsationMode]s at a time point, see [AdvanceInTime].
+    pub fn at_time(&self, time_point:f64)->PulstarConfig{
+        let mut parameters = self.clone();
+        parameters.advance_in_time(time_point);
+        parameters
+    }
 }
 
 impl RasterizedStar{
     /// Creates a new instance of a [RasterizedStar], setting all the member values to zero  and an empty [Vec<SurfaceCell>].
     fn new()->Self{
-        RasterizedStar{ cells: Vec::new(), time_stamp: 0.0, t_eff:0.0, g_0:0.0 }
+        RasterizedStar{ cells: Vec::new(), geometry: Arc::new(Vec::new()), time_stamp: 0.0, t_eff:0.0, g_0:0.0, metallicity: None }
     }
 
+    /// Computes the local quantities of every [SurfaceCell] of the star for the current phase of the [PulsationMode]s.
+    /// The time-independent values of the cells are taken from their [CellGeometry], which is computed again
+    /// if the star was rasterized with pulsation modes of different degrees or azimuthal orders than the ones of `parameters`.
+    ///
+    /// ### Returns:
+    /// * `Err(MathErrors)` - if the local quantities of one of the cells could not be computed, e.g. because it's too close to the poles.
     pub fn compute_local_quantities(&mut self,
         parameters:&PulstarConfig,
-        k: &Coordinates){
-        for cell in self.cells.iter_mut(){
-            cell.update_local_quantities(parameters, k,
+        k: &Coordinates)->Result<(),MathErrors>{
+        if self.geometry.first().is_some_and(|geometry| !geometry.matches(parameters)){
+            self.geometry = Arc::new(self.cells.iter()
+                .map(|cell| CellGeometry::new(parameters, cell.coord_1, cell.coord_2))
+                .collect());
+        }
+        for (cell,geometry) in self.cells.iter_mut().zip(self.geometry.iter()){
+            cell.update_local_quantities(parameters, geometry, k,
                  self.t_eff,
-                  self.g_0);
+                  self.g_0)?;
         }
+        Ok(())
     }
 
+    /// This function computes the local quantities of the star at a time point, i.e. a frame of the run, without modifying the star
+    /// or the configuration. The frames of different time points can then be computed in any order or in parallel.
+    ///
+    /// ### Arguments:
+    /// * `parameters` - the configuration of the run, the phases of its [PulsationMode]s are set for the time point (see [PulstarConfig::at_time]).
+    /// * `time_point` - the time of the frame.
+    /// * `k` - the unit vector pointing towards the observer.
+    /// ### Returns:
+    /// * `Ok(RasterizedStar)` - a copy of the star with the local quantities of the time point.
+    /// * `Err(MathErrors)` - if the local quantities of one of the cells could not be computed, see [RasterizedStar::compute_local_quantities].
+    pub fn frame(&self, parameters:&PulstarConfig, time_point:f64, k:&Coordinates)->Result<RasterizedStar,MathErrors>{
+        let mut frame = self.clone();
+        frame.advance_in_time(time_point);
+        frame.compute_local_quantities(&parameters.at_time(time_point), k)?;
+        Ok(frame)
+    }
 }
 impl SurfaceCell{
     /// Creates a new instance of a [SurfaceCell] setting the coordinates of the barycenter.
@@ -269,22 +326,24 @@ impl SurfaceCell{
     /// 
     /// ### Arguments: 
     /// * `parameters` - A [PulstarConfig] reference that contains the parameters that describe the [PulsationMode]s
+    /// * `geometry` - The [CellGeometry] of the cell, computed for the [PulsationMode]s of `parameters`.
     /// * `k` - A [Coordinates] reference to the unit vector pointing towards the observer. 
     /// * `temperature_0` - A [f64] value of the effective temperature of the star.
     /// * `g0` - A [f64] value of the surface gravity of the star. 
     /// 
     /// ### Returns: 
-    /// * This method updates a mutable instance of a [SurfaceCell].
+    /// * This method updates a mutable instance of a [SurfaceCell]. It returns an [Err] ([MathErrors]) variant
+    /// if the cell is too close to the poles for the surface normal or the pulsation velocity to be computed.
     /// 
-    fn update_local_quantities(&mut self,parameters:& PulstarConfig, k:& Coordinates, temperature_0:f64, g0:f64){
+    fn update_local_quantities(&mut self,parameters:& PulstarConfig, geometry:&CellGeometry, k:& Coordinates, temperature_0:f64, g0:f64)->Result<(),MathErrors>{
         //Select the type of geometry
         match parameters.mesh{
             MeshConfig::Sphere {..} => {
                 let theta = self.coord_1;
                 let phi = self.coord_2;
-                let k_spherical = k.transform(theta, phi);
+                let k_spherical = k.transform_with(&geometry.t_matrix);
                 let s_normal = surface_normal(parameters,
-                     theta, phi).unwrap();
+                     geometry)?;
                 let cos_chi = reference_frames::cos_chi(
                     &s_normal,
                    &k_spherical,
@@ -292,15 +351,16 @@ impl SurfaceCell{
                 if cos_chi <= 0.0 { self.set_local_values_to_zero()}
                 else {
                     self.coschi = cos_chi;
-                    self.v_tot = observed_pulsation_velocity(parameters, theta, phi,k).unwrap();
-                    let local_values = local_surface_temperature_logg(parameters, theta, phi, g0, temperature_0);
+                    self.v_tot = observed_pulsation_velocity(parameters, geometry, k)?;
+                    let local_values = local_surface_temperature_logg(parameters, geometry, g0, temperature_0);
                     self.t_eff = local_values.0;
                     self.log_g = local_values.1;
-                    self.area = s_normal.project_vector(&k_spherical).unwrap();
+                    self.area = s_normal.project_vector(&k_spherical)?;
 
                 }
             }   
         }
+        Ok(())
 
     }
 }
@@ -308,6 +368,10 @@ impl SurfaceCell{
 /// This module contains the functions and methods used for input/output
 pub mod utils;
 
+/// This module contains the [cell_geometry::CellGeometry] of the surface cells, the values that don't change from one time point
+/// to the next (the associated Legendre functions of each pulsation mode and the change of basis), computed once when the star is rasterized.
+pub mod cell_geometry;
+
 /// This module contains the functions, methods, and structures that are used for describing the 
 /// geometry of the star, such as the aproximate deformation of a surface cell due to pulsations, the 
 /// lagrangian displacement. It also contains methods to change between cartesian and spherical coordinates.
@@ -381,5 +445,5 @@ impl AdvanceInTime for RasterizedStar{
 }
 
 pub trait ParsingFromToml {
-    fn read_from_toml(path_to_file:&str)->Self;
+    fn read_from_toml(path_to_file:&str)->Result<Self,PulstarError> where Self: Sized;
 }
\ No newline at end of file
@@ -3,9 +3,7 @@ use super::reference_frames::Coordinates;
 use super::na;
 use temp_name_lib::type_def::{CYCLI2RAD, RADIUSSUN};
 use temp_name_lib::utils::{MACHINE_PRECISION,MathErrors};
-use temp_name_lib::math_module::spherical_harmonics::plmcos::plmcos;
-use temp_name_lib::math_module::spherical_harmonics::d_plmcos_dtheta::deriv1_plmcos_dtheta;
-use temp_name_lib::math_module::spherical_harmonics::norm_factor::ylmnorm;
+use crate::cell_geometry::{CellGeometry,ModeGeometry};
 
 use super::*;
 
@@ -37,8 +35,8 @@ impl PulstarConfig {
 /// 
 /// ### Arguments:
 /// * `mode` - This is a struct that contains the parameters of a pulsation mode in the star. See [crate::PulstarConfig]
+/// * `geometry` - The [ModeGeometry] of the mode over the surface cell, see [CellGeometry]
 /// * `sintheta` - sine of the colatitude coordinate (theta in rads)
-/// * 'costheta' - cosine of the colatitude coordinate (theta in rads)
 /// * `phi_rad`   - azimuthal coordinate  in rads
 /// * `velocity_amplitude`     - Amplitude in the radial direction times the normalization factor `Y_l^m`(see [temp_name_lib::math_module::spherical_harmonics::norm_factors]) in km/s
 /// 
@@ -48,8 +46,8 @@ impl PulstarConfig {
 /// * Err(DivisionByZero) - Where the error is pased to the calling function if the colatitude angle θ is too small. 
 pub fn v_pulse_single_mode(
     mode: &PulsationMode,
+    geometry: &ModeGeometry,
     sintheta:f64,
-    costheta:f64,
     phi_rad:f64,
     velocity_amplitude:f64,
 )->Result<Coordinates,MathErrors>{
@@ -57,21 +55,21 @@ pub fn v_pulse_single_mode(
         true => { Err(MathErrors::DivisionByZero)}
 
         false => {
-            let l=mode.l;
-            let m = mode.m;
+            let m = geometry.m;
             let phase = mode.phase_offset;
             let k = mode.k;
-            let v_r = velocity_amplitude * ylmnorm(l, m)
-                * plmcos(l, m.abs() as u16, sintheta, costheta)
-                * (phase + (m as f64) * phi_rad).sin();
+            let (sin_phase,_) = geometry.phase_terms(phase);
+            let v_r = velocity_amplitude * geometry.ylmnorm
+                * geometry.plm
+                * sin_phase;
             let v_theta = velocity_amplitude * k
-                   * ylmnorm(l, m)
-                   * deriv1_plmcos_dtheta(l, m.abs() as u16, sintheta, costheta)
-                   * (phase + (m as f64) * phi_rad).sin();
+                   * geometry.ylmnorm
+                   * geometry.dplm_dtheta
+                   * sin_phase;
             let v_phi = velocity_amplitude * k
-                   * ylmnorm(l, m)
+                   * geometry.ylmnorm
                    * (-(m as f64))
-                   * plmcos(l, m.abs() as u16, sintheta, costheta)
+                   * geometry.plm
                    * (phase * (m as f64) * phi_rad).cos()
                    / sintheta;
         Ok(Coordinates::Spherical(na::Vector3::new(v_r,v_theta,v_phi)))
@@ -84,8 +82,7 @@ pub fn v_pulse_single_mode(
 /// 
 /// ### Arguments:
 /// * `parameters` - The data contained in [PulstarConfig], here you find the parameters that describe the pulsation modes and the star.
-/// * `theta_rad` - The colatitude angle in rads, must not be too small in order to avoid the poles.
-/// * `phi_rad` - The azimuthal angle in rads
+/// * `geometry` - The [CellGeometry] of the surface cell, computed for the pulsation modes of `parameters`. The colatitude must not be too small in order to avoid the poles.
 /// * `k` - Unit vector in directed towards the observer it is prefered to be in spherical coordinates.
 /// 
 /// ### Returns:
@@ -94,25 +91,21 @@ pub fn v_pulse_single_mode(
 /// * 'Err(DivisionByZero)` - Where the error is pased to the calling function in case that the colatitude angle θ is too small;
 pub fn observed_pulsation_velocity(
     parameters:&PulstarConfig,
-    theta_rad:f64,
-    phi_rad:f64,
+    geometry:&CellGeometry,
     k:&Coordinates,
     )->Result<f64,MathErrors>{
     
 // * `velocity_amplitudes` - A [Vec] collection of the expected velocity amplitudes (with `f64` values) per mode. This collection is ordered in a way that there's a match with the pulsation mode in km/s.
-    let sintheta = theta_rad.sin();
-    let costheta = theta_rad.cos();
-
     let mut collection_velocities:Vec<Coordinates>=Vec::new();
 
     let velocity_amplitudes = parameters.get_velocity_amplitudes();
     
-    for (index,mode) in parameters.mode_data.iter().enumerate(){
+    for (index,(mode,mode_geometry)) in parameters.mode_data.iter().zip(geometry.modes.iter()).enumerate(){
         collection_velocities.push(v_pulse_single_mode(
             mode,
-            sintheta,
-            costheta,
-            phi_rad,
+            mode_geometry,
+            geometry.sintheta,
+            geometry.phi_rad,
             velocity_amplitudes[index])?);
     }
     let sum_velocities = collection_velocities.iter()
@@ -124,7 +117,7 @@ pub fn observed_pulsation_velocity(
             Ok(sum_velocities.project_vector(&k)?)
         }
         Coordinates::Cartesian(_)=>{
-            let k_spherical = k.transform(theta_rad, phi_rad);
+            let k_spherical = k.transform_with(&geometry.t_matrix);
             Ok(sum_velocities.project_vector(&k_spherical)?)
         }
     }
@@ -3,7 +3,9 @@ use pulstar::{reference_frames::{Coordinates},
                     write_grid_data::write_output_to_parquet},
              PulstarConfig,};
 use std::{env,time::Instant};
-use pulstar::{AdvanceInTime,ParsingFromToml};
+use pulstar::ParsingFromToml;
+use pulstar::pulstar_mkr::for_each_frame;
+use pulstar::error::PulstarError;
 use std::fs;
 fn main() {
 
@@ -11,11 +13,15 @@ fn main() {
     let env_args: Vec<String> = env::args().collect();
     
     //having the right number of arguments
-    if env_args.len() != 2usize {
-        panic!("USAGE: pulstar <parameter file>");
+    //`pulstar validate <parameter file>` only checks the parameter file and reports all of the problems found.
+    let validate_only = env_args.len() == 3usize && env_args[1] == "validate";
+    if env_args.len() != 2usize && !validate_only {
+        eprintln!("USAGE: pulstar <parameter file>");
+        eprintln!("       pulstar validate <parameter file>");
+        std::process::exit(1)
     }
 
-    let path = &env_args[1];
+    let path = &env_args[env_args.len()-1];
     println!("--------------------");
     println!("|PULSTARust launched|");
     println!("--------------------");
@@ -29,16 +35,36 @@ fn main() {
 
     let contents = match fs::read_to_string(path){
         Ok(c)=>c,
-        Err(_) => { panic!("Could not read file {}",path)}
+        Err(e) => { eprintln!("Could not read file {}: {}",path,e);
+            std::process::exit(1)}
         };
-    let mut pulse_config = PulstarConfig::read_from_toml(&contents);
+    let pulse_config = match PulstarConfig::read_from_toml(&contents){
+        Ok(config)=>config,
+        Err(e) => { eprintln!("{}",e);
+            std::process::exit(1)}
+        };
+
+    //----------------------------------------
+    //----------Validate input file-----------
+    //----------------------------------------
+    let report = pulse_config.validate();
+    println!("Validation of {}: {}",path,report);
+    if report.is_valid(){
+        println!("Perturbed star within {}",pulse_config.perturbed_parameter_box());
+    }
+    else {
+        std::process::exit(1)
+    }
+    if validate_only {
+        return
+    }
     let time_points = pulse_config.get_time_points(); 
 
     
     //----------------------------------------
     //---Initialize some useful parameters.---
     //----------------------------------------
-    let mut star = pulse_config.rasterize_star();
+    let star = pulse_config.rasterize_star();
 
     //--The components of a unit vector pointing towards the observer
     let k = Coordinates::unit_vector_k(
@@ -48,20 +74,17 @@ fn main() {
     //----------Start of loop-----------------
     //---------------------------------------- 
 
-    for (n,time_stamp) in time_points.iter().enumerate(){
-        println!("\n +-- Computing surface data for time point number {} with time stamp {:.3}.", n,*time_stamp);
-
-        //--Compute the time phases
-        pulse_config.advance_in_time(*time_stamp);
-        star.advance_in_time(*time_stamp);
-        //--Initialize the minimum and maximum arrays
-        
-        //--Computes effective temperature, log gravity, radial component of total velocity, cosχ, etc. on all surface cells avoiding the poles.  
-        star.compute_local_quantities(&pulse_config, &k);
-        
+    //--The frames are computed in parallel and written in the order of the time points.
+    let result = for_each_frame(&pulse_config, &star, &time_points, &k, |n,frame|{
+        println!("\n +-- Computed surface data for time point number {} with time stamp {:.3}.", n,time_points[n]);
         //--Save the data of the current phase.
-        write_output_to_parquet(&star, n as u16 +1).unwrap();
-    }//end for time loop
+        write_output_to_parquet(&frame, n as u16 +1)?;
+        Ok::<(),PulstarError>(())
+    });
+    if let Err(e) = result{
+        eprintln!("Unable to compute or write the rasterized star: {}",e);
+        std::process::exit(1)
+    }
     
     // Prints some values of the run
     print_report(&now, &pulse_config, time_points.len());
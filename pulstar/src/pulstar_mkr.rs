@@ -1,11 +1,47 @@
-use crate::{PulstarConfig, reference_frames::Coordinates, utils::{print_info::print_report, 
-                    write_grid_data::{output_to_parquet, write_output}}};
+use crate::{PulstarConfig, RasterizedStar, reference_frames::Coordinates, error::PulstarError,
+                    utils::write_grid_data::write_output};
 use std::{time::Instant};
-use crate::{AdvanceInTime,ParsingFromToml};
+use crate::ParsingFromToml;
 use polars::prelude::*;
+use rayon::prelude::*;
+use temp_name_lib::utils::MathErrors;
+
+/// Number of time points whose frames are computed in parallel before they're written, it bounds the memory taken by the frames.
+pub const FRAMES_PER_BATCH: usize = 64;
+
+/// This function computes the frames of the star at the time points (see [RasterizedStar::frame]) and passes them to `write`
+/// in the order of the time points. The frames of a batch of [FRAMES_PER_BATCH] time points are computed in parallel
+/// on the threads of the global [rayon] pool (`RAYON_NUM_THREADS` sets their number).
+/// ### Arguments:
+/// * `parameters` - the configuration of the run.
+/// * `star` - the rasterized star, see [PulstarConfig::rasterize_star].
+/// * `time_points` - the time points of the run.
+/// * `k` - the unit vector pointing towards the observer.
+/// * `write` - called with the index of each time point and its frame.
+/// ### Returns:
+/// * `Err(E)` - the error of the first frame that could not be computed, or the first error returned by `write`.
+pub fn for_each_frame<E:From<MathErrors>>(parameters:&PulstarConfig, star:&RasterizedStar, time_points:&[f64], k:&Coordinates,
+    mut write:impl FnMut(usize,RasterizedStar)->Result<(),E>)->Result<(),E>{
+    for (batch,batch_time_points) in time_points.chunks(FRAMES_PER_BATCH).enumerate(){
+        let frames = batch_time_points
+            .par_iter()
+            .map(|time_point| star.frame(parameters, *time_point, k))
+            .collect::<Result<Vec<RasterizedStar>,MathErrors>>()?;
+        for (n,frame) in frames.into_iter().enumerate(){
+            write(batch*FRAMES_PER_BATCH + n, frame)?;
+        }
+    }
+    Ok(())
+}
 
 /// This function is used to get the output of the pulstar code
-pub fn pulstar_main(path:&str)->Option<DataFrame>{
+/// ### Arguments:
+/// * `path` - the contents of a `pulstar_input.toml` file.
+/// ### Returns:
+/// This function returns a [Result] with the following variants:
+/// * `Ok(DataFrame)` - the rasterized star for all of the time points.
+/// * `Err(PulstarError)` - if the configuration could not be parsed, it has no time points, or the computation failed.
+pub fn pulstar_main(path:&str)->Result<DataFrame,PulstarError>{
     println!("--------------------");
     println!("|PULSTARust launched|");
     println!("--------------------");
@@ -16,7 +52,14 @@ pub fn pulstar_main(path:&str)->Option<DataFrame>{
     //----------------------------------------
 
     //let path = String::from("pulstar_input.toml");
-    let mut pulse_config = PulstarConfig::read_from_toml(path);
+    let pulse_config = PulstarConfig::read_from_toml(path)?;
+    let report = pulse_config.validate();
+    if !report.is_valid(){
+        return Err(PulstarError::Validation(report))
+    }
+    if !report.diagnostics.is_empty(){
+        println!("{}",report);
+    }
     /*let mut pulse_config:PulstarConfig = match toml::from_str(path){
         Ok(config)=>{config}
         _=>{panic!("error loading pulstar config toml file")}
@@ -28,7 +71,7 @@ pub fn pulstar_main(path:&str)->Option<DataFrame>{
     //----------------------------------------
     //---Initialize some useful parameters.---
     //----------------------------------------
-    let mut star = pulse_config.rasterize_star();
+    let star = pulse_config.rasterize_star();
 
     //--The components of a unit vector pointing towards the observer
     let k = Coordinates::unit_vector_k(
@@ -39,26 +82,18 @@ pub fn pulstar_main(path:&str)->Option<DataFrame>{
     //---------------------------------------- 
     
     let mut collection_df:Option<DataFrame> = None;
-    for (n,time_stamp) in time_points.iter().enumerate(){
-        println!("\n +-- Computing surface data for time point number {} with time stamp {:.3}.", n,*time_stamp);
-
-        //--Compute the time phases
-        pulse_config.advance_in_time(*time_stamp);
-        star.advance_in_time(*time_stamp);
-        //--Initialize the minimum and maximum arrays
-        
-        //--Computes effective temperature, log gravity, radial component of total velocity, cosχ, etc. on all surface cells avoiding the poles.  
-        star.compute_local_quantities(&pulse_config, &k);
-        
+    for_each_frame(&pulse_config, &star, &time_points, &k, |n,frame|{
+        println!("\n +-- Computed surface data for time point number {} with time stamp {:.3}.", n,time_points[n]);
         //--Save the data of the current phase.
-        collection_df=Some(write_output(&star,collection_df).unwrap());
-    }//end for time loop
+        collection_df=Some(write_output(&frame,collection_df.take())?);
+        Ok::<(),PulstarError>(())
+    })?;
     
     
     println!("----------------------");
     println!("|PULSTARust Finished |");
     println!("----------------------");
 
-    collection_df
+    collection_df.ok_or(PulstarError::InvalidConfig(String::from("there are no time points to compute")))
 
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 
 use super::{*,na};
-use spherical_harmonics::{self,plmcos::plmcos};
+use crate::cell_geometry::{CellGeometry,ModeGeometry};
 use temp_name_lib::math_module::{spherical_harmonics::norm_factor::ylmnorm};
 
 
@@ -28,10 +28,9 @@ mod implementations_for_coordinates;
 /// Compute the Lagrangian displacement vector in spherical coordinates
 /// 
 /// ### Arguments:
-/// * `mode` - This is a struct that contains the parameters of a pulsation mode in the star. See [crate::PulstarConfig]
+/// * `mode` - The [ModeGeometry] of a pulsation mode over the surface cell, see [crate::cell_geometry::CellGeometry]
 /// * `sintheta` - sine of the colatitude coordinate (theta in rads)
-/// * 'costheta' - cosine of the colatitude coordinate (theta in rads)
-/// * `phi`   - azimuthal coordinate  in rads
+/// * `phase_terms` - sine and cosine of the phase of the mode over the cell, see [ModeGeometry::phase_terms]
 /// * `radial_amplitude`     - amplitude in the radial direction times the normalization factor `Y_l^m`(see [temp_name_lib::math_module::spherical_harmonics::norm_factors])
 /// * `tangential_amplitude` - amplitude in the tangential direction times the normalization factor  'Y_l^m' (see [temp_name_lib::math_module::spherical_harmonics::norm_factors])
 /// 
@@ -40,31 +39,20 @@ mod implementations_for_coordinates;
 /// * `Ok(Coordinates::Spherical)` - an Ok  variant that has binded the spherical components of the displacement vector in the`r,θ,φ` order.
 /// * `Err(DivisionByZero)` - an Err variant that has binded the error produced if the colatitude  coordinate (theta) is too small.
 pub fn displacement(
-    mode: &PulsationMode,
+    mode: &ModeGeometry,
     sintheta:f64,
-    costheta:f64,
-    phi:f64,
+    (sin_phase,cos_phase):(f64,f64),
     radial_amplitude:f64,
     tangential_amplitude:f64)->Result<Coordinates,MathErrors>{
         match sintheta.abs() <= f64::EPSILON.sqrt(){
             true => {Err(MathErrors::DivisionByZero)}
             false => {
-                let phase = mode.phase;
-                let l = mode.l;
-                let m =  mode.m;
-                
-                let plmcostheta = plmcos(l, m.abs() as u16, sintheta, costheta); 
-                let dplmcostheta_dtheta = (- f64::from(l+1) * costheta * plmcostheta  // First derivative
-                                        + f64::from((l as i16) - m + 1) 
-                                        * plmcos(l+1, m.abs() as u16, sintheta, costheta))  
-                                        / sintheta;
-
-                let delta_r     = radial_amplitude * plmcostheta 
-                                    * f64::cos(phase + f64::from(m)*phi);
-                let delta_theta = tangential_amplitude * dplmcostheta_dtheta 
-                                    * f64::cos(phase + f64::from(m)*phi);
-                let delta_phi   = tangential_amplitude * f64::from(-m) * plmcostheta 
-                                    * f64::sin(phase + f64::from(m)*phi) 
+                let delta_r     = radial_amplitude * mode.plm 
+                                    * cos_phase;
+                let delta_theta = tangential_amplitude * mode.dplm_displacement 
+                                    * cos_phase;
+                let delta_phi   = tangential_amplitude * f64::from(-mode.m) * mode.plm 
+                                    * sin_phase 
                                     / (sintheta.abs().powi(2));
 
                 Ok(Coordinates::Spherical(na::Vector3::new(delta_r, delta_theta, delta_phi)))
@@ -86,57 +74,52 @@ mod displacement_derivatives;
 /// 
 /// ### Arguments:
 /// * `parameters` - The data contained in [PulstarConfig], here you find the parameters that describe the pulsation modes and the star.
-/// * `theta_rad` - The colatitude angle in rads, must not be too small in order to avoid the poles.
-/// * `phi_rad` - The azimuthal angle in rads
+/// * `geometry` - The [CellGeometry] of the surface cell, computed for the pulsation modes of `parameters`. The colatitude must not be too small in order to avoid the poles.
 pub fn surface_normal(
 parameters: &PulstarConfig,
-theta_rad: f64,
-phi_rad: f64
+geometry: &CellGeometry,
 )->Result<Coordinates,MathErrors>{
-    let sintheta = theta_rad.sin();
-    let costheta = theta_rad.cos();
+    let sintheta = geometry.sintheta;
+    let costheta = geometry.costheta;
 
     let mut total_p_ds = Coordinates::Spherical(na::Vector3::new(0.0,0.0,0.0));
 	let mut total_dev1=0.0;
 	let mut total_dev2=0.0;
 	let mut total_dev3=0.0;
 	let mut total_dev4=0.0;
-    for mode in parameters.mode_data.iter(){
+    for (mode,mode_geometry) in parameters.mode_data.iter().zip(geometry.modes.iter()){
 
-        let radial_amplitude = ampl_r(mode);
-        let tangential_amplitude = ampl_t(mode);
+        let radial_amplitude = mode.rel_dr * mode_geometry.ylmnorm;
+        let tangential_amplitude = mode.rel_dr * mode_geometry.ylmnorm * mode.k;
+        let phase_terms = mode_geometry.phase_terms(mode.phase);
         
         let pulsation_displacement = displacement(
-            mode,
+            mode_geometry,
             sintheta,
-            costheta, 
-            phi_rad, 
+            phase_terms, 
             radial_amplitude, 
             tangential_amplitude)?;
         
         let drdtheta = displacement_derivatives::d_dr_rdtheta(
             mode,
-            sintheta, 
-            costheta, 
-            phi_rad);
+            mode_geometry, 
+            phase_terms);
         
         let drdphi = displacement_derivatives::d_dr_rdphi(
             mode,
-            sintheta,
-            costheta,
-            phi_rad);
+            mode_geometry,
+            phase_terms);
         
         let dtdtheta = displacement_derivatives::d_dtheta_dtheta(
             mode,
-            sintheta,
-            costheta,
-            phi_rad);
+            mode_geometry,
+            phase_terms);
         
         let dpdphi = displacement_derivatives::d_dphi_dphi(
             mode,
+            mode_geometry,
             sintheta,
-            costheta,
-            phi_rad)?;//<- the ? is necesary to pas to the calling function if the colatitude angle theta is too close to the poles.
+            phase_terms)?;//<- the ? is necesary to pas to the calling function if the colatitude angle theta is too close to the poles.
         
         
         total_p_ds += pulsation_displacement;
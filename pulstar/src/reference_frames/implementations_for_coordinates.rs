@@ -119,14 +119,14 @@ impl Coordinates {
         }
     }
 
-    /// This method obtains the transformation matrix useful for changing basis
+    /// This function obtains the transformation matrix useful for changing basis
     /// 
     /// ### Arguments
     /// * `theta_rad` - The colatitude angle in radians
     /// * `phi_rad` - The azimuthal angle in radians
     /// ### Returns
-    /// * `t_a` -The transformation Matrix to change from spherical to cartesian coordinates and vice versa.
-    fn transformation_matrix(&self,
+    /// * `t_matrix` -The transformation Matrix to change from spherical to cartesian coordinates, its transpose changes from cartesian to spherical coordinates.
+    pub fn transformation_matrix(
         theta_rad:f64,
         phi_rad:f64)-> na::Matrix3<f64>{
             let sintheta = theta_rad.sin();
@@ -135,16 +135,11 @@ impl Coordinates {
             let cosphi = phi_rad.cos();
 
 
-            let t_matrix = na::Matrix3::new(
+            na::Matrix3::new(
                 sintheta*costheta, costheta*cosphi, -sinphi,
                 sintheta*sinphi, costheta*cosphi, cosphi,
                 costheta, -sintheta, 0.0,
-            );//spherical to cartesian matrix
-
-            match self{
-                Coordinates::Spherical(_) => {t_matrix}
-                Coordinates::Cartesian(_) => {t_matrix.transpose()}
-            }
+            )//spherical to cartesian matrix
         }
     /// This method transforms coordinates from spherical to cartesian and vice versa. 
     /// 
@@ -155,10 +150,21 @@ impl Coordinates {
     /// ### Returns:
     /// * ` ` - a new instance of [Coordinates] with different base.
     pub fn transform(&self,theta_rad:f64,phi_rad:f64)->Coordinates{
-        let t_matrix = self.transformation_matrix(theta_rad, phi_rad);
+        self.transform_with(&Coordinates::transformation_matrix(theta_rad, phi_rad))
+    }
+
+    /// This method transforms coordinates from spherical to cartesian and vice versa with a transformation matrix computed beforehand,
+    /// e.g. the one of a surface cell (see [crate::cell_geometry::CellGeometry]).
+    /// 
+    /// ### Arguments:
+    /// * `t_matrix` - The transformation matrix from spherical to cartesian coordinates, see [Coordinates::transformation_matrix].
+    /// 
+    /// ### Returns:
+    /// * ` ` - a new instance of [Coordinates] with different base.
+    pub fn transform_with(&self,t_matrix:&na::Matrix3<f64>)->Coordinates{
         match self{
             Coordinates::Cartesian(value)=>{
-                Coordinates::Spherical(t_matrix * value)}
+                Coordinates::Spherical(t_matrix.transpose() * value)}
             Coordinates::Spherical(value)=>{
                 Coordinates::Cartesian(t_matrix * value)}
         }
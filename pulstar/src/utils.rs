@@ -1,4 +1,4 @@
-use crate::{ParsingFromToml, PulstarConfig};
+use crate::{ParsingFromToml, PulstarConfig, error::PulstarError};
 
 pub mod write_grid_data;
 
@@ -11,15 +11,15 @@ impl ParsingFromToml for PulstarConfig{
     /// #### Arguments:
     /// * `path_to_file` - this is a string that indicates the path to the `profile_input.toml` file
     /// #### Returns:
-    /// * new instance of the profile config structure.
-    fn read_from_toml(path_to_file:&str)->Self {
+    /// * new instance of the profile config structure, or a [PulstarError] if the toml contents could not be parsed.
+    fn read_from_toml(path_to_file:&str)->Result<Self,PulstarError> {
         let input_parameters = 
-        parse_input_file::InputParameters::read_from_toml(path_to_file);
-        Self { mode_data: parse_input_file::PulsationModeNoPhases::
+        parse_input_file::InputParameters::read_from_toml(path_to_file)?;
+        Ok(Self { mode_data: parse_input_file::PulsationModeNoPhases::
             get_initial_phases(input_parameters.mode_data),
 		star_data: input_parameters.star_data,
 		time_points: input_parameters.time_points,
-		mesh: input_parameters.mesh}
+		mesh: input_parameters.mesh})
 
     }
 }
\ No newline at end of file
@@ -1,7 +1,6 @@
 use serde::Deserialize;
-use crate::{MeshConfig, ParsingFromToml, PulsationMode, StarData, TimeType};
+use crate::{MeshConfig, ParsingFromToml, PulsationMode, StarData, TimeType, error::PulstarError};
 use crate::PI;
-use std::fs;
 #[derive(Deserialize,Debug,PartialEq)]
 pub struct InputParameters{
     /// A vector collection of all of the modes that will be analyzed, No phases for velocity, temperatuer and gravity are added.
@@ -58,13 +57,9 @@ impl ParsingFromToml for InputParameters {
     /// This function is used to read the parameters required for the pulstar program to run out of the toml configuration file.
     /// #### Arguments:
     /// * `path_to_file` - this is a string that indicates the path to the `profile_input.toml` file
-    fn read_from_toml(contents:&str)->Self {
-        let params: Self = match toml::from_str(contents){
-            Ok(d) => d,
-            Err(e) => {println!("Unable to load data from {}",contents);
-                panic!("error {}",e)}
-        }; 
-        params
+    fn read_from_toml(contents:&str)->Result<Self,PulstarError> {
+        let params: Self = toml::from_str(contents)?;
+        Ok(params)
     }
 }
 
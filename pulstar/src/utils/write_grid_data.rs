@@ -22,6 +22,8 @@ struct RasterizedStarOutput{
     all_coschi: Vec<f64>,
     /// Collumn with all of the (observed)variations of the cell's area caused by pulsations and with respect to the observer. This quantity is normalized such that the sum of all areas is equal to 1. 
     all_area: Vec<f64>,
+    /// Metallicity of the star, it's written as a column only if it was given in the configuration.
+    metallicity: Option<f64>,
 }
 
 impl RasterizedStarOutput{
@@ -65,11 +67,12 @@ impl RasterizedStarOutput{
             all_logg:all_logg,
             all_temp:all_temp,
             all_times:all_times,
-            all_vel:all_vel}
+            all_vel:all_vel,
+            metallicity: star.metallicity}
     }
 }
 
-/// This function creates a [DataFrame] out of a [RasterizedStarOutput]. WARNING: This function takes ownership of the RasterizedStarOutput.
+/// This function creates a [DataFrame] out of a [RasterizedStarOutput]. The `metallicity` column is only present if the star has a metallicity. WARNING: This function takes ownership of the RasterizedStarOutput.
 /// 
 /// ### Arguments:
 /// * `star` - An instance of a [RasterizedStarOutput] that contains all of the local values of all of the cell surfaces.
@@ -78,8 +81,9 @@ impl RasterizedStarOutput{
 /// * `Ok(DataFrame)` - In case the [DataFrame] was adequately created.
 /// * `Err(PolarsError)` - Returning a [PolarsError] to the calling function. 
 fn create_rasterized_star_dataframe(star: RasterizedStarOutput)->PolarsResult<DataFrame>{
+    let ncells = star.all_area.len();
     // The df! macro creates a new dataframe with the columns ("column header"=>values) ordered from left to right
-    df!(
+    let mut star_df = df!(
         "coord1" => star.all_coords1,
         "coord2" => star.all_coords2,
         "time" => star.all_times,
@@ -88,7 +92,11 @@ fn create_rasterized_star_dataframe(star: RasterizedStarOutput)->PolarsResult<Da
         "log gravity" => star.all_logg,
         "coschi" => star.all_coschi,
         "area" => star.all_area,
-    )
+    )?;
+    if let Some(metallicity) = star.metallicity{
+        star_df.with_column(Column::new("metallicity".into(), vec![metallicity;ncells]))?;
+    }
+    Ok(star_df)
 }
 
 /// This function opens the parquet file and creates a lazyframe out of the handle.
@@ -0,0 +1,243 @@
+use crate::{MeshConfig, PulstarConfig, TimeType};
+use crate::reference_frames::ampl_r;
+use temp_name_lib::math_module::spherical_harmonics::plmcos::plmcos;
+
+/// Relative perturbations (Δr/r, ΔT/T, Δg/g) above this value are reported as a warning,
+/// the linear approximation used by pulstar starts to be questionable.
+pub const LINEAR_AMPLITUDE_WARNING: f64 = 0.2;
+
+/// Relative perturbations (Δr/r, ΔT/T, Δg/g) equal or above this value are reported as an error,
+/// the local temperature or gravity would become zero or negative somewhere on the surface.
+pub const LINEAR_AMPLITUDE_LIMIT: f64 = 1.0;
+
+/// How serious a [Diagnostic] is.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Severity{
+    /// The run would fail or produce meaningless results.
+    Error,
+    /// The run is possible but the results should be taken with care.
+    Warning,
+}
+
+/// A single problem found while validating a configuration.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Diagnostic{
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Collection of all of the problems found while validating a configuration.
+///
+/// The checks don't stop at the first problem, so that the user can fix all of them at once.
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct ValidationReport{
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A box on the (T_eff, log g) plane.
+/// It's used both for the range of values reached over the surface of the star and for the range covered by the intensity grids.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct ParameterBox{
+    /// Minimum and maximum effective temperature in K.
+    pub temperature: [f64;2],
+    /// Minimum and maximum logarithm of the surface gravity (cgs).
+    pub log_gravity: [f64;2],
+}
+
+impl ValidationReport{
+    /// Creates an empty report.
+    pub fn new()->Self{
+        Self::default()
+    }
+
+    /// Adds an error to the report.
+    pub fn error(&mut self, message:String){
+        self.diagnostics.push(Diagnostic { severity: Severity::Error, message });
+    }
+
+    /// Adds a warning to the report.
+    pub fn warning(&mut self, message:String){
+        self.diagnostics.push(Diagnostic { severity: Severity::Warning, message });
+    }
+
+    /// Moves all of the diagnostics of `other` into this report.
+    pub fn append(&mut self, mut other:ValidationReport){
+        self.diagnostics.append(&mut other.diagnostics);
+    }
+
+    /// Returns the number of diagnostics with the given [Severity].
+    pub fn count(&self, severity:Severity)->usize{
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == severity).count()
+    }
+
+    /// Returns `true` if the report doesn't contain any error. Warnings don't prevent a run.
+    pub fn is_valid(&self)->bool{
+        self.count(Severity::Error) == 0
+    }
+}
+
+impl std::fmt::Display for Severity{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationReport{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} error(s), {} warning(s)", self.count(Severity::Error), self.count(Severity::Warning))?;
+        for diagnostic in self.diagnostics.iter(){
+            write!(f, "\n  {}: {}", diagnostic.severity, diagnostic.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl ParameterBox{
+    /// Returns `true` if `other` lies completely within this box.
+    pub fn contains(&self, other:&ParameterBox)->bool{
+        self.temperature[0] <= other.temperature[0] && other.temperature[1] <= self.temperature[1]
+        && self.log_gravity[0] <= other.log_gravity[0] && other.log_gravity[1] <= self.log_gravity[1]
+    }
+
+    /// Returns `true` if the point (`temperature`, `log_gravity`) lies within this box.
+    pub fn contains_point(&self, temperature:f64, log_gravity:f64)->bool{
+        self.temperature[0] <= temperature && temperature <= self.temperature[1]
+        && self.log_gravity[0] <= log_gravity && log_gravity <= self.log_gravity[1]
+    }
+}
+
+impl std::fmt::Display for ParameterBox{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "T_eff in [{:.1}, {:.1}] K and log g in [{:.4}, {:.4}]",
+            self.temperature[0], self.temperature[1], self.log_gravity[0], self.log_gravity[1])
+    }
+}
+
+impl PulstarConfig{
+    /// This function checks the configuration before any computation is done and reports every problem found.
+    ///
+    /// The checks are:
+    /// * `|m| <= l` for every pulsation mode.
+    /// * the time points are not empty and are strictly increasing.
+    /// * the mesh steps are positive.
+    /// * the inclination angle is within [0,180] degrees.
+    /// * the mass, radius and effective temperature of the star are positive.
+    /// * the relative perturbations are small enough for the linear theory (see [LINEAR_AMPLITUDE_WARNING] and [LINEAR_AMPLITUDE_LIMIT]).
+    /// ### Returns:
+    /// * A [ValidationReport] with all of the errors and warnings found.
+    pub fn validate(&self)->ValidationReport{
+        let mut report = ValidationReport::new();
+
+        if self.mode_data.is_empty(){
+            report.warning(String::from("there are no pulsation modes, the star will be static"));
+        }
+        for (n,mode) in self.mode_data.iter().enumerate(){
+            if mode.m.unsigned_abs() > mode.l{
+                report.error(format!("mode {}: |m| = {} is bigger than l = {}", n+1, mode.m.unsigned_abs(), mode.l));
+            }
+        }
+
+        match &self.time_points{
+            TimeType::Explicit { collection } => {
+                if collection.is_empty(){
+                    report.error(String::from("the collection of time points is empty"));
+                }
+                if let Some(n) = collection.windows(2).position(|pair| pair[1] <= pair[0]){
+                    report.error(format!("the time points are not strictly increasing ({} is followed by {})",
+                        collection[n], collection[n+1]));
+                }
+            }
+            TimeType::Uniform { start, end, step } => {
+                if *step <= 0.0{
+                    report.error(format!("the step of the time points must be positive, it is {}", step));
+                }
+                if end < start{
+                    report.error(format!("the time points end ({}) before they start ({})", end, start));
+                }
+            }
+        }
+
+        let (theta_step,phi_step) = self.get_mesh_structure();
+        if theta_step <= 0.0{
+            report.error(format!("the mesh step in θ must be positive, it is {}", theta_step));
+        }
+        if phi_step <= 0.0{
+            report.error(format!("the mesh step in φ must be positive, it is {}", phi_step));
+        }
+
+        let inclination = self.star_data.inclination_angle;
+        if !(0.0..=180.0).contains(&inclination){
+            report.error(format!("the inclination angle must be within [0,180] degrees, it is {}", inclination));
+        }
+        if self.star_data.mass <= 0.0 || self.star_data.radius <= 0.0 || self.star_data.effective_temperature <= 0.0{
+            report.error(String::from("the mass, radius and effective temperature of the star must be positive"));
+        }
+
+        if report.is_valid(){
+            let (dr, dtemp, dg) = self.relative_perturbations();
+            for (name,value) in [("Δr/r",dr),("ΔT/T",dtemp),("Δg/g",dg)]{
+                if value >= LINEAR_AMPLITUDE_LIMIT{
+                    report.error(format!("the relative perturbation {} reaches {:.3}, the linear theory doesn't hold", name, value));
+                }
+                else if value > LINEAR_AMPLITUDE_WARNING{
+                    report.warning(format!("the relative perturbation {} reaches {:.3}, the linear theory might not be accurate", name, value));
+                }
+            }
+        }
+        report
+    }
+
+    /// This function returns the equilibrium value of log(g_0), where g_0 is in cgs units.
+    pub fn equilibrium_log_g(&self)->f64{
+        //--Mass & radius are in solar units
+        4.438 + self.star_data.mass.log10()
+            - 2.0 * self.star_data.radius.log10()
+    }
+
+    /// This function returns a box on the (T_eff, log g) plane that contains all of the local values the star will reach.
+    ///
+    /// The box is computed from the amplitudes of the modes on the colatitudes of the mesh, taking the maximum of the
+    /// azimuthal and time dependence for every mode, so it's never smaller than the range reached by the rasterized star.
+    /// It should only be used on configurations that passed [PulstarConfig::validate].
+    pub fn perturbed_parameter_box(&self)->ParameterBox{
+        let (_, dtemp, dg) = self.relative_perturbations();
+        let temperature_0 = self.star_data.effective_temperature;
+        let g0 = 10.0_f64.powf(self.equilibrium_log_g());
+        ParameterBox{
+            temperature: [temperature_0 * (1.0 - dtemp), temperature_0 * (1.0 + dtemp)],
+            log_gravity: [(g0 * (1.0 - dg)).log10(), (g0 * (1.0 + dg)).log10()],
+        }
+    }
+
+    /// This function returns the maximum over the mesh of the relative perturbations (Δr/r, ΔT/T, Δg/g)
+    /// summing the contribution of every mode as if all of them were in phase.
+    fn relative_perturbations(&self)->(f64,f64,f64){
+        let (theta_step,_) = self.get_mesh_structure();
+        let mut dr = 0.0;
+        let mut dtemp = 0.0;
+        let mut dg = 0.0;
+        for mode in self.mode_data.iter(){
+            let mut max_plm:f64 = 0.0;
+            match self.mesh{
+                MeshConfig::Sphere { .. } => {
+                    // Same colatitudes as in PulstarConfig::rasterize_star
+                    let mut theta:f64 = 1.0;
+                    while theta < 180.0{
+                        let theta_rad = theta.to_radians();
+                        let plm = plmcos(mode.l, mode.m.unsigned_abs(), theta_rad.sin(), theta_rad.cos());
+                        max_plm = max_plm.max(plm.abs());
+                        theta += theta_step;
+                    }
+                }
+            }
+            let mode_dr = (ampl_r(mode) * max_plm).abs();
+            dr += mode_dr;
+            dtemp += (mode.rel_dtemp * mode_dr).abs();
+            dg += (mode.rel_dg * mode_dr).abs();
+        }
+        (dr, dtemp, dg)
+    }
+}
@@ -0,0 +1,87 @@
+//! Tests for the evaluation of the frames of the rasterized star at each time point, using the golden fixture.
+use polars::prelude::*;
+use pulstar::error::PulstarError;
+use pulstar::pulstar_mkr::{FRAMES_PER_BATCH, for_each_frame};
+use pulstar::reference_frames::Coordinates;
+use pulstar::utils::write_grid_data::write_output;
+use pulstar::{ParsingFromToml, PulstarConfig};
+use std::path::PathBuf;
+
+fn config() -> PulstarConfig {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("golden")
+        .join("pulstar_input.toml");
+    PulstarConfig::read_from_toml(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn observer(config: &PulstarConfig) -> Coordinates {
+    Coordinates::unit_vector_k(config.star_data.inclination_angle.to_radians())
+}
+
+/// Collects the frames of the time points with a pool of `threads` threads.
+fn collect_frames(config: &PulstarConfig, time_points: &[f64], threads: usize) -> DataFrame {
+    let star = config.rasterize_star();
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+    let mut written: Vec<usize> = Vec::new();
+    let mut collection: Option<DataFrame> = None;
+    pool.install(|| {
+        for_each_frame(config, &star, time_points, &observer(config), |n, frame| {
+            written.push(n);
+            collection = Some(write_output(&frame, collection.take())?);
+            Ok::<(), PulstarError>(())
+        })
+    })
+    .unwrap();
+    assert_eq!(written, (0..time_points.len()).collect::<Vec<usize>>());
+    collection.unwrap()
+}
+
+#[test]
+fn frames_are_pure() {
+    let config = config();
+    let star = config.rasterize_star();
+    let k = observer(&config);
+    let frame = |time_point: f64| write_output(&star.frame(&config, time_point, &k).unwrap(), None).unwrap();
+
+    // A frame doesn't depend on the frames computed before, and it doesn't modify the configuration.
+    let first = frame(0.3);
+    frame(0.1);
+    assert!(first.equals(&frame(0.3)));
+    assert_eq!(config, self::config());
+    assert!(!first.equals(&frame(0.1)));
+}
+
+#[test]
+fn frames_are_written_in_order_regardless_of_the_threads() {
+    let config = config();
+    // More time points than a batch, in no particular order.
+    let time_points: Vec<f64> = (0..FRAMES_PER_BATCH + 5).map(|n| ((n * 37) % 101) as f64 / 101.0).collect();
+    let single = collect_frames(&config, &time_points, 1);
+    let parallel = collect_frames(&config, &time_points, 4);
+    assert!(single.equals(&parallel));
+
+    // The frames are appended in the order of the time points.
+    let mut times: Vec<f64> = single.column("time").unwrap().f64().unwrap().into_iter().flatten().collect();
+    times.dedup();
+    assert_eq!(times, time_points);
+}
+
+#[test]
+fn frames_recompute_the_geometry_of_other_modes() {
+    let config = config();
+    let mut other = config.clone();
+    other.mode_data[0].l = 3;
+    other.mode_data[0].m = -2;
+    let k = observer(&config);
+    let frame = |star: &pulstar::RasterizedStar, parameters: &PulstarConfig| {
+        write_output(&star.frame(parameters, 0.05, &k).unwrap(), None).unwrap()
+    };
+
+    // The star keeps the geometry of the modes it was rasterized with, but can still be used with other modes.
+    let star = config.rasterize_star();
+    let expected = frame(&other.rasterize_star(), &other);
+    assert!(frame(&star, &other).equals(&expected));
+    assert!(!frame(&star, &config).equals(&expected));
+}
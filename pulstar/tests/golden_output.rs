@@ -0,0 +1,65 @@
+//! Golden snapshot test for the rasterized star.
+//!
+//! The fixture directory `tests/fixtures/golden/` holds a pulstar input together with the legacy
+//! PULSTAR inputs it mirrors (`input_pulstar.txt`, `input_timepoints.txt`) and the expected
+//! rasterized star (`expected_rasterized_star.csv`). The test runs [pulstar_main] and compares
+//! the output column by column within the tolerances listed in [TOLERANCES].
+//!
+//! The expected table is a snapshot of the output of the Rust port itself, not of the original C
+//! program, so the test catches unintended changes of the numbers, not errors of the port.
+//! Running the test with `PULSTAR_BLESS=1` overwrites the expected values with the current output.
+//! Only do this in the commit that intentionally changes the numbers, and say so in its message
+//! (see `tests/fixtures/golden/README.md`).
+
+use golden_support::{Tolerance, assert_matches_expected_table, write_expected_table};
+use pulstar::pulstar_mkr::pulstar_main;
+use std::path::PathBuf;
+
+/// Tolerances per quantity of the rasterized star.
+///
+/// * `coord1`, `coord2` - the cell barycenters are set by the mesh only, so they must match to round-off.
+/// * `time` - copied from the input, must match to round-off.
+/// * `velocity` - projected velocity in km/s. The legacy program prints it with 6 significant digits,
+///   so 1e-5 km/s absolute plus 1e-6 relative.
+/// * `temperature` - local effective temperature in K. Printed with 6 significant digits by the legacy
+///   program, that is 1e-6 relative.
+/// * `log gravity` - dex, 1e-6 absolute.
+/// * `coschi` - dimensionless in [0,1], 1e-6 absolute.
+/// * `area` - normalized projected area, 1e-6 relative plus 1e-9 absolute for cells close to the limb.
+const TOLERANCES: [Tolerance; 8] = [
+    Tolerance { column: "coord1", absolute: 1.0e-12, relative: 0.0 },
+    Tolerance { column: "coord2", absolute: 1.0e-12, relative: 0.0 },
+    Tolerance { column: "time", absolute: 1.0e-12, relative: 0.0 },
+    Tolerance { column: "velocity", absolute: 1.0e-5, relative: 1.0e-6 },
+    Tolerance { column: "temperature", absolute: 0.0, relative: 1.0e-6 },
+    Tolerance { column: "log gravity", absolute: 1.0e-6, relative: 0.0 },
+    Tolerance { column: "coschi", absolute: 1.0e-6, relative: 0.0 },
+    Tolerance { column: "area", absolute: 1.0e-9, relative: 1.0e-6 },
+];
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("golden")
+        .join(name)
+}
+
+#[test]
+fn rasterized_star_matches_golden_output() {
+    let input = std::fs::read_to_string(fixture_path("pulstar_input.toml")).unwrap();
+    let star_df = pulstar_main(&input).expect("pulstar produced no output");
+    let expected_path = fixture_path("expected_rasterized_star.csv");
+
+    if std::env::var("PULSTAR_BLESS").is_ok() {
+        write_expected_table(
+            &expected_path,
+            &star_df,
+            &TOLERANCES,
+            "Expected rasterized star for pulstar_input.toml, see README.md for its provenance.",
+        );
+        return;
+    }
+
+    assert_matches_expected_table(&star_df, &expected_path, &TOLERANCES);
+}
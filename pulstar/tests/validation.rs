@@ -0,0 +1,66 @@
+//! Tests for [PulstarConfig::validate].
+use polars::prelude::*;
+use pulstar::pulstar_mkr::pulstar_main;
+use pulstar::validation::Severity;
+use pulstar::{ParsingFromToml, PulstarConfig};
+use std::path::PathBuf;
+
+fn golden_input() -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("golden")
+        .join("pulstar_input.toml");
+    std::fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn golden_configuration_is_valid() {
+    let config = PulstarConfig::read_from_toml(&golden_input()).unwrap();
+    let report = config.validate();
+    assert!(report.is_valid(), "{}", report);
+
+    // The box must contain every visible cell of the rasterized star.
+    let star_box = config.perturbed_parameter_box();
+    let star_df = pulstar_main(&golden_input()).unwrap();
+    let visible = star_df.lazy().filter(col("coschi").gt(lit(0.0)));
+    for (column, bounds) in [("temperature", star_box.temperature), ("log gravity", star_box.log_gravity)] {
+        let extremes = visible
+            .clone()
+            .select([col(column).min().alias("min"), col(column).max().alias("max")])
+            .collect()
+            .unwrap();
+        let min = extremes.column("min").unwrap().f64().unwrap().get(0).unwrap();
+        let max = extremes.column("max").unwrap().f64().unwrap().get(0).unwrap();
+        let tolerance = 1.0e-9 * bounds[1].abs();
+        assert!(bounds[0] - tolerance <= min && max <= bounds[1] + tolerance,
+            "{} reaches [{}, {}], outside of {}", column, min, max, star_box);
+    }
+}
+
+#[test]
+fn every_problem_is_reported_at_once() {
+    let input = golden_input()
+        .replace("collection = [0.01, 0.05, 0.1]", "collection = [0.05, 0.01]")
+        .replace("m = 1", "m = 5")
+        .replace("theta_step = 10.0", "theta_step = 0.0")
+        .replace("inclination_angle = 45.0", "inclination_angle = 200.0");
+    let config = PulstarConfig::read_from_toml(&input).unwrap();
+    let report = config.validate();
+
+    assert!(!report.is_valid());
+    assert_eq!(report.count(Severity::Error), 4, "{}", report);
+    let text = report.to_string();
+    for expected in ["|m| = 5", "strictly increasing", "mesh step in θ", "inclination angle"] {
+        assert!(text.contains(expected), "'{}' missing from the report:\n{}", expected, text);
+    }
+}
+
+#[test]
+fn large_amplitudes_break_linear_theory() {
+    let input = golden_input().replace("rel_dg = 10.0", "rel_dg = 200.0");
+    let config = PulstarConfig::read_from_toml(&input).unwrap();
+    let report = config.validate();
+    assert!(!report.is_valid());
+    assert!(report.to_string().contains("Δg/g"), "{}", report);
+}
@@ -8,16 +8,47 @@ use pyo3::exceptions::PyValueError;
 
 mod make_pulstar_input;
 
+use pyo3::exceptions::{PyArithmeticError, PyFileNotFoundError, PyIOError, PyRuntimeError, PyValueError};
+use pulstar::error::PulstarError;
+use profile::error::ProfileError;
+
+/// Maps a [PulstarError] into the closest Python exception.
+fn pulstar_error_to_py(e: PulstarError) -> PyErr {
+    let message = e.to_string();
+    match e {
+        PulstarError::Math(_) => PyArithmeticError::new_err(message),
+        PulstarError::Io(_) => PyIOError::new_err(message),
+        PulstarError::Toml(_) | PulstarError::InvalidConfig(_) | PulstarError::Validation(_) => PyValueError::new_err(message),
+        PulstarError::Polars(_) => PyRuntimeError::new_err(message),
+    }
+}
+
+/// Maps a [ProfileError] into the closest Python exception.
+fn profile_error_to_py(e: ProfileError) -> PyErr {
+    let message = e.to_string();
+    match e {
+        ProfileError::Math(_) => PyArithmeticError::new_err(message),
+        ProfileError::Io(_) => PyIOError::new_err(message),
+        ProfileError::MissingGridFile(_) => PyFileNotFoundError::new_err(message),
+        ProfileError::Toml(_) | ProfileError::InvalidConfig(_) | ProfileError::Validation(_)
+        | ProfileError::GridCoverage{..} | ProfileError::MetallicityCoverage{..} => PyValueError::new_err(message),
+        ProfileError::Polars(_) | ProfileError::GridFormat(_) => PyRuntimeError::new_err(message),
+    }
+}
+
 
 /// A Python module implemented in Rust.
 #[pymodule]
 mod pulstar_py {
     use pyo3::prelude::*;
     use pyo3_polars::PyDataFrame;
+    use super::{pulstar_error_to_py, profile_error_to_py};
     
 
+    /// Runs pulstar and profile, and returns the fluxes and the number of visible surface cells outside of the intensity grids
+    /// of every time point, with the columns `|time|out of bounds cells|`.
     #[pyfunction]
-    fn propulse(profile_input:&str,pulstar_input:&str)->PyResult<PyDataFrame>{
+    fn propulse(profile_input:&str,pulstar_input:&str)->PyResult<(PyDataFrame,PyDataFrame)>{
 
 
         println!("---------------------------");
@@ -31,16 +62,16 @@ mod pulstar_py {
 
 //        println!("{}",profile_input_rs);
 
-        let df =match pulstar::pulstar_mkr::pulstar_main(&pulstar_input_rs){
-            Some(star_df)=>{
-                profile::profile_mkr::profile_main(&profile_input_rs,star_df)
-            }
-            None => {panic!("Unable to create rasterized star")}};
+        let star_df = pulstar::pulstar_mkr::pulstar_main(&pulstar_input_rs)
+            .map_err(pulstar_error_to_py)?;
+        let output = profile::profile_mkr::profile_output(&profile_input_rs,star_df)
+            .map_err(profile_error_to_py)?;
         
 
-        let pydf = PyDataFrame(df);
+        let pydf = PyDataFrame(output.data_frame);
+        let out_of_bounds_pydf = PyDataFrame(output.out_of_bounds_cells);
 
-        Ok(pydf)
+        Ok((pydf,out_of_bounds_pydf))
 
     }        
 }
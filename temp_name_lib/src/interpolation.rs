@@ -1,4 +1,17 @@
 use crate::utils::MathErrors;
+use ndarray::{Array2,ArrayD,ArrayView2,Axis};
+
+/// This enum indicates how [ParameterSpaceHypercube::multilinear_interpolation_with], [RegularGridInterpolator] and [DelaunayInterpolator]
+/// treat a point outside of the grid.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum BoundsHandling{
+    /// Return an [Err] ([MathErrors::OutOfBounds]) variant.
+    Error,
+    /// Move the coordinates that are out of bounds to the closest edge of the hypercube.
+    Clamp,
+    /// Use the linear interpolation beyond the edges, i.e. fractional distances outside of [0,1].
+    Extrapolate,
+}
 
 /// This structure contains all the relevant information to produce 
 /// multilinear interpolation. 
@@ -70,10 +83,11 @@ impl ParameterSpaceHypercube{
     /// This method is used to compute the fractional distances to a point in parameter space.
     /// ### Arguments: 
     /// * `coords_in_param_space` - a &[[f64]] slice that contains the coordinates of a poin inside the [ParameterSpaceHypercube] where we want to know the result of the interpolation.
+    /// * `bounds_handling` - what to do if the point is outside of the [ParameterSpaceHypercube], see [BoundsHandling].
     /// ### Returns: 
     /// * This method returns a [Result] with a [Ok()] variant in case the fractional distances where calculated correctly and an 
-    /// [Err] variant  in case the coordinates of the point are not well indicated or the point is outside the domain of the [ParameterSpaceHypercube].
-    fn get_fractional_distances( &mut self, coords_in_param_space:&[f64])->Result<(),MathErrors>{
+    /// [Err] variant  in case the coordinates of the point are not well indicated or the point is outside the domain of the [ParameterSpaceHypercube] and `bounds_handling` is [BoundsHandling::Error].
+    fn get_fractional_distances( &mut self, coords_in_param_space:&[f64], bounds_handling:BoundsHandling)->Result<(),MathErrors>{
        if coords_in_param_space.len()!= self.fractional_distances.len(){
         println!("here're the values {},{}",coords_in_param_space.len(),self.fractional_distances.len());
         Err(MathErrors::NotAdequateNumberOfElements)} 
@@ -81,13 +95,14 @@ impl ParameterSpaceHypercube{
             for (index,item) in self.fractional_coordinates.iter().enumerate(){
                 let x_l = item[0];
                 let x_r = item[1];
-                if coords_in_param_space[index]<x_l || coords_in_param_space[index]>x_r{
-                    println!("One of the coordinates of the point in parameter space is out of bounds");
-                    println!("coordinate value = {}, left bound = {}, right bound = {}",coords_in_param_space[index],x_l,x_r);
-                    return Err(MathErrors::OutOfBounds)
-                };
+                let fractional_distance = (coords_in_param_space[index]-x_l)/(x_r-x_l);
+                let is_out_of_bounds = coords_in_param_space[index]<x_l || coords_in_param_space[index]>x_r;
                 
-                self.fractional_distances[index] = (coords_in_param_space[index]-x_l)/(x_r-x_l);
+                self.fractional_distances[index] = match (is_out_of_bounds,bounds_handling){
+                    (false,_) | (true,BoundsHandling::Extrapolate) => {fractional_distance}
+                    (true,BoundsHandling::Clamp) => {fractional_distance.clamp(0.0, 1.0)}
+                    (true,BoundsHandling::Error) => {return Err(MathErrors::OutOfBounds)}
+                };
             }
             Ok(())
        }
@@ -121,23 +136,39 @@ impl ParameterSpaceHypercube{
     /// This method performs multilinear interpolation for a point in the parameter space. The implementation was based on the algorithm described on the [Numerical Recipes](https://numerical.recipes/) book. 
     /// The implementation thus relies on doing linear interpolations in a loop on the dimensions.
     /// 
-    /// For two dimensions, if the corner values are `C00,C01,C10,C11`, and the fractional distances are `t=(x-xl)/(xr-xl)` and `u =(y-yl)/(yr-yl)`, we have
+    /// The corner values are ordered as the vertices of the hypercube with the first axis changing first, so the linear interpolations
+    /// go from the first axis to the last one.
+    /// For two dimensions, if the corner values are `C00,C10,C01,C11`, and the fractional distances are `t=(x-xl)/(xr-xl)` and `u =(y-yl)/(yr-yl)`, we have
     /// 
-    /// `C1 = C00*t + C01 *(1-t); //<- Linear interpolation`
+    /// `C0 = C00*(1-t) + C10*t; //<- Linear interpolation`
     /// 
-    /// `C2 = C10*t + C11 *(1-t);`
+    /// `C1 = C01*(1-t) + C11*t;`
     /// 
-    /// `multi_interpolation = C1*u + C2*(1-u);`
+    /// `multi_interpolation = C0*(1-u) + C1*u;`
     /// 
     /// ### Arguments:
     /// * `Coords_in_param_space`- A &[[f64]] reference that contains the coordinates of a point in the parameter space. 
     /// ### Returns: 
     /// This function returns a [Result] with the following variants: 
     /// * [Ok] ([f64]) - where the binded value is the result of the interpolation. 
-    /// * [Err] ([MathErrors]) - If there was a problem with the slice containing the coordinates on the parameter space. 
+    /// * [Err] ([MathErrors]) - If there was a problem with the slice containing the coordinates on the parameter space, or the point is out of bounds. 
     pub fn multilinear_interpolation(&mut self, coords_in_param_space:&[f64])->Result<f64,MathErrors>{
+        self.multilinear_interpolation_with(coords_in_param_space, BoundsHandling::Error)
+    }
+
+    /// This method performs multilinear interpolation for a point in the parameter space, as [ParameterSpaceHypercube::multilinear_interpolation] does,
+    /// but the points outside of the hypercube are treated according to `bounds_handling`.
+    /// ### Arguments:
+    /// * `Coords_in_param_space`- A &[[f64]] reference that contains the coordinates of a point in the parameter space. 
+    /// * `bounds_handling` - A [BoundsHandling] value that indicates what to do with a point outside of the hypercube.
+    /// ### Returns: 
+    /// This function returns a [Result] with the following variants: 
+    /// * [Ok] ([f64]) - where the binded value is the result of the interpolation (or extrapolation). 
+    /// * [Err] ([MathErrors]) - If there was a problem with the slice containing the coordinates on the parameter space,
+    /// or the point is out of bounds and `bounds_handling` is [BoundsHandling::Error]. 
+    pub fn multilinear_interpolation_with(&mut self, coords_in_param_space:&[f64], bounds_handling:BoundsHandling)->Result<f64,MathErrors>{
         //Compute the fractional distances for all of the dimensions. 
-        self.get_fractional_distances(coords_in_param_space)?;        
+        self.get_fractional_distances(coords_in_param_space, bounds_handling)?;        
         
         // get the dimensions of the parameterspace
         let dimension = coords_in_param_space.len();
@@ -169,15 +200,424 @@ impl ParameterSpaceHypercube{
     }
 }
 
-fn linear_interpolation(values:&[f64],fractional_distance:f64)->f64{
-    values[0]*fractional_distance +  values[1]*(1.0-fractional_distance)
+/// Interpolates linearly between two values.
+/// ### Arguments:
+/// * `values` - the values at the left and right nodes.
+/// * `fractional_distance` - `(x-x_l)/(x_r-x_l)`, the position of the point between the nodes.
+pub fn linear_interpolation(values:&[f64],fractional_distance:f64)->f64{
+    values[0]*(1.0-fractional_distance) +  values[1]*fractional_distance
+}
+
+/// This enum indicates how the slopes at the nodes of a [cubic_hermite_interpolation] are estimated out of the values.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum HermiteSlopes{
+    /// The slope of the neighbouring nodes (Catmull-Rom spline). It reproduces quadratic functions on evenly spaced nodes, but it can overshoot
+    /// next to steep changes.
+    CatmullRom,
+    /// The weighted harmonic mean of the slopes of the neighbouring intervals, or zero at a local extreme (Fritsch-Butland, as PCHIP does).
+    /// The interpolation preserves the monotonicity of the values, so it doesn't overshoot.
+    Monotone,
+}
+
+/// This function performs a cubic Hermite interpolation within an interval of an axis, using up to one more node on each side to estimate the slopes.
+/// At the ends of the axis the slope is the one of the end interval, and with only two nodes the interpolation is linear.
+/// ### Arguments:
+/// * `nodes` - two to four coordinates of the axis in increasing order, e.g. `[x_(i-1), x_i, x_(i+1), x_(i+2)]`.
+/// * `values` - the values at the nodes.
+/// * `index` - the position in `nodes` of the left node of the interval, `x` must lie within `nodes[index]` and `nodes[index+1]`.
+/// * `x` - the coordinate of the point.
+/// * `slopes` - how the slopes at the nodes are estimated, see [HermiteSlopes].
+/// ### Returns:
+/// * the interpolated value at `x`.
+pub fn cubic_hermite_interpolation(nodes:&[f64], values:&[f64], index:usize, x:f64, slopes:HermiteSlopes)->f64{
+    let secant = |i:usize| (values[i+1]-values[i])/(nodes[i+1]-nodes[i]);
+    let slope = |i:usize|->f64{
+        if i == 0 || i+1 == nodes.len() {return secant(i.min(nodes.len()-2))}
+        match slopes{
+            HermiteSlopes::CatmullRom => {(values[i+1]-values[i-1])/(nodes[i+1]-nodes[i-1])}
+            HermiteSlopes::Monotone => {
+                let (left,right) = (secant(i-1),secant(i));
+                if left*right <= 0.0 {return 0.0}
+                let (h_left,h_right) = (nodes[i]-nodes[i-1],nodes[i+1]-nodes[i]);
+                let (w_left,w_right) = (2.0*h_right+h_left, h_right+2.0*h_left);
+                (w_left+w_right)/(w_left/left + w_right/right)
+            }
+        }
+    };
+    let h = nodes[index+1]-nodes[index];
+    let t = (x-nodes[index])/h;
+    let (t2,t3) = (t*t,t*t*t);
+    (2.0*t3-3.0*t2+1.0)*values[index] + (t3-2.0*t2+t)*h*slope(index)
+        + (-2.0*t3+3.0*t2)*values[index+1] + (t3-t2)*h*slope(index+1)
+}
+
+
+
+/// A multilinear interpolator over a regular grid, i.e. a grid whose nodes are the combinations of the nodes of every axis.
+/// The axes don't need to be evenly spaced.
+///
+/// Unlike the [ParameterSpaceHypercube], it owns the whole grid: it finds the cell that brackets a point, gathers the values at its corners
+/// and interpolates them. Every node can hold several components (e.g. the coefficients of a law, or the intensities of a spectrum),
+/// which are interpolated with the same weights.
+#[derive(Debug,Clone,PartialEq)]
+pub struct RegularGridInterpolator{
+    /// The nodes of every axis in increasing order.
+    axes:Vec<Vec<f64>>,
+    /// The values at the nodes, with shape `(axes[0].len(), ..., axes[N-1].len(), ncomponents)` in standard layout.
+    values:ArrayD<f64>,
+}
+
+impl RegularGridInterpolator{
+    /// Creates a new interpolator.
+    /// ### Arguments:
+    /// * `axes` - the nodes of every axis in increasing order, at least two per axis.
+    /// * `values` - the values at the nodes, with shape `(axes[0].len(), ..., axes[N-1].len())` for a single component,
+    ///   or with an extra last axis for several components.
+    /// ### Returns:
+    /// * `Err(MathErrors::NotAdequateNumberOfElements)` - if an axis has less than two nodes or isn't increasing,
+    ///   or the shape of `values` doesn't match the axes.
+    pub fn new(axes:Vec<Vec<f64>>, values:ArrayD<f64>)->Result<Self,MathErrors>{
+        if axes.is_empty() || axes.iter().any(|axis| axis.len() < 2 || axis.windows(2).any(|pair| pair[0] >= pair[1])){
+            return Err(MathErrors::NotAdequateNumberOfElements)
+        }
+        let nodes:Vec<usize> = axes.iter().map(|axis| axis.len()).collect();
+        let values = if values.ndim() == axes.len() {values.insert_axis(Axis(axes.len()))} else {values};
+        if values.ndim() != axes.len()+1 || values.shape()[..axes.len()] != nodes[..]{
+            return Err(MathErrors::NotAdequateNumberOfElements)
+        }
+        Ok(Self{axes, values: values.as_standard_layout().into_owned()})
+    }
+
+    /// Returns the number of axes of the grid.
+    pub fn dimension(&self)->usize{
+        self.axes.len()
+    }
+
+    /// Returns the number of components of every node.
+    pub fn ncomponents(&self)->usize{
+        self.values.shape()[self.axes.len()]
+    }
+
+    /// Returns the nodes of every axis.
+    pub fn axes(&self)->&[Vec<f64>]{
+        &self.axes
+    }
+
+    /// Returns `true` if the point lies within the grid.
+    pub fn contains(&self, point:&[f64])->bool{
+        self.axes.iter().zip(point.iter()).all(|(axis,x)| axis[0] <= *x && *x <= axis[axis.len()-1])
+    }
+
+    /// This method interpolates the components at a point and writes them into `output`.
+    /// ### Arguments:
+    /// * `point` - the coordinates of the point, one per axis.
+    /// * `bounds_handling` - what to do if the point lies outside of the grid, see [BoundsHandling].
+    /// * `output` - where the components are written, its length must be [RegularGridInterpolator::ncomponents].
+    /// ### Returns:
+    /// * `Err(MathErrors::NotAdequateNumberOfElements)` - if `point` or `output` don't have the right length.
+    /// * `Err(MathErrors::OutOfBounds)` - if the point lies outside of the grid and `bounds_handling` is [BoundsHandling::Error].
+    pub fn interpolate_into(&self, point:&[f64], bounds_handling:BoundsHandling, output:&mut [f64])->Result<(),MathErrors>{
+        if point.len() != self.dimension() || output.len() != self.ncomponents(){
+            return Err(MathErrors::NotAdequateNumberOfElements)
+        }
+        let mut lower_nodes:Vec<usize> = Vec::with_capacity(self.dimension());
+        let mut fractions:Vec<f64> = Vec::with_capacity(self.dimension());
+        for (axis,x) in self.axes.iter().zip(point.iter()){
+            let (index,fraction) = bracket(axis, *x, bounds_handling)?;
+            lower_nodes.push(index);
+            fractions.push(fraction);
+        }
+
+        // Sum the components of the 2^N corners of the cell times their weights, the product of (1-t) or t along every axis.
+        let values = self.values.as_slice().expect("the values are stored in standard layout");
+        let strides = self.values.strides();
+        output.fill(0.0);
+        for corner in 0..1usize<<self.dimension(){
+            let mut weight = 1.0;
+            let mut offset = 0usize;
+            for (axis,(index,fraction)) in lower_nodes.iter().zip(fractions.iter()).enumerate(){
+                let upper = (corner >> (self.dimension()-1-axis)) & 1;
+                weight *= if upper == 1 {*fraction} else {1.0-fraction};
+                offset += (index+upper)*strides[axis] as usize;
+            }
+            for (component,value) in output.iter_mut().zip(values[offset..offset+self.ncomponents()].iter()){
+                *component += weight*value;
+            }
+        }
+        Ok(())
+    }
+
+    /// This method interpolates the components at a point, see [RegularGridInterpolator::interpolate_into].
+    pub fn interpolate(&self, point:&[f64], bounds_handling:BoundsHandling)->Result<Vec<f64>,MathErrors>{
+        let mut output = vec![0.0;self.ncomponents()];
+        self.interpolate_into(point, bounds_handling, &mut output)?;
+        Ok(output)
+    }
+
+    /// This method interpolates the components at many points.
+    /// ### Arguments:
+    /// * `points` - the coordinates of a point in every row.
+    /// * `bounds_handling` - what to do if a point lies outside of the grid, see [BoundsHandling].
+    /// ### Returns:
+    /// * `Ok(Array2<f64>)` - the components of every point in a row.
+    /// * `Err(MathErrors)` - if the points don't have a coordinate per axis, or one of them lies outside of the grid and `bounds_handling` is [BoundsHandling::Error].
+    pub fn interpolate_many(&self, points:ArrayView2<f64>, bounds_handling:BoundsHandling)->Result<Array2<f64>,MathErrors>{
+        let mut output = Array2::zeros((points.nrows(),self.ncomponents()));
+        let mut point:Vec<f64> = vec![0.0;points.ncols()];
+        for (row,mut components) in points.rows().into_iter().zip(output.rows_mut()){
+            point.iter_mut().zip(row.iter()).for_each(|(x,value)| *x = *value);
+            let components = components.as_slice_mut().expect("the rows of the output are contiguous");
+            self.interpolate_into(&point, bounds_handling, components)?;
+        }
+        Ok(output)
+    }
+}
+
+/// A linear interpolator over scattered nodes on a plane, e.g. a grid with some of its nodes missing.
+///
+/// The nodes are joined into their Delaunay triangulation (Bowyer-Watson algorithm), and a point is interpolated within the triangle
+/// that contains it with its barycentric coordinates, which are the weights of the three vertices. The interpolation is exact for linear
+/// functions and continuous across the edges of the triangles. The coordinates are rescaled to the bounding box of the nodes
+/// before the triangulation, so that axes with very different units (e.g. K and dex) give well shaped triangles.
+#[derive(Debug,Clone,PartialEq)]
+pub struct DelaunayInterpolator{
+    /// The nodes rescaled to the unit square of their bounding box.
+    nodes:Vec<[f64;2]>,
+    /// The lower corner of the bounding box of the nodes.
+    origin:[f64;2],
+    /// The size of the bounding box of the nodes along each axis.
+    extent:[f64;2],
+    /// The indices of the vertices of every triangle, in counterclockwise order.
+    triangles:Vec<[usize;3]>,
+}
+
+impl DelaunayInterpolator{
+    /// Creates a new interpolator out of the Delaunay triangulation of the nodes.
+    /// ### Arguments:
+    /// * `nodes` - the coordinates of the nodes, at least three of them and not all on a line.
+    /// ### Returns:
+    /// * `Err(MathErrors::NotAdequateNumberOfElements)` - if there are less than three different nodes or they all lie on a line.
+    pub fn new(nodes:&[[f64;2]])->Result<Self,MathErrors>{
+        let mut origin = [f64::INFINITY;2];
+        let mut upper = [f64::NEG_INFINITY;2];
+        for node in nodes.iter(){
+            for axis in 0..2{
+                origin[axis] = origin[axis].min(node[axis]);
+                upper[axis] = upper[axis].max(node[axis]);
+            }
+        }
+        let extent = [upper[0]-origin[0], upper[1]-origin[1]];
+        if nodes.len() < 3 || !(extent[0] > 0.0 && extent[1] > 0.0){
+            return Err(MathErrors::NotAdequateNumberOfElements)
+        }
+        let rescaled:Vec<[f64;2]> = nodes.iter().map(|node| [(node[0]-origin[0])/extent[0], (node[1]-origin[1])/extent[1]]).collect();
+        if rescaled.iter().enumerate().any(|(n,node)| rescaled[..n].contains(node)){
+            return Err(MathErrors::NotAdequateNumberOfElements)
+        }
+        let triangles = delaunay_triangulation(&rescaled);
+        if triangles.is_empty(){
+            return Err(MathErrors::NotAdequateNumberOfElements)
+        }
+        Ok(Self{nodes: rescaled, origin, extent, triangles})
+    }
+
+    /// Returns the number of nodes.
+    pub fn nnodes(&self)->usize{
+        self.nodes.len()
+    }
+
+    /// Returns the indices of the vertices of every triangle, in counterclockwise order.
+    pub fn triangles(&self)->&[[usize;3]]{
+        &self.triangles
+    }
+
+    /// Returns `true` if the point lies within the convex hull of the nodes, i.e. within one of the triangles.
+    pub fn contains(&self, point:[f64;2])->bool{
+        let point = self.rescale(point);
+        self.triangles.iter().any(|triangle| self.barycentric(triangle, point).iter().all(|weight| *weight >= -HULL_TOLERANCE))
+    }
+
+    /// This method returns the weights of the nodes that interpolate a point, as pairs `(index of the node, weight)`.
+    /// Outside of the convex hull of the nodes the closest triangle is used, and the point is treated according to `bounds_handling`:
+    /// [BoundsHandling::Clamp] moves it to the closest point of the triangle and [BoundsHandling::Extrapolate] extends the linear interpolation.
+    /// ### Arguments:
+    /// * `point` - the coordinates of the point.
+    /// * `bounds_handling` - what to do if the point lies outside of the convex hull, see [BoundsHandling].
+    /// ### Returns:
+    /// * `Ok([(usize,f64);3])` - the vertices of the triangle and their weights, which add up to one.
+    /// * `Err(MathErrors::OutOfBounds)` - if the point lies outside of the convex hull and `bounds_handling` is [BoundsHandling::Error].
+    pub fn weights(&self, point:[f64;2], bounds_handling:BoundsHandling)->Result<[(usize,f64);3],MathErrors>{
+        let point = self.rescale(point);
+        let mut closest = (f64::INFINITY, 0usize);
+        for (n,triangle) in self.triangles.iter().enumerate(){
+            let weights = self.barycentric(triangle, point);
+            if weights.iter().all(|weight| *weight >= -HULL_TOLERANCE){
+                return Ok(zip_weights(triangle, weights))
+            }
+            let distance = distance_squared(point, self.closest_point(triangle, point));
+            if distance < closest.0 {closest = (distance, n)}
+        }
+
+        let triangle = &self.triangles[closest.1];
+        match bounds_handling{
+            BoundsHandling::Error => {Err(MathErrors::OutOfBounds)}
+            BoundsHandling::Clamp => {Ok(zip_weights(triangle, self.barycentric(triangle, self.closest_point(triangle, point))))}
+            BoundsHandling::Extrapolate => {Ok(zip_weights(triangle, self.barycentric(triangle, point)))}
+        }
+    }
+
+    /// This method interpolates a value at a point, see [DelaunayInterpolator::weights].
+    /// ### Arguments:
+    /// * `point` - the coordinates of the point.
+    /// * `values` - the values at the nodes, in the order of the nodes given to [DelaunayInterpolator::new].
+    /// * `bounds_handling` - what to do if the point lies outside of the convex hull, see [BoundsHandling].
+    /// ### Returns:
+    /// * `Err(MathErrors::NotAdequateNumberOfElements)` - if there isn't a value per node.
+    /// * `Err(MathErrors::OutOfBounds)` - if the point lies outside of the convex hull and `bounds_handling` is [BoundsHandling::Error].
+    pub fn interpolate(&self, point:[f64;2], values:&[f64], bounds_handling:BoundsHandling)->Result<f64,MathErrors>{
+        if values.len() != self.nnodes(){
+            return Err(MathErrors::NotAdequateNumberOfElements)
+        }
+        Ok(self.weights(point, bounds_handling)?.iter().map(|(node,weight)| weight*values[*node]).sum())
+    }
+
+    /// Returns the coordinates of a point rescaled to the bounding box of the nodes.
+    fn rescale(&self, point:[f64;2])->[f64;2]{
+        [(point[0]-self.origin[0])/self.extent[0], (point[1]-self.origin[1])/self.extent[1]]
+    }
+
+    /// Returns the barycentric coordinates of a (rescaled) point with respect to the vertices of a triangle.
+    fn barycentric(&self, triangle:&[usize;3], point:[f64;2])->[f64;3]{
+        let [a,b,c] = triangle.map(|vertex| self.nodes[vertex]);
+        let area = cross(a, b, c);
+        let (wb,wc) = (cross(a, point, c)/area, cross(a, b, point)/area);
+        [1.0-wb-wc, wb, wc]
+    }
+
+    /// Returns the point of a triangle closest to a (rescaled) point.
+    fn closest_point(&self, triangle:&[usize;3], point:[f64;2])->[f64;2]{
+        if self.barycentric(triangle, point).iter().all(|weight| *weight >= 0.0){
+            return point
+        }
+        (0..3).map(|edge| closest_point_on_segment(self.nodes[triangle[edge]], self.nodes[triangle[(edge+1)%3]], point))
+            .min_by(|p,q| distance_squared(point, *p).total_cmp(&distance_squared(point, *q)))
+            .expect("a triangle has three edges")
+    }
 }
 
+/// Tolerance on the barycentric coordinates of the points on the edges of the convex hull of a [DelaunayInterpolator].
+const HULL_TOLERANCE:f64 = 1.0e-12;
+
+/// Returns the triangles of the Delaunay triangulation of nodes within the unit square, in counterclockwise order, with the Bowyer-Watson algorithm.
+/// Every node is added to the triangulation in turn: the triangles whose circumcircle contains it are removed,
+/// and the hole they leave is filled with triangles that join its edges to the node.
+fn delaunay_triangulation(nodes:&[[f64;2]])->Vec<[usize;3]>{
+    // A triangle far larger than the unit square contains all of the nodes at the start, its vertices follow the nodes.
+    const SUPER_TRIANGLE_SIZE:f64 = 1.0e4;
+    let n = nodes.len();
+    let mut vertices = nodes.to_vec();
+    vertices.extend([[-SUPER_TRIANGLE_SIZE,-SUPER_TRIANGLE_SIZE],[3.0*SUPER_TRIANGLE_SIZE,-SUPER_TRIANGLE_SIZE],[-SUPER_TRIANGLE_SIZE,3.0*SUPER_TRIANGLE_SIZE]]);
+    let mut triangles:Vec<[usize;3]> = vec![[n,n+1,n+2]];
+
+    let mut edges:Vec<[usize;2]> = Vec::new();
+    for node in 0..n{
+        let point = vertices[node];
+        // The hole grows from the triangles that contain the node through their neighbours, so that it's always connected
+        // even if rounding errors make the circumcircle test inconsistent for cocircular nodes, which are common on lattices.
+        let mut removed:Vec<bool> = triangles.iter()
+            .map(|triangle| (0..3).all(|edge| cross(vertices[triangle[edge]], vertices[triangle[(edge+1)%3]], point) >= -HULL_TOLERANCE))
+            .collect();
+        let mut grown = true;
+        while grown{
+            grown = false;
+            for k in 0..triangles.len(){
+                if removed[k] || !in_circumcircle(triangles[k].map(|vertex| vertices[vertex]), point){
+                    continue
+                }
+                let is_neighbour = (0..triangles.len()).any(|l| removed[l] && shares_edge(&triangles[k], &triangles[l]));
+                if is_neighbour{
+                    removed[k] = true;
+                    grown = true;
+                }
+            }
+        }
+
+        edges.clear();
+        for (triangle,_) in triangles.iter().zip(removed.iter()).filter(|(_,removed)| **removed){
+            edges.extend((0..3).map(|edge| [triangle[edge], triangle[(edge+1)%3]]));
+        }
+        let mut k = 0;
+        triangles.retain(|_| {k += 1; !removed[k-1]});
+        // The edges shared by two removed triangles are inside of the hole, the others are its boundary.
+        for edge in edges.iter(){
+            if !edges.contains(&[edge[1], edge[0]]){
+                triangles.push([edge[0], edge[1], node]);
+            }
+        }
+    }
+    triangles.retain(|triangle| triangle.iter().all(|vertex| *vertex < n) && cross(vertices[triangle[0]], vertices[triangle[1]], vertices[triangle[2]]) > HULL_TOLERANCE);
+    triangles
+}
+
+/// Returns `true` if two triangles share an edge.
+fn shares_edge(a:&[usize;3], b:&[usize;3])->bool{
+    a.iter().filter(|vertex| b.contains(vertex)).count() == 2
+}
+
+/// Returns twice the signed area of the triangle `(a,b,c)`, positive if its vertices are in counterclockwise order.
+fn cross(a:[f64;2], b:[f64;2], c:[f64;2])->f64{
+    (b[0]-a[0])*(c[1]-a[1]) - (b[1]-a[1])*(c[0]-a[0])
+}
+
+/// Returns `true` if a point lies strictly within the circumcircle of a counterclockwise triangle.
+fn in_circumcircle(triangle:[[f64;2];3], point:[f64;2])->bool{
+    let [a,b,c] = triangle.map(|vertex| [vertex[0]-point[0], vertex[1]-point[1]]);
+    let lifted = |v:[f64;2]| v[0]*v[0] + v[1]*v[1];
+    let determinant = lifted(a)*(b[0]*c[1]-c[0]*b[1]) - lifted(b)*(a[0]*c[1]-c[0]*a[1]) + lifted(c)*(a[0]*b[1]-b[0]*a[1]);
+    determinant > 0.0
+}
+
+/// Returns the point of the segment from `a` to `b` closest to `point`.
+fn closest_point_on_segment(a:[f64;2], b:[f64;2], point:[f64;2])->[f64;2]{
+    let direction = [b[0]-a[0], b[1]-a[1]];
+    let t = (((point[0]-a[0])*direction[0] + (point[1]-a[1])*direction[1])/(direction[0]*direction[0] + direction[1]*direction[1])).clamp(0.0, 1.0);
+    [a[0]+t*direction[0], a[1]+t*direction[1]]
+}
+
+fn distance_squared(a:[f64;2], b:[f64;2])->f64{
+    (a[0]-b[0]).powi(2) + (a[1]-b[1]).powi(2)
+}
+
+/// Pairs the vertices of a triangle with their weights.
+fn zip_weights(triangle:&[usize;3], weights:[f64;3])->[(usize,f64);3]{
+    [(triangle[0],weights[0]), (triangle[1],weights[1]), (triangle[2],weights[2])]
+}
+
+/// Returns the lower node of the interval of an axis that brackets `x` and the fractional distance from it.
+/// Outside of the axis the end interval is used, and the fractional distance is treated according to `bounds_handling`.
+/// ### Arguments:
+/// * `axis` - the nodes of the axis in increasing order, at least two of them.
+/// * `x` - the coordinate of the point.
+/// * `bounds_handling` - what to do if `x` lies outside of the axis, see [BoundsHandling].
+/// ### Returns:
+/// * `Ok((index,fraction))` - the lower node and `(x-axis[index])/(axis[index+1]-axis[index])`.
+/// * `Err(MathErrors::OutOfBounds)` - if `x` lies outside of the axis and `bounds_handling` is [BoundsHandling::Error].
+pub fn bracket(axis:&[f64], x:f64, bounds_handling:BoundsHandling)->Result<(usize,f64),MathErrors>{
+    let index = axis.partition_point(|node| *node <= x).saturating_sub(1).min(axis.len()-2);
+    let fraction = (x-axis[index])/(axis[index+1]-axis[index]);
+    match bounds_handling{
+        _ if (0.0..=1.0).contains(&fraction) => {Ok((index,fraction))}
+        BoundsHandling::Error => {Err(MathErrors::OutOfBounds)}
+        BoundsHandling::Clamp => {Ok((index,fraction.clamp(0.0, 1.0)))}
+        BoundsHandling::Extrapolate => {Ok((index,fraction))}
+    }
+}
 
 
 #[cfg(test)]
 mod tests {
     use ndarray::{Array3, array};
+    use assert_approx_eq::assert_approx_eq;
 
     use super::*;
     //quizas valdria la pena  pensar en utilizar un diccionario  para estas abstracciones
@@ -334,7 +774,7 @@ mod tests {
 
         let mut hypercube = SampleGrids::fill_hypercube();
 
-        hypercube.get_fractional_distances(&coordinates).unwrap();
+        hypercube.get_fractional_distances(&coordinates, BoundsHandling::Error).unwrap();
 
         //first 16 partial lineal interpolations; this are done on temperature.
         let mut d = hypercube.fractional_distances[0];
@@ -373,6 +813,202 @@ mod tests {
 
     }
 
+    #[test]
+    fn multilinear_interpolation_reproduces_multilinear_functions(){
+        // f is linear along each axis, so the interpolation must be exact. The axes have different lengths to tell them apart.
+        let f = |x:&[f64]| 1.0 + 2.0*x[0] - 3.0*x[1] + 0.5*x[2] + x[0]*x[1] - 2.0*x[1]*x[2] + 0.25*x[0]*x[1]*x[2];
+        let pairs = [[1.0,2.0],[10.0,30.0],[-1.0,4.0]];
+        let mut hypercube = ParameterSpaceHypercube::new(3);
+        hypercube.fill_coordinates(&pairs).unwrap();
+        // The first axis changes first.
+        let mut corner_values:Vec<f64> = Vec::with_capacity(8);
+        for k in 0..2usize{
+            for j in 0..2usize{
+                for i in 0..2usize{
+                    corner_values.push(f(&[pairs[0][i],pairs[1][j],pairs[2][k]]));
+                }
+            }
+        }
+        hypercube.fill_vertices_data(&corner_values).unwrap();
+        for point in [[1.25,12.0,0.5],[1.9,29.0,-0.75],[1.0,10.0,-1.0],[2.0,30.0,4.0]]{
+            assert_approx_eq!(hypercube.multilinear_interpolation(&point).unwrap(), f(&point), 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn cubic_hermite_interpolation_reproduces_quadratic_functions(){
+        let f = |x:f64| 2.0 - 3.0*x + 0.5*x*x;
+        let nodes = [1.0,2.0,3.0,4.0];
+        let values = nodes.map(f);
+        for x in [2.0,2.3,2.5,2.9,3.0]{
+            assert_approx_eq!(cubic_hermite_interpolation(&nodes, &values, 1, x, HermiteSlopes::CatmullRom), f(x), 1.0e-12);
+        }
+        // Linear functions are reproduced on any nodes, also by the monotone slopes and at the ends of the axis.
+        let g = |x:f64| 1.5 + 0.25*x;
+        let nodes = [0.0,0.3,1.2];
+        let values = nodes.map(g);
+        for slopes in [HermiteSlopes::CatmullRom,HermiteSlopes::Monotone]{
+            for (index,x) in [(0,0.1),(1,0.7),(1,1.2)]{
+                assert_approx_eq!(cubic_hermite_interpolation(&nodes, &values, index, x, slopes), g(x), 1.0e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn monotone_hermite_interpolation_does_not_overshoot(){
+        // A step: the Catmull-Rom spline overshoots it, the monotone one stays within the values.
+        let nodes = [0.0,1.0,2.0,3.0];
+        let values = [0.0,0.0,1.0,1.0];
+        let (mut overshoots,mut previous) = (false,0.0);
+        for step in 0..=20{
+            for index in 0..3usize{
+                let x = nodes[index] + step as f64/20.0;
+                overshoots |= !(0.0..=1.0).contains(&cubic_hermite_interpolation(&nodes, &values, index, x, HermiteSlopes::CatmullRom));
+            }
+            let monotone = cubic_hermite_interpolation(&nodes, &values, 1, 1.0 + step as f64/20.0, HermiteSlopes::Monotone);
+            assert!(monotone >= previous && monotone <= 1.0, "{}", monotone);
+            previous = monotone;
+        }
+        assert!(overshoots);
+        assert_eq!(cubic_hermite_interpolation(&nodes, &values, 0, 0.5, HermiteSlopes::Monotone), 0.0);
+    }
+
+    /// A regular grid with unevenly spaced axes of different lengths and the values of a multilinear function `f` at its nodes.
+    fn regular_grid(f:impl Fn(&[f64])->f64)->RegularGridInterpolator{
+        let axes = vec![vec![1.0,1.5,3.0],vec![10.0,30.0],vec![-1.0,0.0,2.0,4.0]];
+        let values = ndarray::ArrayD::from_shape_fn(vec![3,2,4], |index| f(&[axes[0][index[0]],axes[1][index[1]],axes[2][index[2]]]));
+        RegularGridInterpolator::new(axes, values).unwrap()
+    }
+
+    #[test]
+    fn regular_grid_interpolator_reproduces_multilinear_functions(){
+        let f = |x:&[f64]| 1.0 + 2.0*x[0] - 3.0*x[1] + 0.5*x[2] + x[0]*x[1] - 2.0*x[1]*x[2] + 0.25*x[0]*x[1]*x[2];
+        let interpolator = regular_grid(f);
+        assert_eq!((interpolator.dimension(),interpolator.ncomponents()),(3,1));
+        let points = array![[1.25,12.0,0.5],[2.9,29.0,-0.75],[1.0,10.0,-1.0],[3.0,30.0,4.0],[1.5,20.0,3.0]];
+        for point in points.rows(){
+            let point = point.to_vec();
+            assert_approx_eq!(interpolator.interpolate(&point, BoundsHandling::Error).unwrap()[0], f(&point), 1.0e-12);
+        }
+        // The batch evaluation gives the same values.
+        let batch = interpolator.interpolate_many(points.view(), BoundsHandling::Error).unwrap();
+        for (point,value) in points.rows().into_iter().zip(batch.column(0).iter()){
+            assert_eq!(interpolator.interpolate(&point.to_vec(), BoundsHandling::Error).unwrap()[0], *value);
+        }
+    }
+
+    #[test]
+    fn regular_grid_interpolator_interpolates_every_component(){
+        let axes = vec![vec![0.0,1.0],vec![0.0,2.0]];
+        let values = array![[[1.0,10.0],[3.0,30.0]],[[5.0,50.0],[7.0,70.0]]].into_dyn();
+        let interpolator = RegularGridInterpolator::new(axes, values).unwrap();
+        assert_eq!(interpolator.ncomponents(), 2);
+        assert_eq!(interpolator.interpolate(&[0.5,1.0], BoundsHandling::Error).unwrap(), vec![4.0,40.0]);
+        assert_eq!(interpolator.interpolate(&[1.0,0.0], BoundsHandling::Error).unwrap(), vec![5.0,50.0]);
+    }
+
+    #[test]
+    fn regular_grid_interpolator_treats_points_outside_of_the_grid(){
+        let interpolator = regular_grid(|x:&[f64]| x[0] + x[1] + x[2]);
+        assert!(!interpolator.contains(&[3.5,20.0,0.0]));
+        assert!(matches!(interpolator.interpolate(&[3.5,20.0,0.0], BoundsHandling::Error),Err(MathErrors::OutOfBounds)));
+        assert_approx_eq!(interpolator.interpolate(&[3.5,20.0,0.0], BoundsHandling::Clamp).unwrap()[0], 23.0, 1.0e-12);
+        assert_approx_eq!(interpolator.interpolate(&[3.5,20.0,-2.0], BoundsHandling::Extrapolate).unwrap()[0], 21.5, 1.0e-12);
+        assert!(matches!(interpolator.interpolate(&[1.0,20.0], BoundsHandling::Error),Err(MathErrors::NotAdequateNumberOfElements)));
+    }
+
+    #[test]
+    fn regular_grid_interpolator_checks_its_grid(){
+        let values = ndarray::ArrayD::zeros(vec![2,3]);
+        assert!(RegularGridInterpolator::new(vec![vec![0.0,1.0],vec![0.0,1.0,2.0]], values.clone()).is_ok());
+        assert!(RegularGridInterpolator::new(vec![vec![0.0,1.0],vec![0.0,1.0]], values.clone()).is_err());
+        assert!(RegularGridInterpolator::new(vec![vec![0.0,1.0],vec![0.0,2.0,1.0]], values.clone()).is_err());
+        assert!(RegularGridInterpolator::new(vec![vec![0.0,1.0,2.0]], ndarray::ArrayD::zeros(vec![3])).is_ok());
+        assert!(RegularGridInterpolator::new(vec![vec![0.0]], ndarray::ArrayD::zeros(vec![1])).is_err());
+    }
+
+    /// A 3×3 lattice on the (T_eff, log g) plane without its hot, low gravity corner.
+    fn lattice_with_a_missing_node()->Vec<[f64;2]>{
+        [20000.0,23000.0,26000.0].iter()
+            .flat_map(|t| [3.5,4.0,4.5].map(|g| [*t,g]))
+            .filter(|node| *node != [26000.0,3.5])
+            .collect()
+    }
+
+    /// Nodes scattered over [0,1]×[0,10] by a linear congruential generator.
+    fn scattered_nodes(n:usize)->Vec<[f64;2]>{
+        let mut state:u64 = 12345;
+        let mut next = || {state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407); (state >> 11) as f64/(1u64 << 53) as f64};
+        (0..n).map(|_| [next(), 10.0*next()]).collect()
+    }
+
+    #[test]
+    fn delaunay_interpolator_reproduces_linear_functions(){
+        let f = |x:[f64;2]| 3.0 + 2.0e-4*x[0] - 1.5*x[1];
+        for nodes in [lattice_with_a_missing_node(), scattered_nodes(40)]{
+            let interpolator = DelaunayInterpolator::new(&nodes).unwrap();
+            let values:Vec<f64> = nodes.iter().map(|node| f(*node)).collect();
+            for (a,b,c) in interpolator.triangles().iter().map(|triangle| (nodes[triangle[0]],nodes[triangle[1]],nodes[triangle[2]])){
+                let centroid = [(a[0]+b[0]+c[0])/3.0, (a[1]+b[1]+c[1])/3.0];
+                assert_approx_eq!(interpolator.interpolate(centroid, &values, BoundsHandling::Error).unwrap(), f(centroid), 1.0e-9);
+                let weights = interpolator.weights(centroid, BoundsHandling::Error).unwrap();
+                assert!(weights.iter().all(|(_,weight)| *weight > 0.0));
+                assert_approx_eq!(weights.iter().map(|(_,weight)| weight).sum::<f64>(), 1.0, 1.0e-12);
+            }
+            // At the nodes the interpolation gives their values.
+            for (node,value) in nodes.iter().zip(values.iter()){
+                assert_approx_eq!(interpolator.interpolate(*node, &values, BoundsHandling::Error).unwrap(), *value, 1.0e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn delaunay_triangulation_fills_the_convex_hull(){
+        let nodes = lattice_with_a_missing_node();
+        let interpolator = DelaunayInterpolator::new(&nodes).unwrap();
+        // The hull is the 6000 K × 1 dex box without the half of the missing corner cell.
+        let area:f64 = interpolator.triangles().iter().map(|triangle| 0.5*cross(nodes[triangle[0]], nodes[triangle[1]], nodes[triangle[2]])).sum();
+        assert_approx_eq!(area, 6000.0 - 0.5*3000.0*0.5, 1.0e-6);
+        assert_eq!(interpolator.triangles().len(), 7);
+
+        // No node lies within the circumcircle of a triangle, in the rescaled coordinates.
+        let nodes = scattered_nodes(40);
+        let interpolator = DelaunayInterpolator::new(&nodes).unwrap();
+        for triangle in interpolator.triangles(){
+            assert!(cross(nodes[triangle[0]], nodes[triangle[1]], nodes[triangle[2]]) > 0.0);
+            let vertices = triangle.map(|vertex| interpolator.nodes[vertex]);
+            assert!(interpolator.nodes.iter().all(|node| !in_circumcircle(vertices, *node) || vertices.contains(node)));
+        }
+    }
+
+    #[test]
+    fn delaunay_interpolator_treats_points_outside_of_the_hull(){
+        let nodes = lattice_with_a_missing_node();
+        let interpolator = DelaunayInterpolator::new(&nodes).unwrap();
+        let f = |x:[f64;2]| 1.0e-3*x[0] + 2.0*x[1];
+        let values:Vec<f64> = nodes.iter().map(|node| f(*node)).collect();
+        // Within the missing corner, beyond the diagonal from (23000, 3.5) to (26000, 4.0).
+        let point = [25500.0,3.6];
+        assert!(!interpolator.contains(point));
+        assert!(interpolator.contains([24500.0,3.75]));
+        assert!(matches!(interpolator.interpolate(point, &values, BoundsHandling::Error),Err(MathErrors::OutOfBounds)));
+        assert_approx_eq!(interpolator.interpolate(point, &values, BoundsHandling::Extrapolate).unwrap(), f(point), 1.0e-9);
+        // The point is moved to the diagonal, in the rescaled coordinates where it goes from (0.5, 0) to (1, 0.5).
+        let clamped = interpolator.interpolate(point, &values, BoundsHandling::Clamp).unwrap();
+        let on_diagonal = [(point[0]-20000.0)/6000.0, point[1]-3.5];
+        let t = 0.5*(on_diagonal[0] + on_diagonal[1] - 0.5);
+        assert_approx_eq!(clamped, f([20000.0 + 6000.0*(0.5+t), 3.5 + t]), 1.0e-9);
+        assert!(matches!(interpolator.interpolate(point, &values[1..], BoundsHandling::Clamp),Err(MathErrors::NotAdequateNumberOfElements)));
+    }
+
+    #[test]
+    fn delaunay_interpolator_needs_nodes_off_a_line(){
+        assert!(DelaunayInterpolator::new(&[[0.0,0.0],[1.0,0.0]]).is_err());
+        assert!(DelaunayInterpolator::new(&[[0.0,0.0],[1.0,1.0],[2.0,2.0],[3.0,3.0]]).is_err());
+        assert!(DelaunayInterpolator::new(&[[0.0,0.0],[1.0,0.0],[0.0,1.0],[1.0,0.0]]).is_err());
+        assert!(DelaunayInterpolator::new(&[[0.0,0.0],[1.0,0.0],[0.0,1.0]]).is_ok());
+    }
+
     #[test]
     fn multilinear_interpolation_works_on_1d(){
         let point_a=1.0;
@@ -395,9 +1031,40 @@ mod tests {
         assert_eq!(linear_interpolation(&corner_values, fractional_distance),hypercube.multilinear_interpolation(&[point_b]).unwrap())
     }
 
-}
-
+    fn hypercube_1d()->ParameterSpaceHypercube{
+        let mut hypercube = ParameterSpaceHypercube::new(1);
+        hypercube.fill_coordinates(&[[1.0,7.0]]).unwrap();
+        hypercube.fill_vertices_data(&[5.0,18.0]).unwrap();
+        hypercube
+    }
 
+    #[test]
+    fn out_of_bounds_is_an_error_by_default(){
+        let mut hypercube = hypercube_1d();
+        assert!(matches!(hypercube.multilinear_interpolation(&[8.0]),Err(MathErrors::OutOfBounds)));
+        assert!(matches!(hypercube.multilinear_interpolation_with(&[0.0],BoundsHandling::Error),Err(MathErrors::OutOfBounds)));
+    }
 
+    #[test]
+    fn out_of_bounds_clamps_to_the_edges(){
+        let mut hypercube = hypercube_1d();
+        let left_edge = hypercube.multilinear_interpolation(&[1.0]).unwrap();
+        let right_edge = hypercube.multilinear_interpolation(&[7.0]).unwrap();
+        assert_eq!(hypercube.multilinear_interpolation_with(&[-3.0],BoundsHandling::Clamp).unwrap(),left_edge);
+        assert_eq!(hypercube.multilinear_interpolation_with(&[10.0],BoundsHandling::Clamp).unwrap(),right_edge);
+    }
 
+    #[test]
+    fn out_of_bounds_extrapolates_linearly(){
+        let mut hypercube = hypercube_1d();
+        // The interpolant is a straight line, so the value one step beyond the edge must continue it.
+        let at_1 = hypercube.multilinear_interpolation(&[1.0]).unwrap();
+        let at_4 = hypercube.multilinear_interpolation(&[4.0]).unwrap();
+        let at_7 = hypercube.multilinear_interpolation(&[7.0]).unwrap();
+        let at_10 = hypercube.multilinear_interpolation_with(&[10.0],BoundsHandling::Extrapolate).unwrap();
+        let at_minus_2 = hypercube.multilinear_interpolation_with(&[-2.0],BoundsHandling::Extrapolate).unwrap();
+        assert_approx_eq!(at_10 - at_7, at_7 - at_4);
+        assert_approx_eq!(at_1 - at_minus_2, at_4 - at_1);
+    }
 
+}
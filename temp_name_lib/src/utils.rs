@@ -8,4 +8,20 @@ pub enum MathErrors{
     NotAdequateNumberOfElements,
 }
 
-pub const MACHINE_PRECISION:f64 = 1.0e-8;
\ No newline at end of file
+impl std::fmt::Display for MathErrors{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self{
+            MathErrors::DivisionByZero => "division by zero",
+            MathErrors::CosineBiggerThanOne => "cosine bigger than one",
+            MathErrors::VectorLengthZero => "vector of length zero",
+            MathErrors::DifferentVectorBase => "vectors expressed on different bases",
+            MathErrors::OutOfBounds => "value out of bounds",
+            MathErrors::NotAdequateNumberOfElements => "not an adequate number of elements",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for MathErrors{}
+
+pub const MACHINE_PRECISION:f64 = 1.0e-8;